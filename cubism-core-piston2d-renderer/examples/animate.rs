@@ -1,4 +1,8 @@
-use cubism::{expression::Expression, motion::Motion};
+use cubism::{
+    expression::Expression,
+    matrix::{ModelMatrix, ViewMatrix},
+    motion::Motion,
+};
 use cubism_core as core;
 use cubism_core_piston2d_renderer::*;
 use glium::backend::Facade;
@@ -87,6 +91,10 @@ fn main() {
     motion.play();
     motion.set_looped(false); // just for cosmetic...
 
+    // the model matrix spans the full height of the window with the canvas
+    let model_matrix = ModelMatrix::from_canvas_info(haru.canvas_info());
+    let mut view_matrix = ViewMatrix::new(width as f32, height as f32);
+
     // initialize renderer
     let mut renderer = Renderer::new();
 
@@ -96,7 +104,9 @@ fn main() {
         if let Some(v) = e.render_args() {
             use graphics::*;
 
-            let viewport = v.draw_size;
+            let [view_width, view_height] = v.window_size;
+            view_matrix.resize(view_width as f32, view_height as f32);
+            let mvp = view_matrix.mvp(&model_matrix);
             let mut target = window.draw();
 
             motion.update(&mut haru);
@@ -104,13 +114,9 @@ fn main() {
 
             haru.update();
 
-            g2d.draw(&mut target, v.viewport(), |c, g| {
-                let t = c
-                    .transform
-                    .trans(viewport[0] as f64 * 0.5, viewport[1] as f64 * 0.5)
-                    .scale(0.2, 0.2);
+            g2d.draw(&mut target, v.viewport(), |_, g| {
                 clear([1.0, 1.0, 1.0, 1.0], g);
-                renderer.draw_model(g, t, &haru, &textures);
+                renderer.draw_model_mvp(g, mvp.as_array(), &haru, &textures);
             });
 
             target.finish().unwrap();
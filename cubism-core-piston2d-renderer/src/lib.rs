@@ -104,6 +104,43 @@ impl Renderer {
     ) where
         G: Graphics<Texture = T>,
         T: ImageSize,
+    {
+        use graphics::Transformed;
+
+        // vertex positions are in canvas units with the y axis pointing up
+        let (_, _, ppu) = model.canvas_info();
+        let ppu = f64::from(ppu);
+        self.draw_model_units(g, transform.scale(ppu, -ppu), model, textures);
+    }
+
+    /// Draws a model with a column-major 4x4 matrix transforming the model's
+    /// canvas units into clip space, like the one returned by `ViewMatrix::mvp`
+    /// of the cubism crate. Only the 2d part of the matrix is used.
+    pub fn draw_model_mvp<G, T>(
+        &mut self,
+        g: &mut G,
+        mvp: &[f32; 16],
+        model: &Model,
+        textures: &[T],
+    ) where
+        G: Graphics<Texture = T>,
+        T: ImageSize,
+    {
+        let m = |i: usize| f64::from(mvp[i]);
+        let transform = [[m(0), m(4), m(12)], [m(1), m(5), m(13)]];
+        self.draw_model_units(g, transform, model, textures);
+    }
+
+    // Draws the model with a transform from canvas units into clip space.
+    fn draw_model_units<G, T>(
+        &mut self,
+        g: &mut G,
+        transform: Matrix2d,
+        model: &Model,
+        textures: &[T],
+    ) where
+        G: Graphics<Texture = T>,
+        T: ImageSize,
     {
         let mut sorted_draw_indices = vec![0; model.drawable_count()];
 
@@ -174,9 +211,6 @@ impl Renderer {
 
         let draw_state = &draw_state;
 
-        // obtain pixel-per-unit information
-        let (_, _, ppu) = model.canvas_info();
-
         let vtx_pos = model.drawable_vertex_positions(index);
         let vtx_uv = model.drawable_vertex_uvs(index);
         let idx_buffer = model.drawable_indices()[index];
@@ -193,7 +227,7 @@ impl Renderer {
             let i = usize::from(*i);
 
             let [x, y] = vtx_pos[i];
-            let (x, y) = (f64::from(x * ppu), f64::from(-y * ppu));
+            let (x, y) = (f64::from(x), f64::from(y));
 
             let tp = [tx(transform, x, y), ty(transform, x, y)];
             pos.push(tp);
@@ -1,87 +1,184 @@
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
-fn lookup_cubism_core(arch: &str, vendor: &str, sys: &str, abi: &str, linking_strategy: &str) {
-    let mut lib_dir = if let Ok(lib_dir) = env::var("CUBISM_CORE").map(PathBuf::from) {
-        lib_dir
-    } else {
-        // TODO: this won't appear unless the compilation fails.
-        println!(
-            "cargo:warning=it seems that the CUBISM_CORE environment variable is not set. \
-             Please set it to your Live2DCubismCore directory before compiling, \
-             or specify the Live2DCubismCore library manually. \
-             Check out https://github.com/Veykril/cubism-rs for more information."
-        );
-        return;
-    };
+const LIB_NAME: &str = "Live2DCubismCore";
 
-    if linking_strategy == "static" {
-        lib_dir.push("Core/lib");
-    } else {
-        lib_dir.push("Core/dll");
-    }
+struct Target<'a> {
+    arch: &'a str,
+    vendor: &'a str,
+    sys: &'a str,
+    abi: &'a str,
+}
 
-    match (vendor, sys) {
-        ("pc", "windows") => {
-            lib_dir.push("windows");
-            lib_dir.push(match arch {
-                "x86_64" => "x86_64",
-                "i686" => "x86",
-                _ => panic!("unknown windows architecture: {}", arch),
-            });
-            lib_dir.push("140");
+// Returns the candidate directories of the library inside of the official
+// sdk for the target, relative to the `Core/lib` or `Core/dll` directory.
+fn sdk_layouts(target: &Target, linking_strategy: &str) -> Result<Vec<PathBuf>, String> {
+    let Target {
+        arch,
+        vendor,
+        sys,
+        abi,
+    } = *target;
+    let dirs: &[&str] = match (vendor, sys) {
+        ("pc", "windows") => match arch {
+            "x86_64" => &["windows/x86_64/140"],
+            "i686" => &["windows/x86/140"],
+            _ => return Err(format!("unknown windows architecture: {}", arch)),
         },
         ("apple", "darwin") => {
             if linking_strategy != "static" {
-                panic!(
+                return Err(
                     "since Live2DCubismCore is in MH_BUNDLE format (which is deprecated), \
-                     dynamic linking on macOS is not supported. \
-                     See https://github.com/Veykril/cubism-rs for more information."
+                            dynamic linking on macOS is not supported. \
+                            See https://github.com/Veykril/cubism-rs for more information."
+                        .to_owned(),
                 );
             }
-            if arch == "i686" {
-                panic!("no 32-bit support for macOS.");
+            match arch {
+                "x86_64" | "aarch64" => &["macos"],
+                _ => return Err(format!("unsupported macOS architecture: {}", arch)),
             }
-
-            lib_dir.push("macos");
         },
-        ("apple", "ios") => {
-            unimplemented!("TODO: implement ios linking");
+        ("apple", "ios") => return Err("linking on ios is not implemented yet".to_owned()),
+        ("unknown", "linux") => match arch {
+            "x86_64" => &["linux/x86_64"],
+            "aarch64" => &["linux/arm64", "linux/aarch64"],
+            "armv7" => &["linux/armv7l", "linux/armv7", "experimental/rpi"],
+            _ => return Err(format!("unsupported linux architecture: {}", arch)),
+        },
+        ("linux", "android") | ("linux", "androideabi") => match arch {
+            "i686" => &["android/x86"],
+            "x86_64" => &["android/x86_64"],
+            "armv7" => &["android/armeabi-v7a"],
+            "aarch64" => &["android/arm64-v8a"],
+            _ => return Err(format!("unsupported android architecture: {}", arch)),
         },
-        ("unknown", "linux") => {
-            lib_dir.push("linux");
-            lib_dir.push(match arch {
-                "x86_64" => "x86_64",
-                _ => panic!("linux is only supported on x86_64"),
-            });
+        _ => {
+            return Err(format!(
+                "unsupported target triple: {}-{}-{}-{}",
+                arch, vendor, sys, abi
+            ))
         },
-        ("linux", "android") | ("linux", "androideabi") => {
-            lib_dir.push("android");
-            lib_dir.push(match arch {
-                "i686" => "x86",
-                "armv7" => "armeabi-v7a",
-                "aarch64" => "arm64-v8a",
-                _ => panic!("unsupported android architecture: {}", arch),
-            });
+    };
+    Ok(dirs.iter().map(PathBuf::from).collect())
+}
+
+// Looks the library up in the sdk directory set by `CUBISM_CORE`.
+fn lookup_sdk(sdk_dir: &Path, target: &Target, linking_strategy: &str) -> Result<PathBuf, String> {
+    let core_dir = if linking_strategy == "static" {
+        sdk_dir.join("Core/lib")
+    } else {
+        sdk_dir.join("Core/dll")
+    };
+    let candidates: Vec<_> = sdk_layouts(target, linking_strategy)?
+        .into_iter()
+        .map(|dir| core_dir.join(dir))
+        .collect();
+    candidates
+        .iter()
+        .find(|dir| dir.is_dir())
+        .cloned()
+        .ok_or_else(|| {
+            let tried: Vec<_> = candidates
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect();
+            format!(
+                "the Live2DCubismCore library wasn't found in CUBISM_CORE, tried {}",
+                tried.join(", ")
+            )
+        })
+}
+
+fn lookup_pkg_config(linking_strategy: &str) -> Result<(), String> {
+    pkg_config::Config::new()
+        .statik(linking_strategy == "static")
+        .probe(LIB_NAME)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn default_lib_name(target: &Target) -> &'static str {
+    let profile = env::var("PROFILE").unwrap_or_default();
+    match (target.vendor, target.sys, &*profile) {
+        ("pc", "windows", "debug") => "Live2DCubismCore_MTd",
+        ("pc", "windows", _) => "Live2DCubismCore_MT",
+        _ => LIB_NAME,
+    }
+}
+
+fn lookup_cubism_core(target: &Target, linking_strategy: &str) -> Result<(), String> {
+    let lib_name =
+        env::var("CUBISM_CORE_LIB_NAME").unwrap_or_else(|_| default_lib_name(target).to_owned());
+
+    // An explicitly given directory always wins.
+    if let Ok(lib_dir) = env::var("CUBISM_CORE_LIB_DIR") {
+        let lib_dir = PathBuf::from(lib_dir);
+        if !lib_dir.is_dir() {
+            return Err(format!(
+                "CUBISM_CORE_LIB_DIR is set to {}, which is not a directory",
+                lib_dir.display()
+            ));
+        }
+        println!("cargo:rustc-link-search=all={}", lib_dir.display());
+        println!("cargo:rustc-link-lib={}={}", linking_strategy, lib_name);
+        return Ok(());
+    }
+
+    let mut errors = Vec::new();
+    match env::var("CUBISM_CORE").map(PathBuf::from) {
+        Ok(sdk_dir) => match lookup_sdk(&sdk_dir, target, linking_strategy) {
+            Ok(lib_dir) => {
+                println!("cargo:rustc-link-search=all={}", lib_dir.display());
+                println!("cargo:rustc-link-lib={}={}", linking_strategy, lib_name);
+                return Ok(());
+            },
+            Err(e) => errors.push(e),
         },
-        _ => panic!(
-            "unsupported target triple: {}-{}-{}-{}",
-            arch, vendor, sys, abi
-        ),
+        Err(_) => errors.push("the CUBISM_CORE environment variable is not set".to_owned()),
+    }
+
+    // Distribution packages may ship a pkg-config file instead.
+    match lookup_pkg_config(linking_strategy) {
+        Ok(()) => return Ok(()),
+        Err(e) => errors.push(format!("pkg-config: {}", e)),
     }
-    println!("cargo:rustc-link-search=all={}", lib_dir.display());
+
+    let message = format!(
+        "could not find the Live2DCubismCore library:\n  - {}\n\
+         Set CUBISM_CORE to your Cubism SDK directory, or CUBISM_CORE_LIB_DIR (and \
+         optionally CUBISM_CORE_LIB_NAME) to the directory containing the library. \
+         Check out https://github.com/Veykril/cubism-rs for more information.",
+        errors.join("\n  - ")
+    );
+    if linking_strategy == "static" {
+        return Err(message);
+    }
+    // The dynamic library may still be found in the system library paths.
+    for line in message.lines() {
+        println!("cargo:warning={}", line);
+    }
+    println!("cargo:rustc-link-lib={}={}", linking_strategy, lib_name);
+    Ok(())
 }
 
 fn main() {
     println!("cargo:rerun-if-env-changed=CUBISM_CORE");
+    println!("cargo:rerun-if-env-changed=CUBISM_CORE_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=CUBISM_CORE_LIB_NAME");
+    if cfg!(feature = "dynamic-load") {
+        // the library is loaded at runtime, there is nothing to link
+        return;
+    }
     let target = env::var("TARGET").unwrap();
-    let (arch, vendor, sys, abi) = {
-        let mut target_s = target.split('-');
-        (
-            target_s.next().unwrap_or(""),
-            target_s.next().unwrap_or(""),
-            target_s.next().unwrap_or(""),
-            target_s.next().unwrap_or(""),
-        )
+    let mut target_s = target.split('-');
+    let target = Target {
+        arch: target_s.next().unwrap_or(""),
+        vendor: target_s.next().unwrap_or(""),
+        sys: target_s.next().unwrap_or(""),
+        abi: target_s.next().unwrap_or(""),
     };
 
     let linking_strategy = if cfg!(feature = "static-link") {
@@ -90,19 +187,7 @@ fn main() {
         "dylib"
     };
 
-    lookup_cubism_core(arch, vendor, sys, abi, linking_strategy);
-
-    let profile = env::var("PROFILE").unwrap_or_default();
-
-    match (vendor, sys, &*profile) {
-        ("pc", "windows", "debug") => println!(
-            "cargo:rustc-link-lib={}=Live2DCubismCore_MTd",
-            linking_strategy
-        ),
-        ("pc", "windows", "release") => println!(
-            "cargo:rustc-link-lib={}=Live2DCubismCore_MT",
-            linking_strategy
-        ),
-        _ => println!("cargo:rustc-link-lib={}=Live2DCubismCore", linking_strategy),
+    if let Err(e) = lookup_cubism_core(&target, linking_strategy) {
+        panic!("{}", e);
     }
 }
@@ -0,0 +1,122 @@
+//! Loading of the core library at runtime.
+use std::{
+    error,
+    ffi::OsStr,
+    fmt,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+use libloading::Library;
+
+// The loaded library, kept alive for the rest of the program.
+static LIBRARY: Mutex<Option<Library>> = Mutex::new(None);
+
+/// An error returned when loading the core library fails.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The library couldn't be opened.
+    Open(libloading::Error),
+    /// The library doesn't export the listed functions.
+    MissingSymbols(Vec<&'static str>),
+    /// A library has already been loaded.
+    AlreadyLoaded,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Open(e) => write!(fmt, "failed to open the cubism core library: {}", e),
+            LoadError::MissingSymbols(symbols) => write!(
+                fmt,
+                "the cubism core library is missing the functions {}",
+                symbols.join(", ")
+            ),
+            LoadError::AlreadyLoaded => write!(fmt, "the cubism core library is already loaded"),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LoadError::Open(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The platform specific file name of the core library, for example
+/// `libLive2DCubismCore.so` on linux.
+pub fn library_filename() -> std::ffi::OsString {
+    libloading::library_filename("Live2DCubismCore")
+}
+
+/// Loads the core library at the path and resolves all of its functions.
+/// This has to be called once before any other function of this crate.
+///
+/// Functions only present in newer versions of the library may be missing,
+/// calling them panics.
+pub fn load<P: AsRef<OsStr>>(path: P) -> Result<(), LoadError> {
+    let mut guard = LIBRARY.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_some() {
+        return Err(LoadError::AlreadyLoaded);
+    }
+    let library = unsafe { Library::new(path.as_ref()) }.map_err(LoadError::Open)?;
+    let mut missing = Vec::new();
+    crate::symbols::load(&library, &mut missing);
+    crate::moc::symbols::load(&library, &mut missing);
+    crate::model::symbols::load(&library, &mut missing);
+    if !missing.is_empty() {
+        crate::symbols::unload();
+        crate::moc::symbols::unload();
+        crate::model::symbols::unload();
+        return Err(LoadError::MissingSymbols(missing));
+    }
+    *guard = Some(library);
+    Ok(())
+}
+
+/// Loads the core library by its platform specific name from the default
+/// library search paths.
+pub fn load_default() -> Result<(), LoadError> {
+    load(library_filename())
+}
+
+/// Returns whether the core library has been loaded.
+pub fn is_loaded() -> bool {
+    LIBRARY.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+// Resolves the symbol into the pointer, returning whether it exists.
+pub(crate) fn resolve(library: &Library, name: &str, ptr: &AtomicPtr<()>) -> bool {
+    let mut symbol = Vec::with_capacity(name.len() + 1);
+    symbol.extend_from_slice(name.as_bytes());
+    symbol.push(0);
+    match unsafe { library.get::<*mut ()>(&symbol) } {
+        Ok(f) => {
+            ptr.store(*f, Ordering::Release);
+            true
+        },
+        Err(_) => false,
+    }
+}
+
+// Returns the resolved pointer of the function, panicking if the library
+// hasn't been loaded or doesn't export it.
+pub(crate) fn symbol(ptr: &AtomicPtr<()>, name: &str) -> *mut () {
+    let f = ptr.load(Ordering::Acquire);
+    if f.is_null() {
+        if is_loaded() {
+            panic!("the loaded cubism core library doesn't export {}", name)
+        } else {
+            panic!(
+                "{} called before the cubism core library has been loaded",
+                name
+            )
+        }
+    }
+    f
+}
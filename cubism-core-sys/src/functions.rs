@@ -0,0 +1,106 @@
+// Declares the functions of the core library. They are linked directly by
+// default, with the `dynamic-load` feature they are resolved at runtime by
+// `dynamic::load` instead, with every function panicking if it is called
+// before the library has been loaded.
+//
+// Functions in the `required` section have to exist in every supported
+// version of the library, the ones in the `optional` sections only exist in
+// newer versions and may stay unresolved. A linked library only has to provide
+// the optional functions whose feature is enabled, without it they are
+// declared as functions that panic when called, so that older libraries still
+// link. The wrapping crate checks the features before calling them.
+#[cfg(not(feature = "dynamic-load"))]
+macro_rules! csm_functions {
+    (
+        mod $symbols:ident;
+        required {
+            $(pub fn $req:ident($($req_arg:ident: $req_ty:ty),* $(,)?) $(-> $req_ret:ty)?;)*
+        }
+        $(optional($since:literal, $feature:literal) {
+            $(pub fn $opt:ident($($opt_arg:ident: $opt_ty:ty),* $(,)?) $(-> $opt_ret:ty)?;)*
+        })*
+    ) => {
+        extern "C" {
+            $(pub fn $req($($req_arg: $req_ty),*) $(-> $req_ret)?;)*
+        }
+        $(
+            #[cfg(feature = $feature)]
+            extern "C" {
+                $(
+                    #[doc = concat!(
+                        "Available since Cubism Core ", $since, ", linked with the `",
+                        $feature, "` feature."
+                    )]
+                    pub fn $opt($($opt_arg: $opt_ty),*) $(-> $opt_ret)?;
+                )*
+            }
+            $(
+                #[cfg(not(feature = $feature))]
+                #[doc = concat!(
+                    "Available since Cubism Core ", $since, ", panics as the `", $feature,
+                    "` feature is disabled."
+                )]
+                pub unsafe fn $opt($($opt_arg: $opt_ty),*) $(-> $opt_ret)? {
+                    $(let _ = $opt_arg;)*
+                    panic!(concat!(
+                        stringify!($opt), " isn't linked, enable the `", $feature, "` feature"
+                    ))
+                }
+            )*
+        )*
+    };
+}
+
+#[cfg(feature = "dynamic-load")]
+macro_rules! csm_functions {
+    (
+        mod $symbols:ident;
+        required {
+            $(pub fn $req:ident($($req_arg:ident: $req_ty:ty),* $(,)?) $(-> $req_ret:ty)?;)*
+        }
+        $(optional($since:literal, $feature:literal) {
+            $(pub fn $opt:ident($($opt_arg:ident: $opt_ty:ty),* $(,)?) $(-> $opt_ret:ty)?;)*
+        })*
+    ) => {
+        pub(crate) mod $symbols {
+            use std::sync::atomic::AtomicPtr;
+
+            $(pub static $req: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());)*
+            $($(pub static $opt: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());)*)*
+
+            pub fn load(library: &libloading::Library, missing: &mut Vec<&'static str>) {
+                $(
+                    if !crate::dynamic::resolve(library, stringify!($req), &$req) {
+                        missing.push(stringify!($req));
+                    }
+                )*
+                $($(
+                    crate::dynamic::resolve(library, stringify!($opt), &$opt);
+                )*)*
+            }
+
+            pub fn unload() {
+                $($req.store(std::ptr::null_mut(), std::sync::atomic::Ordering::Release);)*
+                $($($opt.store(std::ptr::null_mut(), std::sync::atomic::Ordering::Release);)*)*
+            }
+        }
+
+        $(
+            pub unsafe fn $req($($req_arg: $req_ty),*) $(-> $req_ret)? {
+                let f: unsafe extern "C" fn($($req_ty),*) $(-> $req_ret)? = std::mem::transmute(
+                    crate::dynamic::symbol(&$symbols::$req, stringify!($req)),
+                );
+                f($($req_arg),*)
+            }
+        )*
+        $($(
+            #[doc = concat!("Available since Cubism Core ", $since, ".")]
+            pub unsafe fn $opt($($opt_arg: $opt_ty),*) $(-> $opt_ret)? {
+                let f: unsafe extern "C" fn($($opt_ty),*) $(-> $opt_ret)? = std::mem::transmute(
+                    crate::dynamic::symbol(&$symbols::$opt, stringify!($opt)),
+                );
+                f($($opt_arg),*)
+            }
+        )*)*
+    };
+}
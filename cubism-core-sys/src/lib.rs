@@ -1,6 +1,11 @@
 #![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
-#![allow(clippy::identity_op)]
+#![allow(clippy::identity_op, clippy::missing_safety_doc)]
 
+#[macro_use]
+mod functions;
+
+#[cfg(feature = "dynamic-load")]
+pub mod dynamic;
 pub mod moc;
 pub mod model;
 
@@ -17,13 +22,22 @@ pub struct csmVector2 {
     pub y: c_float,
 }
 
-extern "C" {
-    pub fn csmGetVersion() -> csmVersion;
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct csmVector4 {
+    pub x: c_float,
+    pub y: c_float,
+    pub z: c_float,
+    pub w: c_float,
 }
 
 pub type csmLogFunction = Option<unsafe extern "C" fn(message: *const c_char)>;
 
-extern "C" {
-    pub fn csmGetLogFunction() -> csmLogFunction;
-    pub fn csmSetLogFunction(handler: csmLogFunction);
+csm_functions! {
+    mod symbols;
+    required {
+        pub fn csmGetVersion() -> csmVersion;
+        pub fn csmGetLogFunction() -> csmLogFunction;
+        pub fn csmSetLogFunction(handler: csmLogFunction);
+    }
 }
@@ -1,4 +1,4 @@
-use std::os::raw::{c_uint, c_void};
+use std::os::raw::{c_int, c_uint, c_void};
 
 pub const csmAlignofMoc: usize = 64;
 
@@ -6,6 +6,8 @@ pub const csmMocVersion_Unknown: csmMocVersion = 0;
 pub const csmMocVersion_30: csmMocVersion = 1;
 pub const csmMocVersion_33: csmMocVersion = 2;
 pub const csmMocVersion_40: csmMocVersion = 3;
+pub const csmMocVersion_42: csmMocVersion = 4;
+pub const csmMocVersion_50: csmMocVersion = 5;
 
 pub type csmMocVersion = c_uint;
 
@@ -15,10 +17,16 @@ pub struct csmMoc {
     _unused: [u64; 0],
 }
 
-extern "C" {
-    pub fn csmGetLatestMocVersion() -> csmMocVersion;
-    pub fn csmGetMocVersion(address: *const c_void, size: c_uint) -> csmMocVersion;
-    pub fn csmReviveMocInPlace(aligned_address: *mut c_void, size: c_uint) -> *mut csmMoc;
+csm_functions! {
+    mod symbols;
+    required {
+        pub fn csmGetLatestMocVersion() -> csmMocVersion;
+        pub fn csmGetMocVersion(address: *const c_void, size: c_uint) -> csmMocVersion;
+        pub fn csmReviveMocInPlace(aligned_address: *mut c_void, size: c_uint) -> *mut csmMoc;
+    }
+    optional("4.2", "core-4-2") {
+        pub fn csmHasMocConsistency(aligned_address: *mut c_void, size: c_uint) -> c_int;
+    }
 }
 
 #[test]
@@ -6,6 +6,20 @@ struct Vertex {
     uv: [f32; 2],
 }
 
+const IDENTITY: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+// Flips the y axis of the matrix's output, as the y axis of wgpu's clip space
+// points down.
+fn clip_space_matrix(mvp: &[f32; 16]) -> [f32; 16] {
+    let mut matrix = *mvp;
+    for y in matrix.iter_mut().skip(1).step_by(4) {
+        *y = -*y;
+    }
+    matrix
+}
+
 struct BoundTexture {
     bind_group: wgpu::BindGroup,
 }
@@ -108,9 +122,7 @@ impl Renderer {
                 16,
                 wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
             )
-            .fill_from_slice(&[
-                1.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
-            ]);
+            .fill_from_slice(&clip_space_matrix(&IDENTITY));
         let uniform_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             bindings: &[BindGroupLayoutBinding {
                 binding: 0,
@@ -239,6 +251,16 @@ impl Renderer {
         }
     }
 
+    /// Sets the column-major 4x4 matrix transforming the model's canvas units
+    /// into clip space with the y axis pointing up, like the one returned by
+    /// `ViewMatrix::mvp` of the cubism crate.
+    pub fn set_mvp(&mut self, device: &Device, encoder: &mut CommandEncoder, mvp: &[f32; 16]) {
+        let staging = device
+            .create_buffer_mapped::<f32>(16, wgpu::BufferUsage::COPY_SRC)
+            .fill_from_slice(&clip_space_matrix(mvp));
+        encoder.copy_buffer_to_buffer(&staging, 0, &self.uniform_buffer, 0, 64);
+    }
+
     /// Draws a model.
     pub fn draw_model(
         &mut self,
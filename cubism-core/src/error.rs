@@ -13,6 +13,13 @@ pub enum MocError {
     MocVersionMismatch(u32),
     /// The moc data passed is not a valid moc file.
     InvalidMocData,
+    /// The moc data failed the consistency check, it is malformed and could
+    /// crash the native library if it were loaded.
+    InconsistentMocData,
+    /// The consistency of the moc data has been requested to be checked, but
+    /// the linked library can't do that. It is older than Cubism Core 4.2 or
+    /// has been linked without the `core-4-2` feature.
+    ConsistencyUnsupported,
 }
 
 impl error::Error for MocError {}
@@ -26,6 +33,42 @@ impl fmt::Display for MocError {
                 v
             ),
             MocError::InvalidMocData => write!(fmt, "the moc data is invalid"),
+            MocError::InconsistentMocData => {
+                write!(fmt, "the moc data failed the consistency check")
+            },
+            MocError::ConsistencyUnsupported => write!(
+                fmt,
+                "the cubism core lib can't check the consistency of the moc data"
+            ),
+        }
+    }
+}
+
+/// The result type of reading moc3 metadata.
+pub type Moc3Result<T> = std::result::Result<T, Moc3Error>;
+
+/// An error returned by [`MocMetadata::from_bytes`].
+///
+/// [`MocMetadata::from_bytes`]: ../struct.MocMetadata.html#method.from_bytes
+#[derive(Copy, Clone, Debug)]
+pub enum Moc3Error {
+    /// The data doesn't start with the moc3 magic bytes.
+    InvalidMagic,
+    /// The header contains a version that isn't a moc3 version.
+    InvalidVersion(u8),
+    /// The data ends before the named part of the file does, or the part
+    /// points outside of the data.
+    Truncated(&'static str),
+}
+
+impl error::Error for Moc3Error {}
+
+impl fmt::Display for Moc3Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Moc3Error::InvalidMagic => write!(fmt, "the data is not a moc3 file"),
+            Moc3Error::InvalidVersion(v) => write!(fmt, "the moc3 version {} is invalid", v),
+            Moc3Error::Truncated(what) => write!(fmt, "the moc3 data is truncated in the {}", what),
         }
     }
 }
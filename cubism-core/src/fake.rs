@@ -0,0 +1,486 @@
+use crate::{
+    hierarchy::PartTree, index::DrawableIndex, moc::fit_value, ConstantFlags, DynamicFlags,
+};
+
+/// A declarative description of a model, which can be simulated in pure rust
+/// with [`Model::from_description`] without the native library.
+///
+/// This is meant for testing code built on top of models. The simulation is
+/// deliberately simple: the vertices of a drawable move linearly with the
+/// parameters it has offsets for, and the opacity of a drawable is its own
+/// opacity multiplied with the effective opacity of its part.
+///
+/// [`Model::from_description`]: ./struct.Model.html#method.from_description
+#[derive(Clone, Debug)]
+pub struct ModelDescription {
+    /// The size of the canvas in pixels.
+    pub canvas_size: [f32; 2],
+    /// The origin of the canvas in pixels.
+    pub canvas_origin: [f32; 2],
+    /// The number of pixels per unit.
+    pub pixels_per_unit: f32,
+    /// The parameters of the model.
+    pub parameters: Vec<ParameterDescription>,
+    /// The parts of the model.
+    pub parts: Vec<PartDescription>,
+    /// The drawables of the model.
+    pub drawables: Vec<DrawableDescription>,
+}
+
+impl ModelDescription {
+    /// Creates an empty description with a canvas of one by one unit.
+    pub fn new() -> Self {
+        ModelDescription {
+            canvas_size: [1.0, 1.0],
+            canvas_origin: [0.5, 0.5],
+            pixels_per_unit: 1.0,
+            parameters: Vec::new(),
+            parts: Vec::new(),
+            drawables: Vec::new(),
+        }
+    }
+
+    /// Adds the parameter to the description.
+    pub fn with_parameter(mut self, parameter: ParameterDescription) -> Self {
+        self.parameters.push(parameter);
+        self
+    }
+
+    /// Adds the part to the description.
+    pub fn with_part(mut self, part: PartDescription) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    /// Adds the drawable to the description.
+    pub fn with_drawable(mut self, drawable: DrawableDescription) -> Self {
+        self.drawables.push(drawable);
+        self
+    }
+}
+
+impl Default for ModelDescription {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The description of a parameter of a [`ModelDescription`].
+///
+/// [`ModelDescription`]: ./struct.ModelDescription.html
+#[derive(Clone, Debug)]
+pub struct ParameterDescription {
+    /// The parameter's identifier.
+    pub id: String,
+    /// The parameter's minimum value.
+    pub min_value: f32,
+    /// The parameter's maximum value.
+    pub max_value: f32,
+    /// The parameter's default value.
+    pub default_value: f32,
+    /// The values of the parameter's keyforms in ascending order.
+    pub key_values: Vec<f32>,
+    /// Whether the parameter's value wraps around instead of being clamped.
+    pub repeat: bool,
+}
+
+impl ParameterDescription {
+    /// Creates a parameter with the range and default value.
+    pub fn new<S: Into<String>>(id: S, min_value: f32, max_value: f32, default_value: f32) -> Self {
+        ParameterDescription {
+            id: id.into(),
+            min_value,
+            max_value,
+            default_value,
+            key_values: Vec::new(),
+            repeat: false,
+        }
+    }
+}
+
+/// The description of a part of a [`ModelDescription`].
+///
+/// [`ModelDescription`]: ./struct.ModelDescription.html
+#[derive(Clone, Debug)]
+pub struct PartDescription {
+    /// The part's identifier.
+    pub id: String,
+    /// The index of the part's parent.
+    pub parent: Option<usize>,
+    /// The part's initial opacity.
+    pub opacity: f32,
+}
+
+impl PartDescription {
+    /// Creates an opaque root part.
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        PartDescription {
+            id: id.into(),
+            parent: None,
+            opacity: 1.0,
+        }
+    }
+
+    /// Sets the index of the part's parent.
+    pub fn with_parent(mut self, parent: usize) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+}
+
+/// The description of a drawable of a [`ModelDescription`].
+///
+/// [`ModelDescription`]: ./struct.ModelDescription.html
+#[derive(Clone, Debug)]
+pub struct DrawableDescription {
+    /// The drawable's identifier.
+    pub id: String,
+    /// The index of the part the drawable belongs to.
+    pub parent_part: Option<usize>,
+    /// The drawable's texture index.
+    pub texture_index: i32,
+    /// The drawable's draw order.
+    pub draw_order: i32,
+    /// The drawable's own opacity.
+    pub opacity: f32,
+    /// The drawable's multiply color as rgba.
+    pub multiply_color: [f32; 4],
+    /// The drawable's screen color as rgba.
+    pub screen_color: [f32; 4],
+    /// The drawable's constant drawing flags.
+    pub constant_flags: ConstantFlags,
+    /// The vertex positions at the default values of the parameters.
+    pub vertices: Vec<[f32; 2]>,
+    /// The drawable's uvs, one for each vertex.
+    pub uvs: Vec<[f32; 2]>,
+    /// The drawable's indices.
+    pub indices: Vec<u16>,
+    /// The indices of the drawables masking this drawable.
+    pub masks: Vec<i32>,
+    /// The parameter indices with the offsets the vertices move by for each
+    /// unit the parameter's value is away from its default value.
+    pub offsets: Vec<(usize, Vec<[f32; 2]>)>,
+}
+
+impl DrawableDescription {
+    /// Creates an opaque drawable without a part from its vertices, with
+    /// uvs of zero and no indices.
+    pub fn new<S: Into<String>>(id: S, vertices: Vec<[f32; 2]>) -> Self {
+        DrawableDescription {
+            id: id.into(),
+            parent_part: None,
+            texture_index: 0,
+            draw_order: 500,
+            opacity: 1.0,
+            multiply_color: [1.0, 1.0, 1.0, 1.0],
+            screen_color: [0.0, 0.0, 0.0, 1.0],
+            constant_flags: ConstantFlags::empty(),
+            uvs: vec![[0.0; 2]; vertices.len()],
+            vertices,
+            indices: Vec::new(),
+            masks: Vec::new(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Sets the index of the part the drawable belongs to.
+    pub fn with_parent(mut self, part: usize) -> Self {
+        self.parent_part = Some(part);
+        self
+    }
+
+    /// Moves the vertices linearly with the parameter at the index, by the
+    /// offsets per unit the parameter's value is away from its default.
+    pub fn with_offsets(mut self, parameter: usize, offsets: Vec<[f32; 2]>) -> Self {
+        self.offsets.push((parameter, offsets));
+        self
+    }
+}
+
+// The owned data of a moc created from a description, which the moc points
+// into.
+#[derive(Debug)]
+pub(crate) struct FakeMoc {
+    pub(crate) description: ModelDescription,
+    pub(crate) parameter_min: Box<[f32]>,
+    pub(crate) parameter_max: Box<[f32]>,
+    pub(crate) parameter_default: Box<[f32]>,
+    pub(crate) part_parents: Box<[i32]>,
+    pub(crate) drawable_parents: Box<[i32]>,
+    pub(crate) texture_indices: Box<[i32]>,
+    pub(crate) constant_flags: Box<[ConstantFlags]>,
+    pub(crate) vertex_counts: Box<[i32]>,
+    render_orders: Box<[i32]>,
+}
+
+impl FakeMoc {
+    /// # Panics
+    /// Panics if the description references parts, drawables or parameters
+    /// that don't exist, if the parents of the parts form a cycle, or if the
+    /// vertex data of a drawable is inconsistent.
+    pub(crate) fn new(description: ModelDescription) -> Self {
+        let part_count = description.parts.len();
+        let drawable_count = description.drawables.len();
+        let parameter_count = description.parameters.len();
+        let parent_index = |parent: Option<usize>| match parent {
+            Some(parent) => {
+                assert!(parent < part_count, "part index {} out of bounds", parent);
+                parent as i32
+            },
+            None => -1,
+        };
+        for part in &description.parts {
+            // a chain of parents longer than the number of parts has to
+            // revisit a part
+            let mut parent = part.parent;
+            for _ in 0..part_count {
+                parent = match parent {
+                    Some(parent) => description.parts.get(parent).and_then(|p| p.parent),
+                    None => break,
+                };
+            }
+            assert!(parent.is_none(), "part {} is its own ancestor", part.id);
+        }
+        for drawable in &description.drawables {
+            let vertex_count = drawable.vertices.len();
+            assert_eq!(
+                drawable.uvs.len(),
+                vertex_count,
+                "drawable {} has a different number of uvs than vertices",
+                drawable.id
+            );
+            assert!(
+                drawable
+                    .indices
+                    .iter()
+                    .all(|i| (*i as usize) < vertex_count),
+                "drawable {} has indices out of bounds",
+                drawable.id
+            );
+            assert!(
+                drawable
+                    .masks
+                    .iter()
+                    .all(|m| *m >= 0 && (*m as usize) < drawable_count),
+                "drawable {} has masks out of bounds",
+                drawable.id
+            );
+            for (parameter, offsets) in &drawable.offsets {
+                assert!(
+                    *parameter < parameter_count,
+                    "parameter index {} out of bounds",
+                    parameter
+                );
+                assert_eq!(
+                    offsets.len(),
+                    vertex_count,
+                    "drawable {} has a different number of offsets than vertices",
+                    drawable.id
+                );
+            }
+        }
+
+        // the render order is the rank of the draw order, ties are broken by
+        // the drawable index
+        let mut by_draw_order: Vec<usize> = (0..drawable_count).collect();
+        by_draw_order.sort_by_key(|idx| description.drawables[*idx].draw_order);
+        let mut render_orders = vec![0; drawable_count];
+        for (order, idx) in by_draw_order.into_iter().enumerate() {
+            render_orders[idx] = order as i32;
+        }
+
+        FakeMoc {
+            parameter_min: description.parameters.iter().map(|p| p.min_value).collect(),
+            parameter_max: description.parameters.iter().map(|p| p.max_value).collect(),
+            parameter_default: description
+                .parameters
+                .iter()
+                .map(|p| p.default_value)
+                .collect(),
+            part_parents: description
+                .parts
+                .iter()
+                .map(|p| parent_index(p.parent))
+                .collect(),
+            drawable_parents: description
+                .drawables
+                .iter()
+                .map(|d| parent_index(d.parent_part))
+                .collect(),
+            texture_indices: description
+                .drawables
+                .iter()
+                .map(|d| d.texture_index)
+                .collect(),
+            constant_flags: description
+                .drawables
+                .iter()
+                .map(|d| d.constant_flags)
+                .collect(),
+            vertex_counts: description
+                .drawables
+                .iter()
+                .map(|d| d.vertices.len() as i32)
+                .collect(),
+            render_orders: render_orders.into_boxed_slice(),
+            description,
+        }
+    }
+}
+
+// The dynamic data of a model created from a description, which the model
+// points into.
+#[derive(Debug)]
+pub(crate) struct FakeModel {
+    pub(crate) parameter_values: Box<[f32]>,
+    pub(crate) part_opacities: Box<[f32]>,
+    pub(crate) render_orders: Box<[i32]>,
+    pub(crate) draw_orders: Box<[i32]>,
+    pub(crate) opacities: Box<[f32]>,
+    pub(crate) dynamic_flags: Box<[DynamicFlags]>,
+    pub(crate) vertex_positions: Box<[Box<[[f32; 2]]>]>,
+    pub(crate) vertex_uvs: Box<[Box<[[f32; 2]]>]>,
+    pub(crate) multiply_colors: Box<[[f32; 4]]>,
+    pub(crate) screen_colors: Box<[[f32; 4]]>,
+}
+
+impl FakeModel {
+    pub(crate) fn new(moc: &FakeMoc) -> Self {
+        let drawables = &moc.description.drawables;
+        FakeModel {
+            parameter_values: moc.parameter_default.clone(),
+            part_opacities: moc.description.parts.iter().map(|p| p.opacity).collect(),
+            render_orders: moc.render_orders.clone(),
+            draw_orders: drawables.iter().map(|d| d.draw_order).collect(),
+            // like the native library the drawables are only valid after the
+            // first update
+            opacities: vec![0.0; drawables.len()].into_boxed_slice(),
+            dynamic_flags: vec![DynamicFlags::empty(); drawables.len()].into_boxed_slice(),
+            vertex_positions: drawables
+                .iter()
+                .map(|d| d.vertices.clone().into_boxed_slice())
+                .collect(),
+            vertex_uvs: drawables
+                .iter()
+                .map(|d| d.uvs.clone().into_boxed_slice())
+                .collect(),
+            multiply_colors: drawables.iter().map(|d| d.multiply_color).collect(),
+            screen_colors: drawables.iter().map(|d| d.screen_color).collect(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, moc: &FakeMoc, part_tree: &PartTree) {
+        let parameters = &moc.description.parameters;
+        let deltas: Vec<f32> = parameters
+            .iter()
+            .zip(self.parameter_values.iter())
+            .map(|(p, value)| {
+                fit_value(*value, p.min_value, p.max_value, p.repeat) - p.default_value
+            })
+            .collect();
+        let changes = DynamicFlags::VISIBILITY_CHANGED
+            | DynamicFlags::OPACITY_CHANGED
+            | DynamicFlags::DRAW_ORDER_CHANGED
+            | DynamicFlags::RENDER_ORDER_CHANGED
+            | DynamicFlags::VERTEX_POSITIONS_CHANGED
+            | DynamicFlags::BLEND_COLOR_CHANGED;
+
+        let part_opacities = &self.part_opacities;
+        for (idx, drawable) in moc.description.drawables.iter().enumerate() {
+            let flags = &mut self.dynamic_flags[idx];
+            flags.remove(changes);
+
+            let mut moved = false;
+            for (v, position) in self.vertex_positions[idx].iter_mut().enumerate() {
+                let mut new = drawable.vertices[v];
+                for (parameter, offsets) in &drawable.offsets {
+                    new[0] += offsets[v][0] * deltas[*parameter];
+                    new[1] += offsets[v][1] * deltas[*parameter];
+                }
+                if *position != new {
+                    *position = new;
+                    moved = true;
+                }
+            }
+            if moved {
+                flags.insert(DynamicFlags::VERTEX_POSITIONS_CHANGED);
+            }
+
+            let opacity = drawable.opacity
+                * part_tree
+                    .drawable_parent(DrawableIndex::new(part_tree.moc_id(), idx))
+                    .map_or(1.0, |part| {
+                        part_tree.effective_opacity(part, part_opacities)
+                    });
+            if opacity != self.opacities[idx] {
+                self.opacities[idx] = opacity;
+                flags.insert(DynamicFlags::OPACITY_CHANGED);
+            }
+            let visible = opacity > 0.0;
+            if visible != flags.contains(DynamicFlags::IS_VISIBLE) {
+                flags.set(DynamicFlags::IS_VISIBLE, visible);
+                flags.insert(DynamicFlags::VISIBILITY_CHANGED);
+            }
+        }
+    }
+}
+
+#[test]
+fn fake_model_update() {
+    use crate::Model;
+
+    let description = ModelDescription::new()
+        .with_parameter(ParameterDescription::new("ParamAngleX", -30.0, 30.0, 0.0))
+        .with_part(PartDescription::new("PartRoot"))
+        .with_part(PartDescription::new("PartFace").with_parent(0))
+        .with_drawable(
+            DrawableDescription::new("ArtMesh0", vec![[0.0, 0.0], [1.0, 0.0]])
+                .with_parent(1)
+                .with_offsets(0, vec![[0.1, 0.0], [0.0, 0.1]]),
+        );
+    let mut model = Model::from_description(description);
+    assert!(model.is_fake());
+    assert_eq!(model.parameter("ParamAngleX").unwrap().value, 0.0);
+
+    model.update();
+    let drawable = model.drawable_at(0);
+    assert_eq!(drawable.vertex_positions, [[0.0, 0.0], [1.0, 0.0]]);
+    assert_eq!(drawable.opacity, 1.0);
+    assert!(drawable.dynamic_flags.contains(DynamicFlags::IS_VISIBLE));
+
+    // the value is clamped to the range before moving the vertices
+    model.set_parameter_value(0, 40.0);
+    model.set_part_opacity(0, 0.5);
+    model.update();
+    let drawable = model.drawable_at(0);
+    assert_eq!(drawable.vertex_positions, [[3.0, 0.0], [1.0, 3.0]]);
+    assert_eq!(drawable.opacity, 0.5);
+    assert!(drawable
+        .dynamic_flags
+        .contains(DynamicFlags::VERTEX_POSITIONS_CHANGED | DynamicFlags::OPACITY_CHANGED));
+    assert!(!drawable
+        .dynamic_flags
+        .contains(DynamicFlags::VISIBILITY_CHANGED));
+
+    let clone = model.clone();
+    assert_eq!(clone.parameter_values(), [40.0]);
+    assert_eq!(clone.drawable_vertex_positions(0), [[0.0, 0.0], [1.0, 0.0]]);
+
+    // models of a shared moc start out at the defaults
+    let shared = Model::new(&model.moc_arc());
+    assert_eq!(shared.parameter_values(), [0.0]);
+    assert_eq!(shared.part_opacities(), [1.0, 1.0]);
+    model.reset_to_defaults();
+    assert_eq!(model.parameter_values(), [0.0]);
+    assert_eq!(model.part_opacities(), [1.0, 1.0]);
+}
+
+#[test]
+#[should_panic(expected = "is its own ancestor")]
+fn fake_model_part_cycle() {
+    crate::Model::from_description(
+        ModelDescription::new()
+            .with_part(PartDescription::new("A").with_parent(1))
+            .with_part(PartDescription::new("B").with_parent(0)),
+    );
+}
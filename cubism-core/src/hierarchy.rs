@@ -0,0 +1,298 @@
+use crate::index::{DrawableIndex, MocId, PartIndex};
+
+/// The hierarchy of the parts and drawables of a [Moc](./struct.Moc.html).
+///
+/// Parts can have a parent part, drawables belong to a part. Parent indices
+/// that would make a part its own ancestor are treated as missing, so the
+/// hierarchy is always a forest.
+#[derive(Clone, Debug)]
+pub struct PartTree {
+    moc: MocId,
+    part_parents: Box<[Option<PartIndex>]>,
+    part_children: Box<[Box<[PartIndex]>]>,
+    part_drawables: Box<[Box<[DrawableIndex]>]>,
+    drawable_parents: Box<[Option<PartIndex>]>,
+    roots: Box<[PartIndex]>,
+}
+
+impl PartTree {
+    /// Builds the tree from the parent indices of the parts and drawables as
+    /// returned by the library, where -1 means that there is no parent.
+    pub(crate) fn new(moc: MocId, part_parents: &[i32], drawable_parents: &[i32]) -> Self {
+        let index = |parent: i32, len: usize| {
+            if parent >= 0 && (parent as usize) < len {
+                Some(parent as usize)
+            } else {
+                None
+            }
+        };
+        let part_count = part_parents.len();
+        let mut part_parents: Vec<_> = part_parents
+            .iter()
+            .map(|&parent| index(parent, part_count))
+            .collect();
+        break_cycles(&mut part_parents);
+        let drawable_parents: Vec<_> = drawable_parents
+            .iter()
+            .map(|&parent| index(parent, part_count))
+            .collect();
+
+        let part = |idx| PartIndex::new(moc, idx);
+        let mut part_children = vec![Vec::new(); part_count];
+        let mut roots = Vec::new();
+        for (idx, parent) in part_parents.iter().enumerate() {
+            match parent {
+                Some(parent) => part_children[*parent].push(part(idx)),
+                None => roots.push(part(idx)),
+            }
+        }
+        let mut part_drawables = vec![Vec::new(); part_count];
+        for (idx, parent) in drawable_parents.iter().enumerate() {
+            if let Some(parent) = parent {
+                part_drawables[*parent].push(DrawableIndex::new(moc, idx));
+            }
+        }
+
+        PartTree {
+            moc,
+            part_parents: part_parents.into_iter().map(|p| p.map(part)).collect(),
+            part_children: part_children
+                .into_iter()
+                .map(Vec::into_boxed_slice)
+                .collect(),
+            part_drawables: part_drawables
+                .into_iter()
+                .map(Vec::into_boxed_slice)
+                .collect(),
+            drawable_parents: drawable_parents.into_iter().map(|p| p.map(part)).collect(),
+            roots: roots.into_boxed_slice(),
+        }
+    }
+
+    pub(crate) fn moc_id(&self) -> MocId {
+        self.moc
+    }
+
+    #[inline]
+    fn check_moc_id(&self, id: MocId) {
+        assert!(
+            id == self.moc,
+            "the index belongs to a different moc than the part tree's"
+        );
+    }
+
+    /// Returns the parts without a parent.
+    #[inline]
+    pub fn roots(&self) -> &[PartIndex] {
+        &self.roots
+    }
+
+    /// Returns the parent of the part, or `None` if it is a root.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn parent(&self, part: PartIndex) -> Option<PartIndex> {
+        self.check_moc_id(part.moc_id());
+        self.part_parents[part.index()]
+    }
+
+    /// Returns the direct children of the part.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn children(&self, part: PartIndex) -> &[PartIndex] {
+        self.check_moc_id(part.moc_id());
+        &self.part_children[part.index()]
+    }
+
+    /// Returns the drawables that belong directly to the part.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn drawables(&self, part: PartIndex) -> &[DrawableIndex] {
+        self.check_moc_id(part.moc_id());
+        &self.part_drawables[part.index()]
+    }
+
+    /// Returns the part the drawable belongs to.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn drawable_parent(&self, drawable: DrawableIndex) -> Option<PartIndex> {
+        self.check_moc_id(drawable.moc_id());
+        self.drawable_parents[drawable.index()]
+    }
+
+    /// Returns an iterator over the ancestors of the part, starting with its
+    /// parent.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn ancestors(&self, part: PartIndex) -> Ancestors<'_> {
+        Ancestors {
+            tree: self,
+            next: self.parent(part),
+        }
+    }
+
+    /// Returns an iterator over the descendants of the part in depth-first
+    /// order, not including the part itself.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn descendants(&self, part: PartIndex) -> Descendants<'_> {
+        Descendants {
+            tree: self,
+            stack: self.children(part).iter().rev().copied().collect(),
+        }
+    }
+
+    /// Returns an iterator over the drawables of the part and all of its
+    /// descendants.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    pub fn descendant_drawables(
+        &self,
+        part: PartIndex,
+    ) -> impl Iterator<Item = DrawableIndex> + '_ {
+        std::iter::once(part)
+            .chain(self.descendants(part))
+            .flat_map(move |part| self.drawables(part).iter().copied())
+    }
+
+    /// Returns the product of the opacities of the part and all of its
+    /// ancestors.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc or the opacities are
+    /// too short.
+    pub fn effective_opacity(&self, part: PartIndex, opacities: &[f32]) -> f32 {
+        self.ancestors(part)
+            .fold(opacities[part.index()], |opacity, part| {
+                opacity * opacities[part.index()]
+            })
+    }
+}
+
+// Removes the parent of every part that would close a cycle, the part whose
+// parent leads back to a part on the current path.
+fn break_cycles(parents: &mut [Option<usize>]) {
+    #[derive(Copy, Clone, PartialEq)]
+    enum State {
+        Unvisited,
+        OnPath,
+        Done,
+    }
+    let mut state = vec![State::Unvisited; parents.len()];
+    let mut path = Vec::new();
+    for start in 0..parents.len() {
+        let mut current = start;
+        while state[current] == State::Unvisited {
+            state[current] = State::OnPath;
+            path.push(current);
+            match parents[current] {
+                Some(parent) if state[parent] == State::OnPath => {
+                    parents[current] = None;
+                    break;
+                },
+                Some(parent) => current = parent,
+                None => break,
+            }
+        }
+        for part in path.drain(..) {
+            state[part] = State::Done;
+        }
+    }
+}
+
+/// An iterator over the ancestors of a part.
+#[derive(Clone, Debug)]
+pub struct Ancestors<'tree> {
+    tree: &'tree PartTree,
+    next: Option<PartIndex>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = PartIndex;
+
+    fn next(&mut self) -> Option<PartIndex> {
+        let part = self.next?;
+        self.next = self.tree.parent(part);
+        Some(part)
+    }
+}
+
+/// An iterator over the descendants of a part.
+#[derive(Clone, Debug)]
+pub struct Descendants<'tree> {
+    tree: &'tree PartTree,
+    stack: Vec<PartIndex>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = PartIndex;
+
+    fn next(&mut self) -> Option<PartIndex> {
+        let part = self.stack.pop()?;
+        self.stack
+            .extend(self.tree.children(part).iter().rev().copied());
+        Some(part)
+    }
+}
+
+#[test]
+fn part_tree_traversal() {
+    // 0 -> 1 -> 2, 0 -> 3, 4
+    let moc = MocId::next();
+    let tree = PartTree::new(moc, &[-1, 0, 1, 0, -1], &[2, 1, 4, -1]);
+    let part = |idx| PartIndex::new(moc, idx);
+    let indices = |iter: &mut dyn Iterator<Item = PartIndex>| -> Vec<usize> {
+        iter.map(PartIndex::index).collect()
+    };
+    assert_eq!(indices(&mut tree.roots().iter().copied()), [0, 4]);
+    assert_eq!(indices(&mut tree.children(part(0)).iter().copied()), [1, 3]);
+    assert_eq!(indices(&mut tree.ancestors(part(2))), [1, 0]);
+    assert_eq!(indices(&mut tree.descendants(part(0))), [1, 2, 3]);
+    assert_eq!(
+        tree.descendant_drawables(part(0))
+            .map(DrawableIndex::index)
+            .collect::<Vec<_>>(),
+        [1, 0]
+    );
+    assert_eq!(tree.drawable_parent(DrawableIndex::new(moc, 3)), None);
+    let opacities = [0.5, 0.5, 1.0, 1.0, 0.0];
+    assert_eq!(tree.effective_opacity(part(2), &opacities), 0.25);
+}
+
+#[test]
+fn part_tree_cycles() {
+    // 0 -> 2 -> 1 -> 0 and 3 -> 3, 4 hangs off the cycle
+    let moc = MocId::next();
+    let tree = PartTree::new(moc, &[2, 0, 1, 3, 1], &[0]);
+    let part = |idx| PartIndex::new(moc, idx);
+    // the part closing the cycle loses its parent
+    assert_eq!(
+        tree.roots().iter().map(|p| p.index()).collect::<Vec<_>>(),
+        [1, 3]
+    );
+    assert_eq!(
+        tree.ancestors(part(0))
+            .map(PartIndex::index)
+            .collect::<Vec<_>>(),
+        [2, 1]
+    );
+    assert_eq!(
+        tree.descendants(part(1))
+            .map(PartIndex::index)
+            .collect::<Vec<_>>(),
+        [2, 0, 4]
+    );
+    assert_eq!(tree.effective_opacity(part(0), &[0.5; 5]), 0.125);
+}
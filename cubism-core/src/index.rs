@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Identifies a [Moc](./struct.Moc.html) for the lifetime of the program.
+///
+/// Every moc receives a distinct id when it is loaded, which is used by the
+/// index handles to make sure they are only used with the moc they have been
+/// resolved from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MocId(usize);
+
+impl MocId {
+    pub(in crate) fn next() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        MocId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+macro_rules! index_handle {
+    ($(#[$meta:meta])* $name:ident, $what:literal) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub struct $name {
+            moc: MocId,
+            index: usize,
+        }
+
+        impl $name {
+            pub(in crate) fn new(moc: MocId, index: usize) -> Self {
+                $name { moc, index }
+            }
+
+            #[doc = concat!("Returns the raw ", $what, " index.")]
+            #[inline]
+            pub fn index(self) -> usize {
+                self.index
+            }
+
+            /// Returns the id of the moc this index belongs to.
+            #[inline]
+            pub fn moc_id(self) -> MocId {
+                self.moc
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(fmt, concat!($what, " {}"), self.index)
+            }
+        }
+    };
+}
+
+index_handle!(
+    /// The index of a parameter of a specific moc, resolved with
+    /// [`Moc::parameter_index`](./struct.Moc.html#method.parameter_index).
+    ParameterIndex,
+    "parameter"
+);
+index_handle!(
+    /// The index of a part of a specific moc, resolved with
+    /// [`Moc::part_index`](./struct.Moc.html#method.part_index).
+    PartIndex,
+    "part"
+);
+index_handle!(
+    /// The index of a drawable of a specific moc, resolved with
+    /// [`Moc::drawable_index`](./struct.Moc.html#method.drawable_index).
+    DrawableIndex,
+    "drawable"
+);
+
+/// Maps the raw bytes of the ids to their index. The first occurrence of an id
+/// wins.
+pub(in crate) fn id_map(ids: &[&'static [u8]]) -> HashMap<&'static [u8], usize> {
+    let mut map = HashMap::with_capacity(ids.len());
+    for (idx, id) in ids.iter().enumerate() {
+        map.entry(*id).or_insert(idx);
+    }
+    map
+}
+
+#[test]
+fn id_map_first_occurrence() {
+    let ids: &[&[u8]] = &[b"ParamA", b"Param\xFF", b"ParamA"];
+    let map = id_map(ids);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&b"ParamA"[..]], 0);
+    assert_eq!(map[&b"Param\xFF"[..]], 1);
+    assert_ne!(MocId::next(), MocId::next());
+}
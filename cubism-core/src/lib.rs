@@ -8,12 +8,24 @@
 //! Rust bindings for Live2D's cubism sdk
 
 mod error;
+mod fake;
+mod hierarchy;
+mod index;
 mod log;
 mod mem;
 mod moc;
+mod moc3;
 mod model;
 
-pub use crate::{error::*, log::*, moc::*, model::*};
+pub use crate::{error::*, fake::*, hierarchy::*, index::*, log::*, moc::*, moc3::*, model::*};
+
+/// Loading of the core library at runtime, which has to be done before using
+/// anything else of this crate.
+#[cfg(feature = "dynamic-load")]
+pub use ffi::dynamic::{
+    is_loaded as is_library_loaded, library_filename, load as load_library,
+    load_default as load_default_library, LoadError,
+};
 
 /// Returns the linked library version in a (major, minor, patch) tuple
 pub fn version() -> (u8, u8, u16) {
@@ -24,6 +36,57 @@ pub fn version() -> (u8, u8, u16) {
     (major as u8, minor as u8, patch as u16)
 }
 
+// Whether the functions added in the versions can be called. A linked library
+// only provides them with the matching `core-*` feature, older libraries can
+// be linked without it.
+const LINKED_4_2: bool = cfg!(any(feature = "dynamic-load", feature = "core-4-2"));
+const LINKED_5_0: bool = cfg!(any(feature = "dynamic-load", feature = "core-5-0"));
+const LINKED_5_1: bool = cfg!(any(feature = "dynamic-load", feature = "core-5-1"));
+
+// The first version of the library exposing the multiply and screen colors of
+// the drawables.
+const BLEND_COLOR_VERSION: (u8, u8, u16) = (4, 2, 0);
+
+// The first version of the library able to check the consistency of mocs.
+const MOC_CONSISTENCY_VERSION: (u8, u8, u16) = (4, 2, 0);
+
+/// Returns whether the linked library is able to check the consistency of
+/// moc data, which was added in Cubism Core 4.2.
+///
+/// Always returns false if the library is linked without the `core-4-2`
+/// feature.
+pub fn supports_moc_consistency() -> bool {
+    LINKED_4_2 && version() >= MOC_CONSISTENCY_VERSION
+}
+
+// The first versions of the library exposing the parameter keys, types and
+// repeat flags.
+const PARAMETER_KEYS_VERSION: (u8, u8, u16) = (4, 2, 0);
+const PARAMETER_TYPES_VERSION: (u8, u8, u16) = (5, 0, 0);
+const PARAMETER_REPEATS_VERSION: (u8, u8, u16) = (5, 1, 0);
+
+// Whether the library exposes the parameter keys, types and repeat flags.
+pub(crate) fn supports_parameter_keys(version: (u8, u8, u16)) -> bool {
+    LINKED_4_2 && version >= PARAMETER_KEYS_VERSION
+}
+
+pub(crate) fn supports_parameter_types(version: (u8, u8, u16)) -> bool {
+    LINKED_5_0 && version >= PARAMETER_TYPES_VERSION
+}
+
+pub(crate) fn supports_parameter_repeats(version: (u8, u8, u16)) -> bool {
+    LINKED_5_1 && version >= PARAMETER_REPEATS_VERSION
+}
+
+/// Returns whether the linked library supports the multiply and screen colors
+/// of drawables, which were added in Cubism Core 4.2.
+///
+/// Always returns false if the library is linked without the `core-4-2`
+/// feature, which has to be disabled to link libraries older than 4.2.
+pub fn supports_blend_colors() -> bool {
+    LINKED_4_2 && version() >= BLEND_COLOR_VERSION
+}
+
 bitflags::bitflags! {
     /// The constant flags of a [Model](model/struct.Model.html)'s drawable.
     pub struct ConstantFlags: u8 {
@@ -53,5 +116,8 @@ bitflags::bitflags! {
         const RENDER_ORDER_CHANGED = ffi::csmRenderOrderDidChange;
         /// The drawable's vertex positions changed since the last update.
         const VERTEX_POSITIONS_CHANGED = ffi::csmVertexPositionsDidChange;
+        /// The drawable's multiply or screen color changed since the last
+        /// update. Only set by Cubism Core 4.2 and newer.
+        const BLEND_COLOR_CHANGED = ffi::csmBlendColorDidChange;
     }
 }
@@ -1,58 +1,290 @@
-use std::{ffi::CStr, ptr, ptr::NonNull, slice};
+use std::{collections::HashMap, ffi::CStr, os::raw::c_char, ptr, ptr::NonNull, slice, sync::Arc};
 
 use ffi::{csmMoc, csmModel};
 
 use crate::{
     error::{MocError, MocResult},
+    fake::{FakeMoc, ModelDescription},
+    hierarchy::PartTree,
+    index::{id_map, DrawableIndex, MocId, ParameterIndex, PartIndex},
     mem::AlignedMemory,
     ConstantFlags,
 };
 
-static INVALID_ID_STR: &str = "NON_UTF8_ID";
+/// The Cubism Editor generation a moc file targets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MocVersion {
+    /// The version couldn't be determined, the data is most likely not a moc.
+    Unknown,
+    /// Cubism 3.0 to 3.2.
+    V3_0,
+    /// Cubism 3.3.
+    V3_3,
+    /// Cubism 4.0 and 4.1.
+    V4_0,
+    /// Cubism 4.2.
+    V4_2,
+    /// Cubism 5.0.
+    V5_0,
+    /// A version newer than the ones known to this crate.
+    Newer(u32),
+}
+
+impl MocVersion {
+    /// Converts the raw version returned by the library.
+    pub fn from_raw(raw: u32) -> Self {
+        match raw {
+            ffi::csmMocVersion_30 => MocVersion::V3_0,
+            ffi::csmMocVersion_33 => MocVersion::V3_3,
+            ffi::csmMocVersion_40 => MocVersion::V4_0,
+            ffi::csmMocVersion_42 => MocVersion::V4_2,
+            ffi::csmMocVersion_50 => MocVersion::V5_0,
+            ffi::csmMocVersion_Unknown => MocVersion::Unknown,
+            raw => MocVersion::Newer(raw),
+        }
+    }
+
+    /// Returns the version of the moc data.
+    pub fn of(data: &[u8]) -> Self {
+        Self::from_raw(unsafe { ffi::csmGetMocVersion(data.as_ptr() as _, data.len() as _) })
+    }
+
+    /// Returns the newest version the linked library can load.
+    pub fn latest() -> Self {
+        Self::from_raw(unsafe { ffi::csmGetLatestMocVersion() })
+    }
+}
+
+/// The type of a parameter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParameterType {
+    /// A regular parameter.
+    Normal,
+    /// A blend shape parameter, added in Cubism 5.0.
+    BlendShape,
+}
+
+impl ParameterType {
+    fn from_raw(raw: ffi::csmParameterType) -> Self {
+        match raw {
+            ffi::csmParameterType_BlendShape => ParameterType::BlendShape,
+            _ => ParameterType::Normal,
+        }
+    }
+}
 
 /// This represents a moc.
 ///
-/// A moc should never exist without at least one model instance as it
-/// owns the parameter, part and drawable ids as well as the minimum, maximum
-/// and default parameter values of its [Model](./struct.Model.html).
+/// A moc owns the parameter, part and drawable ids as well as the minimum,
+/// maximum and default parameter values of its [Model](./struct.Model.html)s.
+/// It can be loaded once with [`Moc::from_bytes`](#method.from_bytes) and
+/// then be shared by any number of models created with
+/// [`Model::new`](./struct.Model.html#method.new). The moc keeps a model
+/// instance of its own that this data is read from, so it stays valid after
+/// every model of the moc has been dropped.
 ///
 /// All the data that can be accessed from a moc is static data that will not
 /// change over the course of a programs lifetime.
 #[derive(Debug)]
 pub struct Moc {
-    mem: AlignedMemory<csmMoc>,
+    backend: MocBackend,
+    id: MocId,
+    version: MocVersion,
     pub(in crate) part_ids: Box<[&'static str]>,
     pub(in crate) parameter_ids: Box<[&'static str]>,
     pub(in crate) drawable_ids: Box<[&'static str]>,
+    raw_part_ids: Box<[&'static [u8]]>,
+    raw_parameter_ids: Box<[&'static [u8]]>,
+    raw_drawable_ids: Box<[&'static [u8]]>,
+    part_map: HashMap<&'static [u8], usize>,
+    parameter_map: HashMap<&'static [u8], usize>,
+    drawable_map: HashMap<&'static [u8], usize>,
+    // backing storage of the lossily converted ids that aren't valid utf-8
+    _lossy_ids: Box<[Box<str>]>,
     param_def_val: NonNull<[f32]>,
+    part_default_opacities: Box<[f32]>,
     param_max_val: NonNull<[f32]>,
     param_min_val: NonNull<[f32]>,
+    pub(in crate) param_key_values: Box<[&'static [f32]]>,
+    param_types: Box<[ParameterType]>,
+    param_repeats: Box<[bool]>,
     drawable_texture_indices: NonNull<[i32]>,
     drawable_constant_flags: NonNull<[ConstantFlags]>,
     drawable_indices: Box<[&'static [u16]]>,
     drawable_masks: Box<[&'static [i32]]>,
     drawable_vertex_counts: NonNull<[i32]>,
+    part_parents: NonNull<[i32]>,
+    part_tree: PartTree,
+}
+
+// The memory backing a moc. The ids, parameter ranges and the other data of a
+// native moc are read through a model instance, which is kept alive with the
+// moc so that the data stays valid once every model of the moc is dropped.
+#[derive(Debug)]
+enum MocBackend {
+    Native {
+        moc: AlignedMemory<csmMoc>,
+        _model: AlignedMemory<csmModel>,
+    },
+    Fake(Box<FakeMoc>),
 }
 
 impl Moc {
+    /// Loads a moc from bytes, which can then be shared by models created
+    /// with [`Model::new`](./struct.Model.html#method.new).
+    ///
+    /// The data is checked for consistency before it is loaded, like in
+    /// [`Model::from_bytes`](./struct.Model.html#method.from_bytes).
+    pub fn from_bytes<R: AsRef<[u8]>>(data: R) -> MocResult<Arc<Self>> {
+        unsafe { Moc::new(data.as_ref(), true).map(Arc::new) }
+    }
+
+    /// Loads a moc from bytes without checking the consistency of the data
+    /// first, like
+    /// [`Model::from_bytes_unchecked`](./struct.Model.html#method.from_bytes_unchecked).
+    ///
+    /// # Safety
+    /// The data has to be a well formed moc, malformed data can crash the
+    /// native library.
+    pub unsafe fn from_bytes_unchecked<R: AsRef<[u8]>>(data: R) -> MocResult<Arc<Self>> {
+        Moc::new(data.as_ref(), false).map(Arc::new)
+    }
+
+    /// Returns the version of the file this moc has been loaded from.
+    #[inline]
+    pub fn version(&self) -> MocVersion {
+        self.version
+    }
+
+    /// Returns the id identifying this moc.
+    #[inline]
+    pub fn id(&self) -> MocId {
+        self.id
+    }
+
     /// Returns the parameter names.
+    ///
+    /// Ids that aren't valid utf-8 are converted lossily, their exact bytes
+    /// are returned by [`raw_parameter_ids`](#method.raw_parameter_ids).
     #[inline]
     pub fn parameter_ids<'moc>(&'moc self) -> &[&'moc str] {
         &self.parameter_ids
     }
 
     /// Returns the part names.
+    ///
+    /// Ids that aren't valid utf-8 are converted lossily, their exact bytes
+    /// are returned by [`raw_part_ids`](#method.raw_part_ids).
     #[inline]
     pub fn part_ids<'moc>(&'moc self) -> &[&'moc str] {
         &self.part_ids
     }
 
     /// Returns the drawable names.
+    ///
+    /// Ids that aren't valid utf-8 are converted lossily, their exact bytes
+    /// are returned by [`raw_drawable_ids`](#method.raw_drawable_ids).
     #[inline]
     pub fn drawable_ids<'moc>(&'moc self) -> &[&'moc str] {
         &self.drawable_ids
     }
 
+    /// Returns the parameter names as they are stored in the moc.
+    #[inline]
+    pub fn raw_parameter_ids(&self) -> &[&[u8]] {
+        &self.raw_parameter_ids
+    }
+
+    /// Returns the part names as they are stored in the moc.
+    #[inline]
+    pub fn raw_part_ids(&self) -> &[&[u8]] {
+        &self.raw_part_ids
+    }
+
+    /// Returns the drawable names as they are stored in the moc.
+    #[inline]
+    pub fn raw_drawable_ids(&self) -> &[&[u8]] {
+        &self.raw_drawable_ids
+    }
+
+    /// Returns the index of the first parameter with the given name.
+    #[inline]
+    pub fn parameter_index(&self, id: &str) -> Option<ParameterIndex> {
+        self.raw_parameter_index(id.as_bytes())
+    }
+
+    /// Returns the index of the first parameter with the given raw name,
+    /// which also finds ids that aren't valid utf-8.
+    #[inline]
+    pub fn raw_parameter_index(&self, id: &[u8]) -> Option<ParameterIndex> {
+        self.parameter_map
+            .get(id)
+            .map(|idx| ParameterIndex::new(self.id, *idx))
+    }
+
+    /// Returns the index of the first part with the given name.
+    #[inline]
+    pub fn part_index(&self, id: &str) -> Option<PartIndex> {
+        self.raw_part_index(id.as_bytes())
+    }
+
+    /// Returns the index of the first part with the given raw name, which
+    /// also finds ids that aren't valid utf-8.
+    #[inline]
+    pub fn raw_part_index(&self, id: &[u8]) -> Option<PartIndex> {
+        self.part_map
+            .get(id)
+            .map(|idx| PartIndex::new(self.id, *idx))
+    }
+
+    /// Returns the index of the first drawable with the given name.
+    #[inline]
+    pub fn drawable_index(&self, id: &str) -> Option<DrawableIndex> {
+        self.raw_drawable_index(id.as_bytes())
+    }
+
+    /// Returns the index of the first drawable with the given raw name, which
+    /// also finds ids that aren't valid utf-8.
+    #[inline]
+    pub fn raw_drawable_index(&self, id: &[u8]) -> Option<DrawableIndex> {
+        self.drawable_map
+            .get(id)
+            .map(|idx| DrawableIndex::new(self.id, *idx))
+    }
+
+    /// Returns the [`ParameterIndex`](./struct.ParameterIndex.html) of the
+    /// raw index, or `None` if it is out of bounds.
+    #[inline]
+    pub fn parameter_index_at(&self, idx: usize) -> Option<ParameterIndex> {
+        if idx < self.parameter_count() {
+            Some(ParameterIndex::new(self.id, idx))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the [`PartIndex`](./struct.PartIndex.html) of the raw index, or
+    /// `None` if it is out of bounds.
+    #[inline]
+    pub fn part_index_at(&self, idx: usize) -> Option<PartIndex> {
+        if idx < self.part_count() {
+            Some(PartIndex::new(self.id, idx))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the [`DrawableIndex`](./struct.DrawableIndex.html) of the raw
+    /// index, or `None` if it is out of bounds.
+    #[inline]
+    pub fn drawable_index_at(&self, idx: usize) -> Option<DrawableIndex> {
+        if idx < self.drawable_count() {
+            Some(DrawableIndex::new(self.id, idx))
+        } else {
+            None
+        }
+    }
+
     /// Returns the parameter max values.
     #[inline]
     pub fn parameter_max(&self) -> &[f32] {
@@ -71,6 +303,58 @@ impl Moc {
         unsafe { self.param_def_val.as_ref() }
     }
 
+    /// Returns the part opacities models are initialized with.
+    #[inline]
+    pub fn part_default_opacities(&self) -> &[f32] {
+        &self.part_default_opacities
+    }
+
+    /// Returns the values of the keyforms of the parameters, in ascending
+    /// order. They are empty if the linked library is older than Cubism Core
+    /// 4.2 or linked without the `core-4-2` feature.
+    #[inline]
+    pub fn parameter_key_values(&self) -> &[&[f32]] {
+        &self.param_key_values
+    }
+
+    /// Returns the types of the parameters. Libraries older than Cubism Core
+    /// 5.0 or linked without the `core-5-0` feature only know
+    /// [`ParameterType::Normal`](./enum.ParameterType.html).
+    #[inline]
+    pub fn parameter_types(&self) -> &[ParameterType] {
+        &self.param_types
+    }
+
+    /// Returns whether the parameters repeat, meaning that their values wrap
+    /// around instead of being clamped to their range. Libraries older than
+    /// Cubism Core 5.1 or linked without the `core-5-1` feature don't support
+    /// repeating parameters.
+    #[inline]
+    pub fn parameter_repeats(&self) -> &[bool] {
+        &self.param_repeats
+    }
+
+    /// Fits the value into the range of the parameter at the index, wrapping
+    /// it around if the parameter repeats and clamping it otherwise.
+    ///
+    /// # Panics
+    /// Panics on out of bounds access.
+    #[inline]
+    pub fn fit_parameter_value(&self, idx: usize, value: f32) -> f32 {
+        let (min, max) = (self.parameter_min()[idx], self.parameter_max()[idx]);
+        fit_value(value, min, max, self.param_repeats[idx])
+    }
+
+    /// Returns the keyform value of the parameter at the index closest to the
+    /// value, or the value itself if the parameter has no known keyforms.
+    ///
+    /// # Panics
+    /// Panics on out of bounds access.
+    #[inline]
+    pub fn snap_parameter_value(&self, idx: usize, value: f32) -> f32 {
+        snap_to_key(self.param_key_values[idx], value)
+    }
+
     /// Returns the number of parameters this moc has.
     #[inline]
     pub fn parameter_count(&self) -> usize {
@@ -119,27 +403,65 @@ impl Moc {
         &self.drawable_masks
     }
 
+    /// Returns the hierarchy of the parts and drawables.
+    #[inline]
+    pub fn part_tree(&self) -> &PartTree {
+        &self.part_tree
+    }
+
     /// Returns true if this model is masked.
     #[inline]
     pub fn is_masked(&self) -> bool {
         self.drawable_masks.iter().any(|m| !m.is_empty())
     }
 
-    /// Returns the raw [csmMoc](../cubism_core_sys/moc/struct.csmMoc.html) ptr
+    /// Returns the size in bytes of the memory allocated for the native moc.
+    /// Mocs created from a
+    /// [`ModelDescription`](./struct.ModelDescription.html) report 0.
+    #[inline]
+    pub fn memory_size(&self) -> usize {
+        match &self.backend {
+            MocBackend::Native { moc, .. } => moc.layout().size(),
+            MocBackend::Fake(_) => 0,
+        }
+    }
+
+    /// Returns whether this moc has been created from a
+    /// [`ModelDescription`](./struct.ModelDescription.html) instead of being
+    /// loaded by the native library.
+    #[inline]
+    pub fn is_fake(&self) -> bool {
+        self.fake().is_some()
+    }
+
+    /// Returns the raw [csmMoc](../cubism_core_sys/moc/struct.csmMoc.html)
+    /// ptr, which is null if the moc has been created from a
+    /// [`ModelDescription`](./struct.ModelDescription.html).
     #[inline]
     pub fn as_ptr(&self) -> *mut csmMoc {
-        self.mem.as_ptr()
+        match &self.backend {
+            MocBackend::Native { moc, .. } => moc.as_ptr(),
+            MocBackend::Fake(_) => ptr::null_mut(),
+        }
     }
 }
 
 impl Moc {
-    unsafe fn new_moc(data: &[u8]) -> MocResult<AlignedMemory<csmMoc>> {
+    unsafe fn new_moc(data: &[u8], check_consistency: bool) -> MocResult<AlignedMemory<csmMoc>> {
+        if check_consistency && !crate::supports_moc_consistency() {
+            return Err(MocError::ConsistencyUnsupported);
+        }
         let moc_ver = ffi::csmGetMocVersion(data.as_ptr() as _, data.len() as _);
         if ffi::csmGetLatestMocVersion() < moc_ver {
             Err(MocError::MocVersionMismatch(moc_ver))
         } else {
             let mem = AlignedMemory::alloc(data.len());
             ptr::copy_nonoverlapping(data.as_ptr(), mem.as_ptr() as *mut u8, data.len());
+            if check_consistency
+                && ffi::csmHasMocConsistency(mem.as_ptr() as _, mem.layout().size() as u32) == 0
+            {
+                return Err(MocError::InconsistentMocData);
+            }
             let revived =
                 ffi::csmReviveMocInPlace(mem.as_ptr() as _, mem.layout().size() as u32).is_null();
             if revived {
@@ -150,21 +472,45 @@ impl Moc {
         }
     }
 
-    pub(in crate) unsafe fn new(data: &[u8]) -> MocResult<(Self, AlignedMemory<csmModel>)> {
-        let mem = Self::new_moc(data)?;
+    pub(in crate) unsafe fn new(data: &[u8], check_consistency: bool) -> MocResult<Self> {
+        let mem = Self::new_moc(data, check_consistency)?;
         let model = Self::init_new_model(mem.as_ptr());
         let model_ptr = model.as_ptr();
 
-        let id_transform = |ptr, len| {
-            slice::from_raw_parts_mut(ptr, len)
+        let raw_ids = |ptr: *mut *const c_char, len| -> Box<[&'static [u8]]> {
+            slice::from_raw_parts(ptr, len)
                 .iter()
-                .map(|ptr| CStr::from_ptr(*ptr).to_str().unwrap_or(INVALID_ID_STR))
+                .map(|ptr| CStr::from_ptr(*ptr).to_bytes())
+                .collect()
         };
 
         let param_count = ffi::csmGetParameterCount(model_ptr) as usize;
         let part_count = ffi::csmGetPartCount(model_ptr) as usize;
         let drawable_count = ffi::csmGetDrawableCount(model_ptr) as usize;
 
+        let raw_part_ids = raw_ids(ffi::csmGetPartIds(model_ptr), part_count);
+        let raw_parameter_ids = raw_ids(ffi::csmGetParameterIds(model_ptr), param_count);
+        let raw_drawable_ids = raw_ids(ffi::csmGetDrawableIds(model_ptr), drawable_count);
+        let mut lossy_ids = Vec::new();
+        let mut str_ids = |ids: &[&'static [u8]]| -> Box<[&'static str]> {
+            ids.iter()
+                .map(|id| match std::str::from_utf8(id) {
+                    Ok(id) => id,
+                    Err(_) => {
+                        let lossy: Box<str> = String::from_utf8_lossy(id).into();
+                        // the heap allocation of the box doesn't move and lives as long as
+                        // the moc
+                        let id = &*(&*lossy as *const str);
+                        lossy_ids.push(lossy);
+                        id
+                    },
+                })
+                .collect()
+        };
+        let part_ids = str_ids(&raw_part_ids);
+        let parameter_ids = str_ids(&raw_parameter_ids);
+        let drawable_ids = str_ids(&raw_drawable_ids);
+
         let indices = slice::from_raw_parts(ffi::csmGetDrawableIndices(model_ptr), drawable_count);
         let drawable_indices =
             slice::from_raw_parts(ffi::csmGetDrawableIndexCounts(model_ptr), drawable_count)
@@ -172,6 +518,46 @@ impl Moc {
                 .zip(indices)
                 .map(|(c, indices)| slice::from_raw_parts(*indices, *c as usize))
                 .collect();
+        let version = crate::version();
+        let param_key_values = if crate::supports_parameter_keys(version) {
+            let values =
+                slice::from_raw_parts(ffi::csmGetParameterKeyValues(model_ptr), param_count);
+            slice::from_raw_parts(ffi::csmGetParameterKeyCounts(model_ptr), param_count)
+                .iter()
+                .zip(values)
+                .map(|(c, values)| slice::from_raw_parts(*values, *c as usize))
+                .collect()
+        } else {
+            vec![&[][..]; param_count].into_boxed_slice()
+        };
+        let param_types = if crate::supports_parameter_types(version) {
+            slice::from_raw_parts(ffi::csmGetParameterTypes(model_ptr), param_count)
+                .iter()
+                .map(|ty| ParameterType::from_raw(*ty))
+                .collect()
+        } else {
+            vec![ParameterType::Normal; param_count].into_boxed_slice()
+        };
+        let param_repeats = if crate::supports_parameter_repeats(version) {
+            slice::from_raw_parts(ffi::csmGetParameterRepeats(model_ptr), param_count)
+                .iter()
+                .map(|repeat| *repeat != 0)
+                .collect()
+        } else {
+            vec![false; param_count].into_boxed_slice()
+        };
+
+        let id = MocId::next();
+        let part_parents =
+            slice::from_raw_parts(ffi::csmGetPartParentPartIndices(model_ptr), part_count);
+        let part_tree = PartTree::new(
+            id,
+            part_parents,
+            slice::from_raw_parts(
+                ffi::csmGetDrawableParentPartIndices(model_ptr),
+                drawable_count,
+            ),
+        );
         let masks = slice::from_raw_parts(ffi::csmGetDrawableMasks(model_ptr), drawable_count);
         let drawable_masks =
             slice::from_raw_parts(ffi::csmGetDrawableMaskCounts(model_ptr), drawable_count)
@@ -180,43 +566,115 @@ impl Moc {
                 .map(|(c, masks)| slice::from_raw_parts(*masks, *c as usize))
                 .collect();
 
-        Ok((
-            Moc {
-                mem,
-                part_ids: id_transform(ffi::csmGetPartIds(model_ptr), part_count).collect(),
-                parameter_ids: id_transform(ffi::csmGetParameterIds(model_ptr), param_count)
-                    .collect(),
-                drawable_ids: id_transform(ffi::csmGetDrawableIds(model_ptr), drawable_count)
-                    .collect(),
-                param_def_val: NonNull::from(slice::from_raw_parts(
-                    ffi::csmGetParameterDefaultValues(model_ptr),
-                    param_count,
-                )),
-                param_max_val: NonNull::from(slice::from_raw_parts(
-                    ffi::csmGetParameterMaximumValues(model_ptr),
-                    param_count,
-                )),
-                param_min_val: NonNull::from(slice::from_raw_parts(
-                    ffi::csmGetParameterMinimumValues(model_ptr),
-                    param_count,
-                )),
-                drawable_texture_indices: NonNull::from(slice::from_raw_parts(
-                    ffi::csmGetDrawableTextureIndices(model_ptr),
-                    drawable_count,
-                )),
-                drawable_constant_flags: NonNull::from(slice::from_raw_parts(
-                    ffi::csmGetDrawableConstantFlags(model_ptr) as _,
-                    drawable_count,
-                )),
-                drawable_indices,
-                drawable_masks,
-                drawable_vertex_counts: NonNull::from(slice::from_raw_parts(
-                    ffi::csmGetDrawableVertexCounts(model_ptr),
-                    drawable_count,
-                )),
+        Ok(Moc {
+            backend: MocBackend::Native {
+                moc: mem,
+                _model: model,
             },
-            model,
-        ))
+            id,
+            version: MocVersion::of(data),
+            part_ids,
+            parameter_ids,
+            drawable_ids,
+            part_map: id_map(&raw_part_ids),
+            parameter_map: id_map(&raw_parameter_ids),
+            drawable_map: id_map(&raw_drawable_ids),
+            raw_part_ids,
+            raw_parameter_ids,
+            raw_drawable_ids,
+            _lossy_ids: lossy_ids.into(),
+            param_def_val: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetParameterDefaultValues(model_ptr),
+                param_count,
+            )),
+            part_default_opacities: slice::from_raw_parts(
+                ffi::csmGetPartOpacities(model_ptr),
+                part_count,
+            )
+            .into(),
+            param_max_val: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetParameterMaximumValues(model_ptr),
+                param_count,
+            )),
+            param_min_val: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetParameterMinimumValues(model_ptr),
+                param_count,
+            )),
+            param_key_values,
+            param_types,
+            param_repeats,
+            drawable_texture_indices: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetDrawableTextureIndices(model_ptr),
+                drawable_count,
+            )),
+            drawable_constant_flags: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetDrawableConstantFlags(model_ptr) as _,
+                drawable_count,
+            )),
+            drawable_indices,
+            drawable_masks,
+            drawable_vertex_counts: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetDrawableVertexCounts(model_ptr),
+                drawable_count,
+            )),
+            part_parents: NonNull::from(part_parents),
+            part_tree,
+        })
+    }
+
+    pub(in crate) fn fake(&self) -> Option<&FakeMoc> {
+        match &self.backend {
+            MocBackend::Native { .. } => None,
+            MocBackend::Fake(fake) => Some(fake),
+        }
+    }
+
+    pub(in crate) fn part_parents(&self) -> &[i32] {
+        unsafe { self.part_parents.as_ref() }
+    }
+
+    pub(in crate) fn from_description(description: ModelDescription) -> Self {
+        let fake = Box::new(FakeMoc::new(description));
+        // the ids and slices point into the boxed description, which lives as
+        // long as the moc
+        let desc: &'static ModelDescription = unsafe { &*(&fake.description as *const _) };
+        let raw_part_ids: Box<[&'static [u8]]> =
+            desc.parts.iter().map(|p| p.id.as_bytes()).collect();
+        let raw_parameter_ids: Box<[&'static [u8]]> =
+            desc.parameters.iter().map(|p| p.id.as_bytes()).collect();
+        let raw_drawable_ids: Box<[&'static [u8]]> =
+            desc.drawables.iter().map(|d| d.id.as_bytes()).collect();
+
+        let id = MocId::next();
+        Moc {
+            id,
+            version: MocVersion::Unknown,
+            part_ids: desc.parts.iter().map(|p| &*p.id).collect(),
+            parameter_ids: desc.parameters.iter().map(|p| &*p.id).collect(),
+            drawable_ids: desc.drawables.iter().map(|d| &*d.id).collect(),
+            part_map: id_map(&raw_part_ids),
+            parameter_map: id_map(&raw_parameter_ids),
+            drawable_map: id_map(&raw_drawable_ids),
+            raw_part_ids,
+            raw_parameter_ids,
+            raw_drawable_ids,
+            _lossy_ids: Box::new([]),
+            param_def_val: NonNull::from(&*fake.parameter_default),
+            part_default_opacities: desc.parts.iter().map(|p| p.opacity).collect(),
+            param_max_val: NonNull::from(&*fake.parameter_max),
+            param_min_val: NonNull::from(&*fake.parameter_min),
+            param_key_values: desc.parameters.iter().map(|p| &*p.key_values).collect(),
+            param_types: vec![ParameterType::Normal; desc.parameters.len()].into_boxed_slice(),
+            param_repeats: desc.parameters.iter().map(|p| p.repeat).collect(),
+            drawable_texture_indices: NonNull::from(&*fake.texture_indices),
+            drawable_constant_flags: NonNull::from(&*fake.constant_flags),
+            drawable_indices: desc.drawables.iter().map(|d| &*d.indices).collect(),
+            drawable_masks: desc.drawables.iter().map(|d| &*d.masks).collect(),
+            drawable_vertex_counts: NonNull::from(&*fake.vertex_counts),
+            part_parents: NonNull::from(&*fake.part_parents),
+            part_tree: PartTree::new(id, &fake.part_parents, &fake.drawable_parents),
+            backend: MocBackend::Fake(fake),
+        }
     }
 
     pub(in crate) unsafe fn init_new_model(moc: *const csmMoc) -> AlignedMemory<csmModel> {
@@ -234,5 +692,76 @@ impl Moc {
     }
 }
 
+// Wraps the value around into [min, max) if `repeat` is set, clamps it
+// otherwise.
+pub(in crate) fn fit_value(value: f32, min: f32, max: f32, repeat: bool) -> f32 {
+    if repeat {
+        wrap(value, min, max)
+    } else {
+        value.max(min).min(max)
+    }
+}
+
+// Wraps the value around into [min, max).
+fn wrap(value: f32, min: f32, max: f32) -> f32 {
+    let range = max - min;
+    if range > 0.0 {
+        min + (value - min).rem_euclid(range)
+    } else {
+        min
+    }
+}
+
+// Returns the key closest to the value, or the value if there are no keys.
+pub(in crate) fn snap_to_key(keys: &[f32], value: f32) -> f32 {
+    keys.iter()
+        .copied()
+        .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+        .unwrap_or(value)
+}
+
 unsafe impl Send for Moc {}
 unsafe impl Sync for Moc {}
+
+#[test]
+fn parameter_value_helpers() {
+    assert_eq!(wrap(190.0, -180.0, 180.0), -170.0);
+    assert_eq!(wrap(-540.0, -180.0, 180.0), -180.0);
+    assert_eq!(wrap(5.0, 1.0, 1.0), 1.0);
+    assert_eq!(snap_to_key(&[-30.0, 0.0, 30.0], 14.0), 0.0);
+    assert_eq!(snap_to_key(&[-30.0, 0.0, 30.0], 16.0), 30.0);
+    assert_eq!(snap_to_key(&[], 16.0), 16.0);
+}
+
+// Needs the sample models of the sdk, skipped if `CUBISM_CORE` isn't set at
+// compile time.
+#[cfg(not(feature = "dynamic-load"))]
+#[test]
+fn moc_outlives_models() {
+    use crate::Model;
+
+    let core = match option_env!("CUBISM_CORE") {
+        Some(core) => std::path::Path::new(core).join("Samples/Res/Haru/Haru.moc3"),
+        None => return,
+    };
+    let data = std::fs::read(&core).unwrap();
+    let model = Model::from_bytes(&data).unwrap();
+    let ids: Vec<String> = model.moc().parameter_ids().iter().map(|&id| id.to_owned()).collect();
+    let moc = model.moc_arc();
+    drop(model);
+    assert_eq!(moc.parameter_ids(), &ids[..]);
+
+    let moc = Moc::from_bytes(&data).unwrap();
+    drop(Model::new(&moc));
+    assert_eq!(moc.parameter_ids(), &ids[..]);
+    assert_eq!(moc.parameter_ids().len(), moc.parameter_min().len());
+}
+
+#[cfg(not(any(feature = "dynamic-load", feature = "core-4-2")))]
+#[test]
+fn moc_consistency_unsupported() {
+    match Moc::from_bytes(b"not a moc") {
+        Err(MocError::ConsistencyUnsupported) => (),
+        res => panic!("unexpected result {:?}", res),
+    }
+}
@@ -0,0 +1,337 @@
+use std::convert::TryInto;
+
+use crate::{
+    error::{Moc3Error, Moc3Result},
+    moc::MocVersion,
+};
+
+const MAGIC: &[u8; 4] = b"MOC3";
+const HEADER_SIZE: usize = 64;
+const ID_SIZE: usize = 64;
+
+// Indices into the section offset table that follows the header.
+const SECTION_COUNTS: usize = 0;
+const SECTION_CANVAS: usize = 1;
+const SECTION_PART_IDS: usize = 3;
+const SECTION_ART_MESH_IDS: usize = 33;
+const SECTION_ART_MESH_TEXTURE_NOS: usize = 41;
+const SECTION_PARAMETER_IDS: usize = 50;
+const SECTION_PARAMETER_MAX: usize = 51;
+const SECTION_PARAMETER_MIN: usize = 52;
+const SECTION_PARAMETER_DEFAULT: usize = 53;
+
+// Indices into the count table.
+const COUNT_PARTS: usize = 0;
+const COUNT_ART_MESHES: usize = 4;
+const COUNT_PARAMETERS: usize = 5;
+
+/// The metadata of a moc3 file, read in pure rust without the native
+/// library.
+///
+/// This exposes the same static data as a [Moc](./struct.Moc.html) for tools
+/// that only need to inspect files. Corrupt or truncated files result in an
+/// error instead of a panic.
+#[derive(Clone, Debug)]
+pub struct MocMetadata {
+    version: MocVersion,
+    big_endian: bool,
+    canvas_size: [f32; 2],
+    canvas_origin: [f32; 2],
+    pixels_per_unit: f32,
+    part_ids: Box<[String]>,
+    parameter_ids: Box<[String]>,
+    drawable_ids: Box<[String]>,
+    parameter_max: Box<[f32]>,
+    parameter_min: Box<[f32]>,
+    parameter_default: Box<[f32]>,
+    drawable_texture_indices: Box<[i32]>,
+}
+
+impl MocMetadata {
+    /// Reads the metadata from the contents of a moc3 file.
+    ///
+    /// Ids that aren't valid utf-8 are converted lossily.
+    pub fn from_bytes<R: AsRef<[u8]>>(data: R) -> Moc3Result<Self> {
+        let data = data.as_ref();
+        if data.len() < HEADER_SIZE {
+            return Err(Moc3Error::Truncated("header"));
+        }
+        if &data[..4] != MAGIC {
+            return Err(Moc3Error::InvalidMagic);
+        }
+        let version = match MocVersion::from_raw(u32::from(data[4])) {
+            MocVersion::Unknown => return Err(Moc3Error::InvalidVersion(data[4])),
+            version => version,
+        };
+        let reader = Reader {
+            data,
+            big_endian: data[5] != 0,
+        };
+
+        let counts = reader.section(SECTION_COUNTS, COUNT_PARAMETERS + 1, 4, "counts")?;
+        let count = |idx: usize| reader.u32(&counts[idx * 4..]) as usize;
+        let (part_count, drawable_count, parameter_count) = (
+            count(COUNT_PARTS),
+            count(COUNT_ART_MESHES),
+            count(COUNT_PARAMETERS),
+        );
+        let canvas = reader.f32s(SECTION_CANVAS, 5, "canvas info")?;
+
+        Ok(MocMetadata {
+            version,
+            big_endian: reader.big_endian,
+            pixels_per_unit: canvas[0],
+            canvas_origin: [canvas[1], canvas[2]],
+            canvas_size: [canvas[3], canvas[4]],
+            part_ids: reader.ids(SECTION_PART_IDS, part_count, "part ids")?,
+            parameter_ids: reader.ids(SECTION_PARAMETER_IDS, parameter_count, "parameter ids")?,
+            drawable_ids: reader.ids(SECTION_ART_MESH_IDS, drawable_count, "drawable ids")?,
+            parameter_max: reader.f32s(
+                SECTION_PARAMETER_MAX,
+                parameter_count,
+                "parameter max values",
+            )?,
+            parameter_min: reader.f32s(
+                SECTION_PARAMETER_MIN,
+                parameter_count,
+                "parameter min values",
+            )?,
+            parameter_default: reader.f32s(
+                SECTION_PARAMETER_DEFAULT,
+                parameter_count,
+                "parameter default values",
+            )?,
+            drawable_texture_indices: reader.i32s(
+                SECTION_ART_MESH_TEXTURE_NOS,
+                drawable_count,
+                "drawable texture indices",
+            )?,
+        })
+    }
+
+    /// Returns the version of the file.
+    #[inline]
+    pub fn version(&self) -> MocVersion {
+        self.version
+    }
+
+    /// Returns whether the file has been written in big endian byte order.
+    #[inline]
+    pub fn is_big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    /// Returns information about the model's size, origin and
+    /// pixels-per-unit, like
+    /// [`Model::canvas_info`](./struct.Model.html#method.canvas_info).
+    #[inline]
+    pub fn canvas_info(&self) -> ([f32; 2], [f32; 2], f32) {
+        (self.canvas_size, self.canvas_origin, self.pixels_per_unit)
+    }
+
+    /// Returns the parameter names.
+    #[inline]
+    pub fn parameter_ids(&self) -> &[String] {
+        &self.parameter_ids
+    }
+
+    /// Returns the part names.
+    #[inline]
+    pub fn part_ids(&self) -> &[String] {
+        &self.part_ids
+    }
+
+    /// Returns the drawable names.
+    #[inline]
+    pub fn drawable_ids(&self) -> &[String] {
+        &self.drawable_ids
+    }
+
+    /// Returns the parameter max values.
+    #[inline]
+    pub fn parameter_max(&self) -> &[f32] {
+        &self.parameter_max
+    }
+
+    /// Returns the parameter min values.
+    #[inline]
+    pub fn parameter_min(&self) -> &[f32] {
+        &self.parameter_min
+    }
+
+    /// Returns the parameter default values.
+    #[inline]
+    pub fn parameter_default(&self) -> &[f32] {
+        &self.parameter_default
+    }
+
+    /// Returns the texture indices of the drawables.
+    #[inline]
+    pub fn drawable_texture_indices(&self) -> &[i32] {
+        &self.drawable_texture_indices
+    }
+
+    /// Returns the number of parameters.
+    #[inline]
+    pub fn parameter_count(&self) -> usize {
+        self.parameter_ids.len()
+    }
+
+    /// Returns the number of parts.
+    #[inline]
+    pub fn part_count(&self) -> usize {
+        self.part_ids.len()
+    }
+
+    /// Returns the number of drawables.
+    #[inline]
+    pub fn drawable_count(&self) -> usize {
+        self.drawable_ids.len()
+    }
+}
+
+// Bounds checked access to the sections of a moc3 file.
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = bytes[..4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    // Returns the data of the section with `count` elements of `size` bytes.
+    fn section(
+        &self,
+        section: usize,
+        count: usize,
+        size: usize,
+        what: &'static str,
+    ) -> Moc3Result<&'a [u8]> {
+        let entry = HEADER_SIZE + section * 4;
+        let offset = self
+            .data
+            .get(entry..entry + 4)
+            .ok_or(Moc3Error::Truncated("section offsets"))?;
+        let offset = self.u32(offset) as usize;
+        count
+            .checked_mul(size)
+            .and_then(|len| offset.checked_add(len))
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(Moc3Error::Truncated(what))
+    }
+
+    fn f32s(&self, section: usize, count: usize, what: &'static str) -> Moc3Result<Box<[f32]>> {
+        let data = self.section(section, count, 4, what)?;
+        Ok(data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_bits(self.u32(bytes)))
+            .collect())
+    }
+
+    fn i32s(&self, section: usize, count: usize, what: &'static str) -> Moc3Result<Box<[i32]>> {
+        let data = self.section(section, count, 4, what)?;
+        Ok(data
+            .chunks_exact(4)
+            .map(|bytes| self.u32(bytes) as i32)
+            .collect())
+    }
+
+    // Ids are stored as nul padded strings of a fixed size.
+    fn ids(&self, section: usize, count: usize, what: &'static str) -> Moc3Result<Box<[String]>> {
+        let data = self.section(section, count, ID_SIZE, what)?;
+        Ok(data
+            .chunks_exact(ID_SIZE)
+            .map(|id| {
+                let len = id.iter().position(|b| *b == 0).unwrap_or(ID_SIZE);
+                String::from_utf8_lossy(&id[..len]).into_owned()
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn moc3_metadata_read() {
+    // a minimal file with the count table at 0x400, the canvas info at 0x420
+    // and the remaining sections behind each other starting at 0x440
+    let mut data = vec![0u8; 0x440];
+    data[..4].copy_from_slice(MAGIC);
+    data[4] = 3;
+    let set_offset = |data: &mut Vec<u8>, section: usize, offset: usize| {
+        let entry = HEADER_SIZE + section * 4;
+        data[entry..entry + 4].copy_from_slice(&(offset as u32).to_le_bytes());
+    };
+    set_offset(&mut data, SECTION_COUNTS, 0x400);
+    set_offset(&mut data, SECTION_CANVAS, 0x420);
+    for (idx, count) in [
+        (COUNT_PARTS, 1u32),
+        (COUNT_ART_MESHES, 2),
+        (COUNT_PARAMETERS, 1),
+    ] {
+        data[0x400 + idx * 4..0x404 + idx * 4].copy_from_slice(&count.to_le_bytes());
+    }
+    for (idx, value) in [2.0f32, 0.5, 0.5, 1024.0, 2048.0].iter().enumerate() {
+        data[0x420 + idx * 4..0x424 + idx * 4].copy_from_slice(&value.to_le_bytes());
+    }
+    let push_section = |data: &mut Vec<u8>, section: usize, bytes: &[u8]| {
+        set_offset(data, section, data.len());
+        data.extend_from_slice(bytes);
+    };
+    let id = |id: &str| {
+        let mut bytes = [0u8; ID_SIZE];
+        bytes[..id.len()].copy_from_slice(id.as_bytes());
+        bytes
+    };
+    push_section(&mut data, SECTION_PART_IDS, &id("PartCore"));
+    push_section(&mut data, SECTION_PARAMETER_IDS, &id("ParamAngleX"));
+    push_section(
+        &mut data,
+        SECTION_ART_MESH_IDS,
+        &[id("ArtMesh0"), id("ArtMesh1")].concat(),
+    );
+    push_section(&mut data, SECTION_PARAMETER_MAX, &30.0f32.to_le_bytes());
+    push_section(&mut data, SECTION_PARAMETER_MIN, &(-30.0f32).to_le_bytes());
+    push_section(&mut data, SECTION_PARAMETER_DEFAULT, &0.0f32.to_le_bytes());
+    push_section(
+        &mut data,
+        SECTION_ART_MESH_TEXTURE_NOS,
+        &[0i32.to_le_bytes(), 1i32.to_le_bytes()].concat(),
+    );
+
+    let moc = MocMetadata::from_bytes(&data).unwrap();
+    assert_eq!(moc.version(), MocVersion::V4_0);
+    assert_eq!(moc.canvas_info(), ([1024.0, 2048.0], [0.5, 0.5], 2.0));
+    assert_eq!(moc.part_ids(), ["PartCore"]);
+    assert_eq!(moc.parameter_ids(), ["ParamAngleX"]);
+    assert_eq!(moc.drawable_ids(), ["ArtMesh0", "ArtMesh1"]);
+    assert_eq!(moc.parameter_min(), [-30.0]);
+    assert_eq!(moc.parameter_max(), [30.0]);
+    assert_eq!(moc.drawable_texture_indices(), [0, 1]);
+
+    // truncating the file or corrupting a count fails instead of panicking
+    let truncated = &data[..data.len() - 4];
+    assert!(matches!(
+        MocMetadata::from_bytes(truncated),
+        Err(Moc3Error::Truncated("drawable texture indices"))
+    ));
+    let mut corrupt = data.clone();
+    corrupt[0x400 + COUNT_PARAMETERS * 4..0x404 + COUNT_PARAMETERS * 4]
+        .copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(MocMetadata::from_bytes(&corrupt).is_err());
+    assert!(matches!(
+        MocMetadata::from_bytes(&data[..32]),
+        Err(Moc3Error::Truncated("header"))
+    ));
+    let mut magic = data.clone();
+    magic[3] = b'2';
+    assert!(matches!(
+        MocMetadata::from_bytes(&magic),
+        Err(Moc3Error::InvalidMagic)
+    ));
+}
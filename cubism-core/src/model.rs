@@ -2,13 +2,21 @@ use std::{iter, mem, ops, ptr::NonNull, slice, sync::Arc};
 
 use ffi::csmModel;
 
-use crate::{error::MocResult, mem::AlignedMemory, moc::Moc, ConstantFlags, DynamicFlags};
+use crate::{
+    error::MocResult,
+    fake::{FakeModel, ModelDescription},
+    index::{DrawableIndex, MocId, ParameterIndex, PartIndex},
+    mem::AlignedMemory,
+    moc::{Moc, ParameterType},
+    ConstantFlags, DynamicFlags,
+};
 
 /// This represents a model.
 ///
 /// A model shares its underlying [Moc](./struct.Moc.html) with other models
-/// that have been cloned from this one. Because of this it is preferred to
-/// clone models, instead of creating new ones from the same data.
+/// that have been cloned from this one or created from the same moc with
+/// [`Model::new`](#method.new). Because of this it is preferred to share mocs,
+/// instead of creating new models from the same data.
 ///
 /// Slices returned by functions have to be indexed by the drawable, parameter
 /// or part index for the individual value. If the functions takes an index
@@ -16,42 +24,114 @@ use crate::{error::MocResult, mem::AlignedMemory, moc::Moc, ConstantFlags, Dynam
 /// slice are values that all belong to the drawable.
 #[derive(Debug)]
 pub struct Model {
-    mem: AlignedMemory<csmModel>,
+    backend: ModelBackend,
     moc: Arc<Moc>,
     param_val: NonNull<[f32]>,
     part_opacities: NonNull<[f32]>,
+    render_orders: NonNull<[i32]>,
+    draw_orders: NonNull<[i32]>,
+    opacities: NonNull<[f32]>,
+    dynamic_flags: NonNull<[DynamicFlags]>,
+    vertex_positions: Box<[NonNull<[[f32; 2]]>]>,
+    vertex_uvs: Box<[NonNull<[[f32; 2]]>]>,
+    // `None` if the linked library doesn't support multiply and screen colors
+    multiply_colors: Option<NonNull<[[f32; 4]]>>,
+    screen_colors: Option<NonNull<[[f32; 4]]>>,
+}
+
+// The memory backing a model.
+#[derive(Debug)]
+enum ModelBackend {
+    Native(AlignedMemory<csmModel>),
+    Fake(FakeModel),
 }
 
 impl Model {
+    /// Creates a model of the moc, with the parameters and part opacities set
+    /// to their default values.
+    pub fn new(moc: &Arc<Moc>) -> Self {
+        if moc.is_fake() {
+            Self::new_fake(moc.clone())
+        } else {
+            unsafe { Self::new_native(moc.clone()) }
+        }
+    }
+
     /// Creates a model instance from bytes.
+    ///
+    /// The data is checked for consistency before it is loaded, failing with
+    /// [`MocError::InconsistentMocData`] if it is malformed. Libraries older
+    /// than Cubism Core 4.2 can't do this check, loading fails with
+    /// [`MocError::ConsistencyUnsupported`] then. Use
+    /// [`supports_moc_consistency`] to find out whether it is available and
+    /// [`from_bytes_unchecked`] to load trusted files without it.
+    ///
+    /// [`MocError::InconsistentMocData`]: ./enum.MocError.html#variant.InconsistentMocData
+    /// [`MocError::ConsistencyUnsupported`]: ./enum.MocError.html#variant.ConsistencyUnsupported
+    /// [`supports_moc_consistency`]: ./fn.supports_moc_consistency.html
+    /// [`from_bytes_unchecked`]: #method.from_bytes_unchecked
     #[inline]
     pub fn from_bytes<R: AsRef<[u8]>>(data: R) -> MocResult<Self> {
-        unsafe { Moc::new(data.as_ref()).map(|(moc, mem)| Self::new_impl(Arc::new(moc), mem)) }
+        unsafe { Moc::new(data.as_ref(), true).map(|moc| Self::new_native(Arc::new(moc))) }
+    }
+
+    /// Creates a model simulated in pure rust from the description, which
+    /// doesn't need the native library. See
+    /// [`ModelDescription`](./struct.ModelDescription.html) for what is
+    /// simulated.
+    ///
+    /// # Panics
+    /// Panics if the description references parts, drawables or parameters
+    /// that don't exist, if the parents of the parts form a cycle, or if the
+    /// vertex data of a drawable is inconsistent.
+    pub fn from_description(description: ModelDescription) -> Self {
+        Self::new_fake(Arc::new(Moc::from_description(description)))
+    }
+
+    /// Creates a model instance from bytes without checking the consistency
+    /// of the data first, which saves some time for files that are known to
+    /// be valid.
+    ///
+    /// # Safety
+    /// The data has to be a well formed moc, malformed data can crash the
+    /// native library.
+    #[inline]
+    pub unsafe fn from_bytes_unchecked<R: AsRef<[u8]>>(data: R) -> MocResult<Self> {
+        Moc::new(data.as_ref(), false).map(|moc| Self::new_native(Arc::new(moc)))
     }
 
     /// Returns the first parameter with the given name, or `None` if there is
     /// none with the given name.
     pub fn parameter(&self, name: &str) -> Option<Parameter<'_>> {
-        self.parameter_ids()
-            .iter()
-            .enumerate()
-            .find_map(|(idx, id)| {
-                if *id == name {
-                    Some(self.parameter_at(idx))
-                } else {
-                    None
-                }
-            })
+        self.parameter_index(name)
+            .map(|idx| self.parameter_at(idx.index()))
     }
 
     /// Returns the first parameter with the given name, or `None` if there is
     /// none with the given name.
     pub fn parameter_mut(&mut self, name: &str) -> Option<ParameterMut<'_>> {
-        if let Some(idx) = self.parameter_ids().iter().position(|id| *id == name) {
-            Some(self.parameter_at_mut(idx))
-        } else {
-            None
-        }
+        self.parameter_index(name)
+            .map(move |idx| self.parameter_at_mut(idx.index()))
+    }
+
+    /// Returns the parameter of the index handle.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn parameter_by_index(&self, idx: ParameterIndex) -> Parameter<'_> {
+        self.check_moc_id(idx.moc_id());
+        self.parameter_at(idx.index())
+    }
+
+    /// Returns the parameter of the index handle.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn parameter_by_index_mut(&mut self, idx: ParameterIndex) -> ParameterMut<'_> {
+        self.check_moc_id(idx.moc_id());
+        self.parameter_at_mut(idx.index())
     }
 
     /// Returns the parameter at the specified index.
@@ -68,6 +148,9 @@ impl Model {
                 min_value: *self.parameter_min().get_unchecked(idx),
                 max_value: *self.parameter_max().get_unchecked(idx),
                 default_value: *self.parameter_default().get_unchecked(idx),
+                key_values: self.parameter_key_values().get_unchecked(idx),
+                parameter_type: *self.parameter_types().get_unchecked(idx),
+                repeat: *self.parameter_repeats().get_unchecked(idx),
             }
         }
     }
@@ -83,12 +166,17 @@ impl Model {
             let min_value = *self.parameter_min().get_unchecked(idx);
             let max_value = *self.parameter_max().get_unchecked(idx);
             let default_value = *self.parameter_default().get_unchecked(idx);
+            let parameter_type = *self.parameter_types().get_unchecked(idx);
+            let repeat = *self.parameter_repeats().get_unchecked(idx);
             ParameterMut {
                 id: &self.moc.parameter_ids.get_unchecked(idx),
+                key_values: self.moc.param_key_values.get_unchecked(idx),
                 value: self.parameter_values_mut().get_unchecked_mut(idx),
                 min_value,
                 max_value,
                 default_value,
+                parameter_type,
+                repeat,
             }
         }
     }
@@ -96,23 +184,34 @@ impl Model {
     /// Returns the first part with the given name, or `None` if there is none
     /// with the given name.
     pub fn part(&self, name: &str) -> Option<Part<'_>> {
-        self.part_ids().iter().enumerate().find_map(|(idx, id)| {
-            if *id == name {
-                Some(self.part_at(idx))
-            } else {
-                None
-            }
-        })
+        self.part_index(name).map(|idx| self.part_at(idx.index()))
     }
 
     /// Returns the first part with the given name, or `None` if there is none
     /// with the given name.
     pub fn part_mut(&mut self, name: &str) -> Option<PartMut<'_>> {
-        if let Some(idx) = self.part_ids().iter().position(|id| *id == name) {
-            Some(self.part_at_mut(idx))
-        } else {
-            None
-        }
+        self.part_index(name)
+            .map(move |idx| self.part_at_mut(idx.index()))
+    }
+
+    /// Returns the part of the index handle.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn part_by_index(&self, idx: PartIndex) -> Part<'_> {
+        self.check_moc_id(idx.moc_id());
+        self.part_at(idx.index())
+    }
+
+    /// Returns the part of the index handle.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn part_by_index_mut(&mut self, idx: PartIndex) -> PartMut<'_> {
+        self.check_moc_id(idx.moc_id());
+        self.part_at_mut(idx.index())
     }
 
     /// Returns the parameter at the specified idx.
@@ -142,16 +241,18 @@ impl Model {
     /// Returns the first drawable with the given name, or `None` if there is
     /// none with the given name.
     pub fn drawable(&self, name: &str) -> Option<Drawable<'_>> {
-        self.drawable_ids()
-            .iter()
-            .enumerate()
-            .find_map(|(idx, id)| {
-                if *id == name {
-                    Some(self.drawable_at(idx))
-                } else {
-                    None
-                }
-            })
+        self.drawable_index(name)
+            .map(|idx| self.drawable_at(idx.index()))
+    }
+
+    /// Returns the drawable of the index handle.
+    ///
+    /// # Panics
+    /// Panics if the index belongs to a different moc.
+    #[inline]
+    pub fn drawable_by_index(&self, idx: DrawableIndex) -> Drawable<'_> {
+        self.check_moc_id(idx.moc_id());
+        self.drawable_at(idx.index())
     }
 
     /// Returns the drawable at the specified index.
@@ -171,7 +272,16 @@ impl Model {
                 vertex_positions: self.drawable_vertex_positions(idx),
                 vertex_uvs: self.drawable_vertex_uvs(idx),
                 opacity: *self.drawable_opacities().get_unchecked(idx),
+                multiply_color: self
+                    .drawable_multiply_colors()
+                    .map(|colors| *colors.get_unchecked(idx)),
+                screen_color: self
+                    .drawable_screen_colors()
+                    .map(|colors| *colors.get_unchecked(idx)),
                 masks: self.drawable_masks().get_unchecked(idx),
+                parent_part: self
+                    .part_tree()
+                    .drawable_parent(DrawableIndex::new(self.moc.id(), idx)),
                 constant_flags: *self.drawable_constant_flags().get_unchecked(idx),
                 dynamic_flags: *self.drawable_dynamic_flags().get_unchecked(idx),
             }
@@ -234,11 +344,61 @@ impl Model {
     /// If the value of a parent is -1 it means the part is the root.
     #[inline]
     pub fn part_parents(&self) -> &[i32] {
+        self.moc.part_parents()
+    }
+
+    /// Returns the opacity of the part multiplied with the opacities of all
+    /// of its ancestors, as set in [`part_opacities`](#method.part_opacities).
+    ///
+    /// # Panics
+    /// Panics on out of bounds access.
+    #[inline]
+    pub fn effective_part_opacity(&self, idx: usize) -> f32 {
+        self.part_tree()
+            .effective_opacity(PartIndex::new(self.moc.id(), idx), self.part_opacities())
+    }
+
+    /// Returns the opacity the drawable inherits from its part, which is the
+    /// effective opacity of that part or 1.0 if the drawable has no part.
+    ///
+    /// Unlike [`drawable_opacities`](#method.drawable_opacities) this reflects
+    /// the current part opacities without updating the model, and doesn't
+    /// include the opacity of the drawable itself.
+    ///
+    /// # Panics
+    /// Panics on out of bounds access.
+    #[inline]
+    pub fn effective_drawable_opacity(&self, idx: usize) -> f32 {
+        self.part_tree()
+            .drawable_parent(DrawableIndex::new(self.moc.id(), idx))
+            .map_or(1.0, |part| {
+                self.part_tree()
+                    .effective_opacity(part, self.part_opacities())
+            })
+    }
+
+    /// Sets the parameters and part opacities back to their default values,
+    /// reusing the model's memory. The drawables reflect this after the next
+    /// [`update`](#method.update).
+    pub fn reset_to_defaults(&mut self) {
         unsafe {
-            slice::from_raw_parts(
-                ffi::csmGetPartParentPartIndices(self.as_ptr()),
-                self.part_count(),
-            )
+            self.param_val
+                .as_mut()
+                .copy_from_slice(self.moc.parameter_default());
+            self.part_opacities
+                .as_mut()
+                .copy_from_slice(self.moc.part_default_opacities());
+        }
+    }
+
+    /// Returns the size in bytes of the memory allocated for the native
+    /// model, not including the shared moc. Models created from a
+    /// [`ModelDescription`](./struct.ModelDescription.html) report 0.
+    #[inline]
+    pub fn memory_size(&self) -> usize {
+        match &self.backend {
+            ModelBackend::Native(mem) => mem.layout().size(),
+            ModelBackend::Fake(_) => 0,
         }
     }
 
@@ -246,20 +406,32 @@ impl Model {
     /// This has to be called before accessing the drawables.
     #[inline]
     pub fn update(&mut self) {
-        // FIXME: is this order correct? This is what the pdf says, but the framework
-        // implementation has it reversed
-        unsafe { ffi::csmResetDrawableDynamicFlags(self.mem.as_ptr()) };
-        unsafe { ffi::csmUpdateModel(self.mem.as_ptr()) };
+        match &mut self.backend {
+            ModelBackend::Native(mem) => {
+                // FIXME: is this order correct? This is what the pdf says, but the framework
+                // implementation has it reversed
+                unsafe { ffi::csmResetDrawableDynamicFlags(mem.as_ptr()) };
+                unsafe { ffi::csmUpdateModel(mem.as_ptr()) };
+            },
+            ModelBackend::Fake(fake) => {
+                let moc = self.moc.fake().expect("the moc of a fake model is fake");
+                fake.update(moc, self.moc.part_tree());
+            },
+        }
     }
 
     /// Returns information about this models size, origin and pixels-per-unit.
     pub fn canvas_info(&self) -> ([f32; 2], [f32; 2], f32) {
+        if let Some(moc) = self.moc.fake() {
+            let desc = &moc.description;
+            return (desc.canvas_size, desc.canvas_origin, desc.pixels_per_unit);
+        }
         let mut size = [0.0; 2];
         let mut origin = [0.0; 2];
         let mut ppu = 0.0;
         unsafe {
             ffi::csmReadCanvasInfo(
-                self.mem.as_ptr(),
+                self.as_ptr(),
                 &mut size as *mut _ as *mut _,
                 &mut origin as *mut _ as *mut _,
                 &mut ppu,
@@ -271,67 +443,57 @@ impl Model {
     /// Returns the render orders of the drawables.
     #[inline]
     pub fn drawable_render_orders(&self) -> &[i32] {
-        unsafe {
-            slice::from_raw_parts(
-                ffi::csmGetDrawableRenderOrders(self.as_ptr()),
-                self.drawable_count(),
-            )
-        }
+        unsafe { self.render_orders.as_ref() }
     }
 
     /// Returns the draw orders of the drawables.
     #[inline]
     pub fn drawable_draw_orders(&self) -> &[i32] {
-        unsafe {
-            slice::from_raw_parts(
-                ffi::csmGetDrawableDrawOrders(self.as_ptr()),
-                self.drawable_count(),
-            )
-        }
+        unsafe { self.draw_orders.as_ref() }
     }
 
     /// Returns the vertex positions of the drawable at the specified index.
     #[inline]
     pub fn drawable_vertex_positions(&self, idx: usize) -> &[[f32; 2]] {
-        unsafe {
-            slice::from_raw_parts(
-                *ffi::csmGetDrawableVertexPositions(self.as_ptr()).add(idx) as *const _,
-                self.drawable_vertex_counts()[idx] as usize,
-            )
-        }
+        unsafe { self.vertex_positions[idx].as_ref() }
     }
 
     /// Returns the uv coordinates of the drawable at the specified index.
     #[inline]
     pub fn drawable_vertex_uvs(&self, idx: usize) -> &[[f32; 2]] {
-        unsafe {
-            slice::from_raw_parts(
-                *ffi::csmGetDrawableVertexUvs(self.as_ptr()).add(idx) as *const _,
-                self.drawable_vertex_counts()[idx] as usize,
-            )
-        }
+        unsafe { self.vertex_uvs[idx].as_ref() }
     }
 
     /// Returns the drawable opacities.
     #[inline]
     pub fn drawable_opacities(&self) -> &[f32] {
-        unsafe {
-            slice::from_raw_parts(
-                ffi::csmGetDrawableOpacities(self.as_ptr()),
-                self.drawable_count(),
-            )
-        }
+        unsafe { self.opacities.as_ref() }
+    }
+
+    /// Returns the multiply colors of the drawables as rgba, or `None` if the
+    /// linked library is older than Cubism Core 4.2 or linked without the
+    /// `core-4-2` feature, see
+    /// [`supports_blend_colors`](./fn.supports_blend_colors.html).
+    #[inline]
+    pub fn drawable_multiply_colors(&self) -> Option<&[[f32; 4]]> {
+        self.multiply_colors
+            .map(|colors| unsafe { &*colors.as_ptr() })
+    }
+
+    /// Returns the screen colors of the drawables as rgba, or `None` if the
+    /// linked library is older than Cubism Core 4.2 or linked without the
+    /// `core-4-2` feature, see
+    /// [`supports_blend_colors`](./fn.supports_blend_colors.html).
+    #[inline]
+    pub fn drawable_screen_colors(&self) -> Option<&[[f32; 4]]> {
+        self.screen_colors
+            .map(|colors| unsafe { &*colors.as_ptr() })
     }
 
     /// Returns the [DynamicFlags](./struct.DynamicFlags.html).
     #[inline]
     pub fn drawable_dynamic_flags(&self) -> &[DynamicFlags] {
-        unsafe {
-            slice::from_raw_parts(
-                ffi::csmGetDrawableDynamicFlags(self.as_ptr()) as *const DynamicFlags,
-                self.drawable_count(),
-            )
-        }
+        unsafe { self.dynamic_flags.as_ref() }
     }
 
     /// Returns a reference to the underlying [Moc](./struct.Moc.html).
@@ -349,10 +511,15 @@ impl Model {
     }
 
     /// Returns the raw
-    /// [csmModel](../cubism_core_sys/model/struct.csmModel.html) ptr.
+    /// [csmModel](../cubism_core_sys/model/struct.csmModel.html) ptr, which
+    /// is null if the model has been created from a
+    /// [`ModelDescription`](./struct.ModelDescription.html).
     #[inline]
     pub fn as_ptr(&self) -> *mut csmModel {
-        self.mem.as_ptr()
+        match &self.backend {
+            ModelBackend::Native(mem) => mem.as_ptr(),
+            ModelBackend::Fake(_) => std::ptr::null_mut(),
+        }
     }
 
     /// Returns an iterator over the model's parameters.
@@ -402,29 +569,106 @@ impl Model {
 }
 
 impl Model {
-    unsafe fn new_impl(moc: Arc<Moc>, mem: AlignedMemory<ffi::csmModel>) -> Model {
-        let param_values = NonNull::from(slice::from_raw_parts_mut(
-            ffi::csmGetParameterValues(mem.as_ptr()),
-            moc.parameter_count(),
-        ));
-        let part_opacities = NonNull::from(slice::from_raw_parts_mut(
-            ffi::csmGetPartOpacities(mem.as_ptr()),
-            moc.part_count(),
-        ));
+    #[inline]
+    fn check_moc_id(&self, id: MocId) {
+        assert!(
+            id == self.moc.id(),
+            "the index belongs to a different moc than the model's"
+        );
+    }
+
+    unsafe fn new_native(moc: Arc<Moc>) -> Model {
+        let mem = Moc::init_new_model(moc.as_ptr());
+        let ptr = mem.as_ptr();
+        let drawable_count = moc.drawable_count();
+        let vertex_data = |data: *mut *const ffi::csmVector2| -> Box<[NonNull<[[f32; 2]]>]> {
+            slice::from_raw_parts(data, drawable_count)
+                .iter()
+                .zip(moc.drawable_vertex_counts())
+                .map(|(data, count)| {
+                    NonNull::from(slice::from_raw_parts(*data as *const _, *count as usize))
+                })
+                .collect()
+        };
+        let vertex_positions = vertex_data(ffi::csmGetDrawableVertexPositions(ptr));
+        let vertex_uvs = vertex_data(ffi::csmGetDrawableVertexUvs(ptr));
+        let blend_colors = crate::supports_blend_colors();
+        let colors = |data: *const ffi::csmVector4| {
+            Some(NonNull::from(slice::from_raw_parts(
+                data as *const [f32; 4],
+                drawable_count,
+            )))
+        };
+
+        Model {
+            param_val: NonNull::from(slice::from_raw_parts_mut(
+                ffi::csmGetParameterValues(ptr),
+                moc.parameter_count(),
+            )),
+            part_opacities: NonNull::from(slice::from_raw_parts_mut(
+                ffi::csmGetPartOpacities(ptr),
+                moc.part_count(),
+            )),
+            render_orders: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetDrawableRenderOrders(ptr),
+                drawable_count,
+            )),
+            draw_orders: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetDrawableDrawOrders(ptr),
+                drawable_count,
+            )),
+            opacities: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetDrawableOpacities(ptr),
+                drawable_count,
+            )),
+            dynamic_flags: NonNull::from(slice::from_raw_parts(
+                ffi::csmGetDrawableDynamicFlags(ptr) as *const DynamicFlags,
+                drawable_count,
+            )),
+            vertex_positions,
+            vertex_uvs,
+            multiply_colors: if blend_colors {
+                colors(ffi::csmGetDrawableMultiplyColors(ptr))
+            } else {
+                None
+            },
+            screen_colors: if blend_colors {
+                colors(ffi::csmGetDrawableScreenColors(ptr))
+            } else {
+                None
+            },
+            backend: ModelBackend::Native(mem),
+            moc,
+        }
+    }
+
+    fn new_fake(moc: Arc<Moc>) -> Model {
+        let mut fake = FakeModel::new(moc.fake().expect("the moc has to be fake"));
+        let vertex_data = |data: &[Box<[[f32; 2]]>]| -> Box<[NonNull<[[f32; 2]]>]> {
+            data.iter().map(|data| NonNull::from(&**data)).collect()
+        };
 
+        // the boxed slices don't move when the model does
         Model {
-            mem,
+            param_val: NonNull::from(&mut *fake.parameter_values),
+            part_opacities: NonNull::from(&mut *fake.part_opacities),
+            render_orders: NonNull::from(&*fake.render_orders),
+            draw_orders: NonNull::from(&*fake.draw_orders),
+            opacities: NonNull::from(&*fake.opacities),
+            dynamic_flags: NonNull::from(&*fake.dynamic_flags),
+            vertex_positions: vertex_data(&fake.vertex_positions),
+            vertex_uvs: vertex_data(&fake.vertex_uvs),
+            multiply_colors: Some(NonNull::from(&*fake.multiply_colors)),
+            screen_colors: Some(NonNull::from(&*fake.screen_colors)),
+            backend: ModelBackend::Fake(fake),
             moc,
-            param_val: param_values,
-            part_opacities,
         }
     }
 }
 
 impl Clone for Model {
     fn clone(&self) -> Self {
-        let model_mem = unsafe { Moc::init_new_model(self.moc.as_ptr()) };
-        let mut model = unsafe { Self::new_impl(self.moc_arc(), model_mem) };
+        let mut model = Self::new(&self.moc);
         model
             .parameter_values_mut()
             .copy_from_slice(self.parameter_values());
@@ -466,6 +710,26 @@ pub struct Parameter<'model> {
     pub max_value: f32,
     /// The parameter's default value
     pub default_value: f32,
+    /// The values of the parameter's keyforms in ascending order
+    pub key_values: &'model [f32],
+    /// The parameter's type
+    pub parameter_type: ParameterType,
+    /// Whether the parameter's value wraps around instead of being clamped
+    pub repeat: bool,
+}
+
+impl Parameter<'_> {
+    /// Returns the keyform value closest to the value, or the value itself if
+    /// the parameter has no known keyforms.
+    pub fn snap_to_key(&self, value: f32) -> f32 {
+        crate::moc::snap_to_key(self.key_values, value)
+    }
+
+    /// Fits the value into the parameter's range, wrapping it around if the
+    /// parameter repeats and clamping it otherwise.
+    pub fn fit_value(&self, value: f32) -> f32 {
+        crate::moc::fit_value(value, self.min_value, self.max_value, self.repeat)
+    }
 }
 
 /// A parameter of a model.
@@ -481,6 +745,26 @@ pub struct ParameterMut<'model> {
     pub max_value: f32,
     /// The parameter's default value
     pub default_value: f32,
+    /// The values of the parameter's keyforms in ascending order
+    pub key_values: &'model [f32],
+    /// The parameter's type
+    pub parameter_type: ParameterType,
+    /// Whether the parameter's value wraps around instead of being clamped
+    pub repeat: bool,
+}
+
+impl ParameterMut<'_> {
+    /// Sets the value to the keyform value closest to it, if the parameter
+    /// has known keyforms.
+    pub fn snap_to_key(&mut self) {
+        *self.value = crate::moc::snap_to_key(self.key_values, *self.value);
+    }
+
+    /// Sets the value, wrapping it around if the parameter repeats and
+    /// clamping it to the parameter's range otherwise.
+    pub fn set_fitted(&mut self, value: f32) {
+        *self.value = crate::moc::fit_value(value, self.min_value, self.max_value, self.repeat);
+    }
 }
 
 /// A part of a model.
@@ -522,8 +806,16 @@ pub struct Drawable<'model> {
     pub vertex_uvs: &'model [[f32; 2]],
     /// The drawable's opacity.
     pub opacity: f32,
+    /// The drawable's multiply color as rgba, `None` if the linked library
+    /// doesn't support it.
+    pub multiply_color: Option<[f32; 4]>,
+    /// The drawable's screen color as rgba, `None` if the linked library
+    /// doesn't support it.
+    pub screen_color: Option<[f32; 4]>,
     /// The drawable's masks.
     pub masks: &'model [i32],
+    /// The index of the part the drawable belongs to.
+    pub parent_part: Option<PartIndex>,
     /// The drawable's constant drawing flags.
     pub constant_flags: ConstantFlags,
     /// The drawable's dynamic drawing flags.
@@ -131,6 +131,7 @@ fn main() {
         sc_desc.format,
         textures,
     );
+    let mut view_matrix = cubism::matrix::ViewMatrix::new(width as f32, height as f32);
     let mut last_frame = Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
@@ -149,7 +150,8 @@ fn main() {
                     sc_desc.width = width;
                     sc_desc.height = height;
                     swap_chain = device.create_swap_chain(&surface, &sc_desc);
-                }
+                    view_matrix.resize(width as f32, height as f32);
+                },
                 _ => (),
             },
             Event::RedrawRequested(_) => {
@@ -176,9 +178,11 @@ fn main() {
                     }],
                     depth_stencil_attachment: None,
                 });
+                let mvp = view_matrix.mvp(haru.model_matrix());
+                model_renderer.set_mvp(&device, &mut encoder, mvp.as_array());
                 model_renderer.draw_model(&device, &frame.view, &mut encoder, &haru);
                 queue.submit(&[encoder.finish()]);
-            }
+            },
             Event::MainEventsCleared => (),
             _ => (),
         }
@@ -0,0 +1,218 @@
+//! Per-frame accumulation of parameter values and part opacities.
+//!
+//! Instead of writing into a [`Model`] directly, motions, expressions and
+//! controllers submit contributions to an [`Accumulator`], which resolves them
+//! into the model at once. This makes the result independent of the order the
+//! contributions have been submitted in.
+use cubism_core::{MocId, Model, ParameterIndex, PartIndex};
+
+use crate::json::expression::ExpressionBlendType;
+
+/// How a contribution is combined with the value it applies to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Replaces the value, fading from it by the weight.
+    Overwrite,
+    /// Adds to the value, scaled by the weight.
+    Add,
+    /// Multiplies the value, fading from 1.0 by the weight.
+    Multiply,
+}
+
+impl From<ExpressionBlendType> for BlendMode {
+    fn from(ty: ExpressionBlendType) -> Self {
+        match ty {
+            ExpressionBlendType::Add => BlendMode::Add,
+            ExpressionBlendType::Multiply => BlendMode::Multiply,
+            ExpressionBlendType::Overwrite => BlendMode::Overwrite,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Channel {
+    base: Vec<f32>,
+    // weighted sum of the overwriting values and the sum of their weights
+    overwrite: Vec<(f32, f32)>,
+    add: Vec<f32>,
+    multiply: Vec<f32>,
+}
+
+impl Channel {
+    fn begin(&mut self, base: &[f32]) {
+        self.base.clear();
+        self.base.extend_from_slice(base);
+        self.overwrite.clear();
+        self.overwrite.resize(base.len(), (0.0, 0.0));
+        self.add.clear();
+        self.add.resize(base.len(), 0.0);
+        self.multiply.clear();
+        self.multiply.resize(base.len(), 1.0);
+    }
+
+    fn submit(&mut self, idx: usize, mode: BlendMode, value: f32, weight: f32) {
+        match mode {
+            BlendMode::Overwrite => {
+                let (sum, weights) = &mut self.overwrite[idx];
+                *sum += value * weight;
+                *weights += weight;
+            },
+            BlendMode::Add => self.add[idx] += value * weight,
+            BlendMode::Multiply => self.multiply[idx] *= (value - 1.0).mul_add(weight, 1.0),
+        }
+    }
+
+    fn resolve(&self, idx: usize) -> f32 {
+        let mut value = self.base[idx];
+        let (sum, weights) = self.overwrite[idx];
+        if weights > 0.0 {
+            let target = sum / weights;
+            value += (target - value) * weights.min(1.0);
+        }
+        (value + self.add[idx]) * self.multiply[idx]
+    }
+}
+
+/// Collects the contributions to the parameter values and part opacities of a
+/// model for a frame.
+///
+/// Contributions are resolved per value: overwriting contributions are
+/// averaged by their weights and faded in from the base value by the sum of
+/// their weights, then the additive ones are added and the multiplicative
+/// ones are multiplied. Parameter values are then clamped to their range, or
+/// wrapped around for repeating parameters, and part opacities are clamped to
+/// [0.0, 1.0].
+///
+/// Indices passed to the accessing and submitting functions have to belong to
+/// the moc of the model the accumulator has been started with, indices of a
+/// different moc panic.
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    moc: Option<MocId>,
+    parameters: Channel,
+    parts: Channel,
+    weight: f32,
+}
+
+impl Accumulator {
+    /// Creates a new empty Accumulator.
+    pub fn new() -> Self {
+        Accumulator {
+            moc: None,
+            parameters: Channel::default(),
+            parts: Channel::default(),
+            weight: 1.0,
+        }
+    }
+
+    /// Starts a new frame, taking the current values of the model as the base
+    /// values and discarding all contributions.
+    pub fn begin(&mut self, model: &Model) {
+        self.moc = Some(model.id());
+        self.parameters.begin(model.parameter_values());
+        self.parts.begin(model.part_opacities());
+        self.weight = 1.0;
+    }
+
+    /// The base value of the parameter at the index, as it was when the frame
+    /// started.
+    pub fn parameter_value(&self, idx: ParameterIndex) -> f32 {
+        self.check_moc_id(idx.moc_id());
+        self.parameters.base[idx.index()]
+    }
+
+    /// The base opacity of the part at the index, as it was when the frame
+    /// started.
+    pub fn part_opacity(&self, idx: PartIndex) -> f32 {
+        self.check_moc_id(idx.moc_id());
+        self.parts.base[idx.index()]
+    }
+
+    /// Submits a contribution to the parameter at the index. The weight is
+    /// scaled by the weight set with [`Accumulator::set_weight`].
+    pub fn submit_parameter(
+        &mut self,
+        idx: ParameterIndex,
+        mode: BlendMode,
+        value: f32,
+        weight: f32,
+    ) {
+        self.check_moc_id(idx.moc_id());
+        self.parameters
+            .submit(idx.index(), mode, value, weight * self.weight);
+    }
+
+    /// Submits a contribution to the opacity of the part at the index. The
+    /// weight is scaled by the weight set with [`Accumulator::set_weight`].
+    pub fn submit_part_opacity(
+        &mut self,
+        idx: PartIndex,
+        mode: BlendMode,
+        value: f32,
+        weight: f32,
+    ) {
+        self.check_moc_id(idx.moc_id());
+        self.parts
+            .submit(idx.index(), mode, value, weight * self.weight);
+    }
+
+    /// Sets the factor all following contributions are weighted with. This is
+    /// used by the [`ControllerMap`] to apply the weights of the controllers.
+    ///
+    /// [`ControllerMap`]: ../controller/struct.ControllerMap.html
+    pub fn set_weight(&mut self, weight: f32) {
+        self.weight = weight;
+    }
+
+    /// The factor all contributions are currently weighted with.
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Resolves the contributions and writes the results into the model.
+    pub fn resolve(&self, model: &mut Model) {
+        for idx in 0..self.parameters.base.len() {
+            let value = model.fit_parameter_value(idx, self.parameters.resolve(idx));
+            model.parameter_values_mut()[idx] = value;
+        }
+        for (idx, opacity) in model.part_opacities_mut().iter_mut().enumerate() {
+            *opacity = self.parts.resolve(idx).clamp(0.0, 1.0);
+        }
+    }
+}
+
+impl Accumulator {
+    fn check_moc_id(&self, id: MocId) {
+        assert!(
+            self.moc == Some(id),
+            "the index belongs to a different moc than the accumulator's"
+        );
+    }
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn channel_resolve_order_independent() {
+    let mut channel = Channel::default();
+    channel.begin(&[1.0, 1.0]);
+    let contributions = [
+        (BlendMode::Overwrite, 3.0, 0.5),
+        (BlendMode::Add, 1.0, 1.0),
+        (BlendMode::Overwrite, 5.0, 0.5),
+        (BlendMode::Multiply, 2.0, 0.5),
+    ];
+    for &(mode, value, weight) in contributions.iter() {
+        channel.submit(0, mode, value, weight);
+    }
+    for &(mode, value, weight) in contributions.iter().rev() {
+        channel.submit(1, mode, value, weight);
+    }
+    // the overwrites average to 4.0 at full weight, + 1.0, * 1.5
+    assert_eq!(channel.resolve(0), 7.5);
+    assert_eq!(channel.resolve(0), channel.resolve(1));
+}
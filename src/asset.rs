@@ -0,0 +1,234 @@
+//! Sources the files of a model package are loaded from.
+use fxhash::FxHashMap;
+
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::error::{CubismError, CubismResult};
+use crate::json::{self, JsonFile, Parsed};
+
+#[cfg(feature = "zip")]
+mod archive;
+#[cfg(feature = "zip")]
+pub use self::archive::ZipSource;
+
+/// A source of asset files, like the filesystem or an archive.
+///
+/// Paths passed to a source are relative to whatever the source considers its
+/// root, and use `/` or the platform separator.
+pub trait AssetSource {
+    /// Reads the whole file at the given path.
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>>;
+
+    /// Returns the path of the file at the given path as seen from outside of
+    /// this source, which is used to report errors. Sources that prefix paths
+    /// should include the prefix.
+    fn full_path(&self, path: &Path) -> PathBuf {
+        path.to_owned()
+    }
+}
+
+impl<S: AssetSource + ?Sized> AssetSource for &S {
+    #[inline]
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        (**self).read(path)
+    }
+
+    #[inline]
+    fn full_path(&self, path: &Path) -> PathBuf {
+        (**self).full_path(path)
+    }
+}
+
+impl<S: AssetSource + ?Sized> AssetSource for Box<S> {
+    #[inline]
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        (**self).read(path)
+    }
+
+    #[inline]
+    fn full_path(&self, path: &Path) -> PathBuf {
+        (**self).full_path(path)
+    }
+}
+
+/// Reads the file at the given path, attaching the full path to the error.
+pub(crate) fn read<'s>(source: &'s dyn AssetSource, path: &Path) -> CubismResult<Cow<'s, [u8]>> {
+    source
+        .read(path)
+        .map_err(|e| CubismError::read_file(source.full_path(path), e))
+}
+
+/// Reads and parses the json file at the given path, checking its version.
+pub(crate) fn read_json_file<T: JsonFile>(
+    source: &dyn AssetSource,
+    path: &Path,
+) -> CubismResult<Parsed<T>> {
+    let data = read(source, path)?;
+    json::parse(&data).map_err(|e| CubismError::format(source.full_path(path), e))
+}
+
+/// Reads and parses the json file at the given path, discarding the format
+/// warnings.
+pub(crate) fn read_json<T: JsonFile>(source: &dyn AssetSource, path: &Path) -> CubismResult<T> {
+    read_json_file(source, path).map(|parsed| parsed.value)
+}
+
+/// Lexically normalizes a path, removing `.` components and resolving `..`
+/// components. Returns `None` if the path escapes its root or is absolute.
+pub(crate) fn normalize_path(path: &Path) -> Option<PathBuf> {
+    let mut ret = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => ret.push(c),
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !ret.pop() {
+                    return None;
+                }
+            },
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(ret)
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("asset {} does not exist", path.display()),
+    )
+}
+
+/// An [`AssetSource`] reading files from the filesystem, with paths relative
+/// to the working directory.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FileSystem;
+
+impl AssetSource for FileSystem {
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        fs::read(path).map(Cow::Owned)
+    }
+}
+
+/// An [`AssetSource`] serving files from memory, for example embedded with
+/// `include_bytes!`.
+///
+/// Paths are normalized on insertion and lookup, so `a/./b` and `a/c/../b`
+/// refer to the same file.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySource {
+    files: FxHashMap<PathBuf, Cow<'static, [u8]>>,
+}
+
+impl MemorySource {
+    /// Creates a new empty MemorySource.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a file, returning the previous contents under the same path if
+    /// they exist.
+    ///
+    /// # Panics
+    /// Panics if the path is absolute or escapes the root through `..`.
+    pub fn insert(
+        &mut self,
+        path: impl AsRef<Path>,
+        data: impl Into<Cow<'static, [u8]>>,
+    ) -> Option<Cow<'static, [u8]>> {
+        let path = path.as_ref();
+        let key = normalize_path(path)
+            .unwrap_or_else(|| panic!("invalid asset path: {}", path.display()));
+        self.files.insert(key, data.into())
+    }
+
+    /// Removes and returns the file at the path if it exists.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<Cow<'static, [u8]>> {
+        normalize_path(path.as_ref()).and_then(|key| self.files.remove(&key))
+    }
+
+    /// The paths of all files in this source.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(|p| &**p)
+    }
+}
+
+impl AssetSource for MemorySource {
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        normalize_path(path)
+            .and_then(|key| self.files.get(&key))
+            .map(|data| Cow::Borrowed(&**data))
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+/// An [`AssetSource`] that prefixes every path with a directory before
+/// passing it on to the inner source.
+#[derive(Clone, Debug)]
+pub struct Prefixed<S> {
+    prefix: PathBuf,
+    inner: S,
+}
+
+impl<S: AssetSource> Prefixed<S> {
+    /// Creates a source resolving paths relative to `prefix` in `inner`.
+    pub fn new(prefix: impl Into<PathBuf>, inner: S) -> Self {
+        Prefixed {
+            prefix: prefix.into(),
+            inner,
+        }
+    }
+
+    /// The directory prepended to all paths.
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    /// The wrapped source.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: AssetSource> AssetSource for Prefixed<S> {
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        self.inner.read(&self.prefix.join(path))
+    }
+
+    fn full_path(&self, path: &Path) -> PathBuf {
+        self.inner.full_path(&self.prefix.join(path))
+    }
+}
+
+#[test]
+fn normalize_path_resolves_dots() {
+    assert_eq!(
+        normalize_path(Path::new("a/./b/../c")),
+        Some(PathBuf::from("a/c"))
+    );
+    assert_eq!(normalize_path(Path::new("a/../../c")), None);
+    assert_eq!(normalize_path(Path::new("/a")), None);
+}
+
+#[test]
+fn memory_source_prefixed() {
+    let mut mem = MemorySource::new();
+    mem.insert("models/haru/haru.moc3", &b"moc"[..]);
+    let source = Prefixed::new("models/haru", &mem);
+    assert_eq!(&*source.read(Path::new("./haru.moc3")).unwrap(), b"moc");
+    assert_eq!(
+        source.read(Path::new("../haru.moc3")).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    // errors name the path including the prefix
+    match read(&source, Path::new("missing.moc3")) {
+        Err(CubismError::ReadFile { path, .. }) => {
+            assert_eq!(path, Path::new("models/haru/missing.moc3"))
+        },
+        _ => panic!("reading a missing file succeeded"),
+    }
+}
@@ -0,0 +1,150 @@
+use zip::{result::ZipError, ZipArchive};
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use crate::asset::{normalize_path, AssetSource};
+
+// The default maximum size of a single decompressed file.
+const DEFAULT_SIZE_LIMIT: u64 = 256 * 1024 * 1024;
+// The size stated by an entry is untrusted, buffers grow beyond this on
+// demand.
+const MAX_PREALLOCATION: u64 = 16 * 1024 * 1024;
+
+fn zip_to_io_error(e: ZipError) -> io::Error {
+    match e {
+        ZipError::Io(e) => e,
+        ZipError::FileNotFound => io::Error::new(io::ErrorKind::NotFound, e),
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+/// An [`AssetSource`] reading files from a zip archive without extracting
+/// it.
+///
+/// Paths are normalized before lookup, paths that escape the archive root
+/// through `..` are rejected. Files decompressing to more than the size limit,
+/// 256 MiB by default, fail to read.
+#[derive(Debug)]
+pub struct ZipSource<R> {
+    archive: RefCell<ZipArchive<R>>,
+    size_limit: u64,
+}
+
+impl<R: Read + Seek> ZipSource<R> {
+    /// Opens the zip archive in the reader.
+    pub fn new(reader: R) -> io::Result<Self> {
+        ZipArchive::new(reader)
+            .map(|archive| ZipSource {
+                archive: RefCell::new(archive),
+                size_limit: DEFAULT_SIZE_LIMIT,
+            })
+            .map_err(zip_to_io_error)
+    }
+
+    /// Returns the path of the model3.json file in this archive. If there are
+    /// multiple the one closest to the archive root is returned.
+    pub fn find_model3(&self) -> Option<PathBuf> {
+        let archive = self.archive.borrow();
+        archive
+            .file_names()
+            .filter(|name| name.ends_with(".model3.json"))
+            .min_by_key(|name| (name.matches('/').count(), *name))
+            .map(PathBuf::from)
+    }
+
+    /// Sets the maximum size in bytes a file may decompress to.
+    pub fn set_size_limit(&mut self, size_limit: u64) {
+        self.size_limit = size_limit;
+    }
+
+    /// The maximum size in bytes a file may decompress to.
+    pub fn size_limit(&self) -> u64 {
+        self.size_limit
+    }
+
+    /// Consumes this source, returning the underlying archive.
+    pub fn into_inner(self) -> ZipArchive<R> {
+        self.archive.into_inner()
+    }
+}
+
+impl<R: Read + Seek> AssetSource for ZipSource<R> {
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        let normalized = normalize_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("asset path {} escapes the archive root", path.display()),
+            )
+        })?;
+        // zip entries always use forward slashes
+        let name = normalized
+            .iter()
+            .map(|c| c.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let mut archive = self.archive.borrow_mut();
+        let file = archive.by_name(&name).map_err(zip_to_io_error)?;
+        let capacity = file.size().min(self.size_limit).min(MAX_PREALLOCATION);
+        let mut buf = Vec::with_capacity(capacity as usize);
+        // read one byte past the limit to tell whether it has been exceeded
+        file.take(self.size_limit.saturating_add(1))
+            .read_to_end(&mut buf)?;
+        if buf.len() as u64 > self.size_limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "archive entry {} exceeds the size limit of {} bytes",
+                    name, self.size_limit
+                ),
+            ));
+        }
+        Ok(Cow::Owned(buf))
+    }
+}
+
+#[test]
+fn zip_source_lookup() {
+    use std::io::{Cursor, Write};
+    use zip::{write::FileOptions, ZipWriter};
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for name in &["pkg/model.model3.json", "pkg/motions/idle.motion3.json"] {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(name.as_bytes()).unwrap();
+    }
+    let source = ZipSource::new(writer.finish().unwrap()).unwrap();
+
+    assert_eq!(
+        source.find_model3(),
+        Some(PathBuf::from("pkg/model.model3.json"))
+    );
+    assert_eq!(
+        &*source
+            .read(Path::new("pkg/./motions/../motions/idle.motion3.json"))
+            .unwrap(),
+        b"pkg/motions/idle.motion3.json"
+    );
+    assert_eq!(
+        source
+            .read(Path::new("pkg/../../etc/passwd"))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    let mut source = source;
+    source.set_size_limit(b"pkg/model.model3.json".len() as u64);
+    assert!(source.read(Path::new("pkg/model.model3.json")).is_ok());
+    assert_eq!(
+        source
+            .read(Path::new("pkg/motions/idle.motion3.json"))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidData
+    );
+}
@@ -1,31 +1,53 @@
 //! Controller definitions.
-use fxhash::FxHashMap;
+use fxhash::FxBuildHasher;
+use indexmap::{Equivalent, IndexMap};
 
-use std::any::TypeId;
+use std::{any::TypeId, borrow::Cow, sync::Arc};
 
-use cubism_core::Model;
+use cubism_core::{Moc, Model};
+
+use crate::accumulator::Accumulator;
 
 mod expression;
 pub use self::expression::ExpressionController;
 mod eye_blink;
 pub use self::eye_blink::EyeBlink;
+mod pose;
+pub use self::pose::Pose;
 
 /// Priorities used by the standard controllers of this crate.
 pub mod default_priorities {
     /// The eyeblink controller priority.
     pub const EYE_BLINK: usize = 100;
-    /// The eyeblink controller priority.
+    /// The expression controller priority.
     pub const EXPRESSION: usize = 200;
+    /// The physics controller priority.
+    pub const PHYSICS: usize = 250;
+    /// The pose controller priority.
+    pub const POSE: usize = 300;
 }
 
 /// The controller trait. A controller is an object that modifies a models
 /// parameter and part values in a defined fashion.
 pub trait Controller: 'static {
-    /// Run the controller on the passed [`Model`].
-    fn update_parameters(&mut self, model: &mut Model, delta: f32);
+    /// Run the controller on the passed [`Model`], submitting its changes to
+    /// the [`Accumulator`]. The model holds the values resolved from the
+    /// controllers that ran before, except for the contributions of the ones
+    /// with the same priority.
+    fn update_parameters(&mut self, model: &Model, accumulator: &mut Accumulator, delta: f32);
     /// The execution priority of this controller. The smallest value has the
     /// highest priority.
     fn priority(&self) -> usize;
+    /// Resets the internal state of this controller, for example after the
+    /// model has been reloaded or teleported.
+    fn reset(&mut self, _model: &mut Model) {}
+    /// Called when this controller is added to a [`ControllerMap`] that is
+    /// attached to a moc, or when the map gets attached. This is the place to
+    /// resolve ids once instead of every update.
+    fn on_attach(&mut self, _moc: &Moc) {}
+    /// Called when this controller is removed from a [`ControllerMap`] that is
+    /// attached to a moc, or when the map gets detached.
+    fn on_detach(&mut self, _moc: &Moc) {}
 }
 
 impl dyn Controller {
@@ -40,72 +62,263 @@ impl dyn Controller {
     }
 }
 
-/// A ControllerMap is basically a typemap over [`Controller`]s, it only allows
-/// one controller per type to exist and tracks their enabled status.
+/// Identifies a controller in a [`ControllerMap`] by its type and a name.
+/// Controllers registered without a name use the empty name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ControllerKey {
+    type_id: TypeId,
+    name: Cow<'static, str>,
+}
+
+impl ControllerKey {
+    /// The key of the unnamed controller of the type.
+    pub fn of<C: Controller>() -> Self {
+        Self::named::<C>("")
+    }
+
+    /// The key of the controller of the type with the given name.
+    pub fn named<C: Controller>(name: impl Into<Cow<'static, str>>) -> Self {
+        ControllerKey {
+            type_id: TypeId::of::<C>(),
+            name: name.into(),
+        }
+    }
+
+    /// The name of the controller.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// A borrowed ControllerKey, hashing the same way so that lookups by name don't
+// have to allocate.
+#[derive(Hash)]
+struct KeyRef<'a> {
+    type_id: TypeId,
+    name: &'a str,
+}
+
+impl KeyRef<'_> {
+    fn of<C: Controller>(name: &str) -> KeyRef<'_> {
+        KeyRef {
+            type_id: TypeId::of::<C>(),
+            name,
+        }
+    }
+}
+
+impl Equivalent<ControllerKey> for KeyRef<'_> {
+    fn equivalent(&self, key: &ControllerKey) -> bool {
+        self.type_id == key.type_id && self.name == key.name
+    }
+}
+
+/// A ControllerMap is basically a typemap over [`Controller`]s, keyed by the
+/// controller type and an optional name, so that multiple instances of the
+/// same type can exist. It tracks their enabled status and the order they
+/// run in.
+///
+/// Controllers run in the order of their priority, controllers of equal
+/// priority run in the order they have been registered in. Explicit orderings
+/// added with [`ControllerMap::order`] take precedence over priorities.
 pub struct ControllerMap {
-    map: FxHashMap<TypeId, (Box<dyn Controller>, bool)>,
+    map: IndexMap<ControllerKey, Entry, FxBuildHasher>,
+    // (first, second) pairs, first runs before second
+    orderings: Vec<(ControllerKey, ControllerKey)>,
+    moc: Option<Arc<Moc>>,
+}
+
+struct Entry {
+    controller: Box<dyn Controller>,
+    enabled: bool,
+    weight: f32,
 }
 
 impl ControllerMap {
     /// Creates a new empty controller map.
     pub fn new() -> Self {
         ControllerMap {
-            map: FxHashMap::with_hasher(Default::default()),
+            map: IndexMap::with_hasher(Default::default()),
+            orderings: Vec::new(),
+            moc: None,
         }
     }
 
     /// Registers a new controller, unregistering and returning back the
-    /// previous controller of the same type if it exists.
+    /// previous unnamed controller of the same type if it exists.
     pub fn register<C: Controller>(&mut self, controller: C) -> Option<Box<C>> {
-        let controller: Box<dyn Controller> = Box::new(controller);
+        self.register_named("", controller)
+    }
+
+    /// Registers a new controller under the name, unregistering and returning
+    /// back the previous controller of the same type and name if it exists.
+    ///
+    /// A replaced controller keeps its position in the registration order.
+    pub fn register_named<C: Controller>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        controller: C,
+    ) -> Option<Box<C>> {
+        let mut controller: Box<dyn Controller> = Box::new(controller);
+        if let Some(moc) = self.moc.as_ref() {
+            controller.on_attach(moc);
+        }
+        let entry = Entry {
+            controller,
+            enabled: true,
+            weight: 1.0,
+        };
         self.map
-            .insert(TypeId::of::<C>(), (controller, true))
-            .map(|(old, _)| unsafe { old.downcast_unchecked() })
+            .insert(ControllerKey::named::<C>(name), entry)
+            .map(|old| unsafe { self.detached(old).downcast_unchecked() })
     }
 
-    /// Removes and returns the controller of the type if it exists in the map.
+    fn detached(&self, mut entry: Entry) -> Box<dyn Controller> {
+        if let Some(moc) = self.moc.as_ref() {
+            entry.controller.on_detach(moc);
+        }
+        entry.controller
+    }
+
+    /// Removes and returns the unnamed controller of the type if it exists in
+    /// the map.
     pub fn remove<C: Controller>(&mut self) -> Option<Box<C>> {
+        self.remove_named("")
+    }
+
+    /// Removes and returns the controller of the type with the name if it
+    /// exists in the map.
+    pub fn remove_named<C: Controller>(&mut self, name: &str) -> Option<Box<C>> {
         self.map
-            .remove(&TypeId::of::<C>())
-            .map(|(old, _)| unsafe { old.downcast_unchecked() })
+            .shift_remove(&KeyRef::of::<C>(name))
+            .map(|old| unsafe { self.detached(old).downcast_unchecked() })
     }
 
-    /// Returns a reference to the controller of the type if it exists in the
-    /// map.
+    /// Returns a reference to the unnamed controller of the type if it exists
+    /// in the map.
     pub fn get<C: Controller>(&self) -> Option<&C> {
+        self.get_named("")
+    }
+
+    /// Returns a reference to the controller of the type with the name if it
+    /// exists in the map.
+    pub fn get_named<C: Controller>(&self, name: &str) -> Option<&C> {
         self.map
-            .get(&TypeId::of::<C>())
-            .map(|(con, _)| unsafe { (&**con).downcast_ref_unchecked() })
+            .get(&KeyRef::of::<C>(name))
+            .map(|entry| unsafe { entry.controller.downcast_ref_unchecked() })
     }
 
-    /// Returns a mutable reference to the controller of the type if it exists
-    /// in the map.
+    /// Returns a mutable reference to the unnamed controller of the type if it
+    /// exists in the map.
     pub fn get_mut<C: Controller>(&mut self) -> Option<&mut C> {
+        self.get_named_mut("")
+    }
+
+    /// Returns a mutable reference to the controller of the type with the name
+    /// if it exists in the map.
+    pub fn get_named_mut<C: Controller>(&mut self, name: &str) -> Option<&mut C> {
+        self.map
+            .get_mut(&KeyRef::of::<C>(name))
+            .map(|entry| unsafe { entry.controller.downcast_mut_unchecked() })
+    }
+
+    /// Returns an iterator over the names and controllers of the type, in
+    /// registration order.
+    pub fn instances<C: Controller>(&self) -> impl Iterator<Item = (&str, &C)> {
         self.map
-            .get_mut(&TypeId::of::<C>())
-            .map(|(con, _)| unsafe { (&mut **con).downcast_mut_unchecked() })
+            .iter()
+            .filter(|(key, _)| key.type_id == TypeId::of::<C>())
+            .map(|(key, entry)| {
+                let con = unsafe { entry.controller.downcast_ref_unchecked() };
+                (&*key.name, con)
+            })
     }
 
-    /// Enables or disables the controller of the type. Does nothing if there is
-    /// no controller registered under the type.
+    /// Enables or disables the unnamed controller of the type. Does nothing if
+    /// there is no controller registered under the type.
     pub fn set_enabled<C: Controller>(&mut self, enabled: bool) {
-        if let Some((_, en)) = self.map.get_mut(&TypeId::of::<C>()) {
-            *en = enabled;
+        self.set_named_enabled::<C>("", enabled)
+    }
+
+    /// Enables or disables the controller of the type with the name. Does
+    /// nothing if there is no such controller.
+    pub fn set_named_enabled<C: Controller>(&mut self, name: &str, enabled: bool) {
+        if let Some(entry) = self.map.get_mut(&KeyRef::of::<C>(name)) {
+            entry.enabled = enabled;
         }
     }
 
-    /// Returns true whether the controller is enabled or not. If it doesn't
-    /// exist it returns false.
+    /// Returns true whether the unnamed controller is enabled or not. If it
+    /// doesn't exist it returns false.
     pub fn is_enabled<C: Controller>(&self) -> bool {
+        self.is_named_enabled::<C>("")
+    }
+
+    /// Returns true whether the controller of the type with the name is
+    /// enabled or not. If it doesn't exist it returns false.
+    pub fn is_named_enabled<C: Controller>(&self, name: &str) -> bool {
         self.map
-            .get(&TypeId::of::<C>())
-            .map(|&(_, enabled)| enabled)
+            .get(&KeyRef::of::<C>(name))
+            .map(|entry| entry.enabled)
             .unwrap_or(false)
     }
 
-    /// Checks whether the controller type has been registered or not.
+    /// Checks whether an unnamed controller of the type has been registered or
+    /// not.
     pub fn is_registered<C: Controller>(&self) -> bool {
-        self.map.contains_key(&TypeId::of::<C>())
+        self.is_named_registered::<C>("")
+    }
+
+    /// Checks whether a controller of the type with the name has been
+    /// registered or not.
+    pub fn is_named_registered<C: Controller>(&self, name: &str) -> bool {
+        self.map.contains_key(&KeyRef::of::<C>(name))
+    }
+
+    /// Requires the controller with the key `first` to run before the one
+    /// with the key `second`, regardless of their priorities. The keys don't
+    /// have to be registered yet.
+    ///
+    /// Orderings are transitive through disabled controllers, if a runs
+    /// before b and b before c, a runs before c even while b is disabled.
+    ///
+    /// Returns false and ignores the ordering if it would contradict the
+    /// existing orderings.
+    pub fn order(&mut self, first: ControllerKey, second: ControllerKey) -> bool {
+        if first == second || self.runs_before(&second, &first) {
+            return false;
+        }
+        self.orderings.push((first, second));
+        true
+    }
+
+    /// Removes all orderings involving the controller with the key.
+    pub fn clear_orderings(&mut self, key: &ControllerKey) {
+        self.orderings
+            .retain(|(first, second)| first != key && second != key);
+    }
+
+    // Whether the orderings require `first` to run before `second`, directly
+    // or transitively.
+    fn runs_before(&self, first: &ControllerKey, second: &ControllerKey) -> bool {
+        let mut stack = vec![first];
+        let mut visited = Vec::new();
+        while let Some(key) = stack.pop() {
+            if key == second {
+                return true;
+            }
+            if visited.contains(&key) {
+                continue;
+            }
+            visited.push(key);
+            stack.extend(
+                self.orderings
+                    .iter()
+                    .filter(|(f, _)| f == key)
+                    .map(|(_, s)| s),
+            );
+        }
+        false
     }
 
     /// Returns an iterator over the enabled controllers.
@@ -114,7 +327,8 @@ impl ControllerMap {
     ) -> impl Iterator<Item = &'this dyn Controller> + 'this {
         self.map
             .values()
-            .filter_map(|&(ref con, enabled)| if enabled { Some(&**con) } else { None })
+            .filter(|entry| entry.enabled)
+            .map(|entry| &*entry.controller)
     }
 
     /// Returns an iterator over the enabled controllers.
@@ -123,30 +337,170 @@ impl ControllerMap {
     ) -> impl Iterator<Item = &'this mut dyn Controller> + 'this {
         self.map
             .values_mut()
-            .filter_map(|&mut (ref mut con, enabled)| if enabled { Some(&mut **con) } else { None })
+            .filter(|entry| entry.enabled)
+            .map(|entry| &mut *entry.controller)
     }
 
     /// Returns an iterator over the controllers in this map.
     pub fn controllers<'this>(&'this self) -> impl Iterator<Item = &'this dyn Controller> + 'this {
-        self.map.values().map(|(con, _)| &**con)
+        self.map.values().map(|entry| &*entry.controller)
     }
 
     /// Returns an iterator over the controllers in this map.
     pub fn controllers_mut<'this>(
         &'this mut self,
     ) -> impl Iterator<Item = &'this mut dyn Controller> + 'this {
-        self.map.values_mut().map(|(con, _)| &mut **con)
+        self.map.values_mut().map(|entry| &mut *entry.controller)
+    }
+
+    /// Returns the keys of the enabled controllers in the order they are run
+    /// in by [`update_enabled_controllers`].
+    ///
+    /// [`update_enabled_controllers`]: #method.update_enabled_controllers
+    pub fn execution_order(&self) -> Vec<&ControllerKey> {
+        self.execution_order_indices()
+            .into_iter()
+            .map(|idx| self.map.get_index(idx).unwrap().0)
+            .collect()
+    }
+
+    // The map indices of the enabled controllers in execution order. Among the
+    // controllers whose predecessors have all run, the one with the smallest
+    // priority and registration index runs next.
+    fn execution_order_indices(&self) -> Vec<usize> {
+        // (map index, number of predecessors that haven't been ordered yet).
+        // Disabled controllers are ordered as well and skipped afterwards, so
+        // that they don't break transitive orderings. Orderings whose first
+        // controller isn't registered don't hold back their second one.
+        let mut pending: Vec<(usize, usize)> = (0..self.map.len()).map(|idx| (idx, 0)).collect();
+        for (first, second) in self.orderings.iter() {
+            if !self.map.contains_key(first) {
+                continue;
+            }
+            if let Some(successor) = self.map.get_index_of(second) {
+                pending[successor].1 += 1;
+            }
+        }
+
+        let mut order = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            // `order` keeps the orderings acyclic, so there always is a
+            // controller without pending predecessors
+            let next = pending
+                .iter()
+                .enumerate()
+                .filter(|(_, &(_, predecessors))| predecessors == 0)
+                .min_by_key(|(_, &(idx, _))| (self.map[idx].controller.priority(), idx))
+                .map_or(0, |(pos, _)| pos);
+            let (idx, _) = pending.remove(next);
+            let key = self.map.get_index(idx).unwrap().0;
+            for (_, second) in self.orderings.iter().filter(|(first, _)| first == key) {
+                if let Some(successor) = self.map.get_index_of(second) {
+                    if let Some(entry) = pending.iter_mut().find(|(idx, _)| *idx == successor) {
+                        entry.1 = entry.1.saturating_sub(1);
+                    }
+                }
+            }
+            if self.map[idx].enabled {
+                order.push(idx);
+            }
+        }
+        order
     }
 
     /// Calls [`update_parameters`] on every enabled controller in the order of
-    /// their priority.
-    pub fn update_enabled_controllers(&mut self, model: &mut Model, delta: f32) {
-        let mut controllers = self.enabled_controllers_mut().collect::<Vec<_>>();
-        controllers.sort_unstable_by_key(|c| c.priority());
-        for con in controllers {
-            con.update_parameters(model, delta);
+    /// their priority and orderings.
+    ///
+    /// [`update_parameters`]: trait.Controller.html#tymethod.update_parameters
+    ///
+    /// The contributions of every controller are scaled by its weight. The
+    /// accumulator is resolved into the model whenever the priority changes,
+    /// so controllers see the output of the ones that ran before them. The
+    /// contributions of the last priority have to be resolved afterwards.
+    pub fn update_enabled_controllers(
+        &mut self,
+        model: &mut Model,
+        accumulator: &mut Accumulator,
+        delta: f32,
+    ) {
+        let weight = accumulator.weight();
+        let mut stage = None;
+        for idx in self.execution_order_indices() {
+            let entry = &mut self.map[idx];
+            let priority = entry.controller.priority();
+            if matches!(stage, Some(stage) if stage != priority) {
+                accumulator.resolve(model);
+                accumulator.begin(model);
+            }
+            stage = Some(priority);
+            accumulator.set_weight(weight * entry.weight);
+            entry
+                .controller
+                .update_parameters(model, accumulator, delta);
+        }
+        accumulator.set_weight(weight);
+    }
+
+    /// Sets the weight of the unnamed controller of the type. Does nothing if
+    /// there is no controller registered under the type.
+    ///
+    /// The weight is clamped to [0.0, 1.0], a weight of 0.0 runs the
+    /// controller without any visible effect.
+    pub fn set_weight<C: Controller>(&mut self, weight: f32) {
+        self.set_named_weight::<C>("", weight)
+    }
+
+    /// Sets the weight of the controller of the type with the name. Does
+    /// nothing if there is no such controller.
+    pub fn set_named_weight<C: Controller>(&mut self, name: &str, weight: f32) {
+        if let Some(entry) = self.map.get_mut(&KeyRef::of::<C>(name)) {
+            entry.weight = weight.clamp(0.0, 1.0);
         }
     }
+
+    /// The weight of the unnamed controller of the type, if it exists.
+    pub fn weight<C: Controller>(&self) -> Option<f32> {
+        self.named_weight::<C>("")
+    }
+
+    /// The weight of the controller of the type with the name, if it exists.
+    pub fn named_weight<C: Controller>(&self, name: &str) -> Option<f32> {
+        self.map
+            .get(&KeyRef::of::<C>(name))
+            .map(|entry| entry.weight)
+    }
+
+    /// Resets every controller in this map.
+    pub fn reset_controllers(&mut self, model: &mut Model) {
+        for entry in self.map.values_mut() {
+            entry.controller.reset(model);
+        }
+    }
+
+    /// Attaches this map to the moc, detaching it from the previous one.
+    /// Controllers are notified through [`Controller::on_attach`] and
+    /// [`Controller::on_detach`].
+    pub fn attach(&mut self, moc: Arc<Moc>) {
+        self.detach();
+        for entry in self.map.values_mut() {
+            entry.controller.on_attach(&moc);
+        }
+        self.moc = Some(moc);
+    }
+
+    /// Detaches this map from its moc, returning it.
+    pub fn detach(&mut self) -> Option<Arc<Moc>> {
+        let moc = self.moc.take()?;
+        for entry in self.map.values_mut() {
+            entry.controller.on_detach(&moc);
+        }
+        Some(moc)
+    }
+
+    /// The moc this map is attached to.
+    pub fn moc(&self) -> Option<&Arc<Moc>> {
+        self.moc.as_ref()
+    }
 }
 
 impl Default for ControllerMap {
@@ -154,3 +508,141 @@ impl Default for ControllerMap {
         Self::new()
     }
 }
+
+#[test]
+fn controller_map_execution_order() {
+    struct Prio(usize);
+    impl Controller for Prio {
+        fn update_parameters(&mut self, _: &Model, _: &mut Accumulator, _: f32) {}
+        fn priority(&self) -> usize {
+            self.0
+        }
+    }
+    struct Other;
+    impl Controller for Other {
+        fn update_parameters(&mut self, _: &Model, _: &mut Accumulator, _: f32) {}
+        fn priority(&self) -> usize {
+            0
+        }
+    }
+
+    let mut map = ControllerMap::new();
+    map.register_named("c", Prio(2));
+    map.register_named("a", Prio(1));
+    map.register_named("b", Prio(1));
+    map.register(Other);
+    assert_eq!(map.instances::<Prio>().count(), 3);
+    let names = |map: &ControllerMap| {
+        map.execution_order()
+            .into_iter()
+            .map(|key| key.name().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&map), ["", "a", "b", "c"]);
+
+    assert!(map.order(
+        ControllerKey::named::<Prio>("c"),
+        ControllerKey::named::<Prio>("a")
+    ));
+    assert!(map.order(
+        ControllerKey::named::<Prio>("a"),
+        ControllerKey::of::<Other>()
+    ));
+    assert!(!map.order(
+        ControllerKey::of::<Other>(),
+        ControllerKey::named::<Prio>("c")
+    ));
+    assert_eq!(names(&map), ["b", "c", "a", ""]);
+
+    map.set_named_enabled::<Prio>("a", false);
+    // c still runs before the other controller through the disabled a
+    assert_eq!(names(&map), ["b", "c", ""]);
+    assert!(map.remove_named::<Prio>("c").is_some());
+    assert!(map.get_named::<Prio>("c").is_none());
+    // the ordering of the removed c doesn't hold back a anymore
+    map.set_named_enabled::<Prio>("a", true);
+    assert_eq!(names(&map), ["a", "", "b"]);
+}
+
+#[test]
+fn controller_map_unregistered_ordering() {
+    struct Prio(usize);
+    impl Controller for Prio {
+        fn update_parameters(&mut self, _: &Model, _: &mut Accumulator, _: f32) {}
+        fn priority(&self) -> usize {
+            self.0
+        }
+    }
+
+    let mut map = ControllerMap::new();
+    map.register_named("late", Prio(300));
+    map.register_named("mid", Prio(200));
+    map.register_named("early", Prio(100));
+    let names = |map: &ControllerMap| {
+        map.execution_order()
+            .into_iter()
+            .map(|key| key.name().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert!(map.order(
+        ControllerKey::named::<Prio>("missing"),
+        ControllerKey::named::<Prio>("early")
+    ));
+    assert_eq!(names(&map), ["early", "mid", "late"]);
+
+    // the ordering applies once the first controller is registered
+    map.register_named("missing", Prio(400));
+    assert_eq!(names(&map), ["mid", "late", "missing", "early"]);
+    assert!(map.remove_named::<Prio>("missing").is_some());
+    assert_eq!(names(&map), ["early", "mid", "late"]);
+}
+
+#[test]
+fn controller_map_fake_model() {
+    use crate::json::pose::Pose3;
+    use cubism_core::{ModelDescription, ParameterDescription, PartDescription};
+
+    let mut model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new("ParamEye", 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new("PartA", 0.0, 1.0, 0.0))
+            .with_parameter(ParameterDescription::new("PartB", 0.0, 1.0, 1.0))
+            .with_part(PartDescription::new("PartA"))
+            .with_part(PartDescription::new("PartB")),
+    );
+    let pose3: Pose3 = r#"{
+        "Type": "Live2D Pose",
+        "Groups": [[{ "Id": "PartA", "Link": [] }, { "Id": "PartB", "Link": [] }]]
+    }"#
+    .parse()
+    .unwrap();
+    let eye = model.parameter_index("ParamEye").unwrap();
+
+    let mut map = ControllerMap::new();
+    map.register(EyeBlink::new(vec![eye], 0.0, 1.0, 1.0, 0.25));
+    let mut pose = Pose::from_pose3(&model, &pose3);
+    pose.set_fade_in_time(0.0);
+    map.register(pose);
+    map.set_weight::<EyeBlink>(0.5);
+
+    let mut accumulator = Accumulator::new();
+    let mut update = |map: &mut ControllerMap, model: &mut Model, delta: f32| {
+        model.reset_to_defaults();
+        accumulator.begin(model);
+        map.update_enabled_controllers(model, &mut accumulator, delta);
+        accumulator.resolve(model);
+    };
+    update(&mut map, &mut model, 0.0);
+    assert_eq!(model.parameter_by_index(eye).value, 1.0);
+    // the closed eye is faded in by the weight of the controller
+    update(&mut map, &mut model, 0.25);
+    assert_eq!(model.parameter_by_index(eye).value, 0.5);
+    // the pose shows the second part, whose parameter is set
+    assert_eq!(model.part_opacities(), [0.0, 1.0]);
+
+    map.set_enabled::<Pose>(false);
+    map.set_weight::<EyeBlink>(0.0);
+    update(&mut map, &mut model, 0.0);
+    assert_eq!(model.parameter_by_index(eye).value, 1.0);
+    assert_eq!(model.part_opacities(), [1.0, 1.0]);
+}
@@ -2,16 +2,24 @@ use fxhash::FxHashMap;
 
 use cubism_core::Model;
 
+use crate::accumulator::Accumulator;
 use crate::controller::Controller;
 use crate::expression::Expression;
 use crate::util::SimpleSlab;
 
 /// An ExpressionController is responsible for properly registering and
 /// switching between expressions of a model.
+///
+/// A newly set expression fades in over its fade in time while the replaced
+/// one fades out over its own fade out time.
 pub struct ExpressionController {
     expressions: SimpleSlab<Expression>,
     name_map: FxHashMap<String, usize>,
-    current_expr: Option<usize>,
+    // the current expression and the seconds since it has been set
+    current_expr: Option<(usize, f32)>,
+    // the replaced expression, the seconds since it has been set and the
+    // seconds since it has been replaced
+    fading_expr: Option<(usize, f32, f32)>,
     weight: f32,
 }
 
@@ -22,6 +30,7 @@ impl ExpressionController {
             expressions: SimpleSlab::new(),
             name_map: FxHashMap::default(),
             current_expr: None,
+            fading_expr: None,
             weight: 1.0,
         }
     }
@@ -39,13 +48,20 @@ impl ExpressionController {
     /// Set the current expression, if an expression by the given name doesnt
     /// exist it will be set to apply no expression.
     pub fn set_expression(&mut self, name: &str) {
-        self.current_expr = self.name_map.get(name).copied();
+        let expr = self.name_map.get(name).copied();
+        if expr == self.current_expr.map(|(expr, _)| expr) {
+            return;
+        }
+        if let Some((current, elapsed)) = self.current_expr.take() {
+            self.fading_expr = Some((current, elapsed, 0.0));
+        }
+        self.current_expr = expr.map(|expr| (expr, 0.0));
     }
 
     /// Sets the expression weight to apply.
     /// Note: Weight will be bound between [0.0,1.0].
     pub fn set_expression_weight(&mut self, weight: f32) {
-        self.weight = weight.min(1.0).max(0.0);
+        self.weight = weight.clamp(0.0, 1.0);
     }
 
     /// The names of all currently registered expressions.
@@ -60,12 +76,28 @@ impl ExpressionController {
 }
 
 impl Controller for ExpressionController {
-    fn update_parameters(&mut self, model: &mut Model, _: f32) {
-        self.current_expr.map(|expr| {
-            self.expressions
-                .get(expr)
-                .map(|expr| expr.apply(model, self.weight))
-        });
+    fn update_parameters(&mut self, _: &Model, accumulator: &mut Accumulator, delta: f32) {
+        if let Some((fading, elapsed, since)) = self.fading_expr.as_mut() {
+            *elapsed += delta;
+            *since += delta;
+            let fade_out = self
+                .expressions
+                .get(*fading)
+                .map_or(0.0, |expr| expr.fade_out_weight(*since));
+            if fade_out > 0.0 {
+                if let Some(expr) = self.expressions.get(*fading) {
+                    expr.contribute(accumulator, self.weight * fade_out, *elapsed);
+                }
+            } else {
+                self.fading_expr = None;
+            }
+        }
+        if let Some((current, elapsed)) = self.current_expr.as_mut() {
+            *elapsed += delta;
+            if let Some(expr) = self.expressions.get(*current) {
+                expr.contribute(accumulator, self.weight, *elapsed);
+            }
+        }
     }
 
     fn priority(&self) -> usize {
@@ -78,3 +110,51 @@ impl Default for ExpressionController {
         Self::new()
     }
 }
+
+#[test]
+fn expression_controller_fades() {
+    use crate::json::expression::Expression3;
+    use cubism_core::{ModelDescription, ParameterDescription};
+
+    let mut model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new("ParamA", 0.0, 10.0, 0.0))
+            .with_parameter(ParameterDescription::new("ParamB", 0.0, 10.0, 0.0)),
+    );
+    let expression = |id: &str| {
+        let exp3: Expression3 = format!(
+            r#"{{
+                "Type": "Live2D Expression",
+                "FadeInTime": 1.0,
+                "FadeOutTime": 1.0,
+                "Parameters": [{{ "Id": "{}", "Value": 10.0, "Blend": "Add" }}]
+            }}"#,
+            id
+        )
+        .parse()
+        .unwrap();
+        Expression::from_exp3(&model, exp3)
+    };
+    let mut controller = ExpressionController::new();
+    controller.register("a", expression("ParamA"));
+    controller.register("b", expression("ParamB"));
+
+    let mut accumulator = Accumulator::new();
+    let mut update = |controller: &mut ExpressionController, model: &mut Model, delta: f32| {
+        model.reset_to_defaults();
+        accumulator.begin(model);
+        controller.update_parameters(model, &mut accumulator, delta);
+        accumulator.resolve(model);
+        model.parameter_values().to_vec()
+    };
+    controller.set_expression("a");
+    // halfway through the fade in
+    assert_eq!(update(&mut controller, &mut model, 0.5), [5.0, 0.0]);
+    assert_eq!(update(&mut controller, &mut model, 0.5), [10.0, 0.0]);
+
+    controller.set_expression("b");
+    // a is halfway through its fade out while b is halfway through its fade in
+    assert_eq!(update(&mut controller, &mut model, 0.5), [5.0, 5.0]);
+    assert_eq!(update(&mut controller, &mut model, 0.5), [0.0, 10.0]);
+    assert!(controller.fading_expr.is_none());
+}
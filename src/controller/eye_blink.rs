@@ -1,6 +1,8 @@
-use cubism_core::Model;
+use cubism_core::{Model, ParameterIndex};
 
+use crate::accumulator::{Accumulator, BlendMode};
 use crate::controller::Controller;
+use crate::id::StandardIds;
 
 #[derive(Copy, Clone, Debug)]
 enum EyeState {
@@ -14,7 +16,7 @@ enum EyeState {
 // FIXME: sanitize timing inputs
 #[derive(Clone, Debug)]
 pub struct EyeBlink {
-    parameter_ids: Box<[usize]>,
+    parameter_ids: Box<[ParameterIndex]>,
     current_state: EyeState,
     next_cycle: f32,
     blink_interval: f32,
@@ -41,11 +43,10 @@ impl EyeBlink {
     /// Creates a new EyeBlink Controller acting on the specified parameter ids
     /// with the given timings.
     ///
-    /// The controller assumes that the ids belong to the model that is being
-    /// passed on to [`EyeBlink::update_parameters`], meaning that if this
-    /// is not the case the application may panic on out of bounds access or
-    /// move incorrect parts.
-    pub fn new<B: Into<Box<[usize]>>>(
+    /// The ids have to belong to the moc of the model that is being passed on
+    /// to [`EyeBlink::update_parameters`], updating a model of a different
+    /// moc panics.
+    pub fn new<B: Into<Box<[ParameterIndex]>>>(
         parameter_ids: B,
         blink_interval: f32,
         closed_time: f32,
@@ -63,8 +64,27 @@ impl EyeBlink {
         }
     }
 
+    /// Creates a new EyeBlink Controller with the default timings, acting on
+    /// the eye blink parameters of the standard ids. Returns `None` if there
+    /// are none.
+    pub fn from_standard_ids(ids: &StandardIds) -> Option<Self> {
+        let mut eye_blink = EyeBlink::default();
+        eye_blink.set_standard_ids(ids);
+        if eye_blink.parameter_ids.is_empty() {
+            None
+        } else {
+            Some(eye_blink)
+        }
+    }
+
+    /// Set the parameters that are affected by this controller to the eye
+    /// blink parameters of the standard ids.
+    pub fn set_standard_ids(&mut self, ids: &StandardIds) {
+        self.set_ids(ids.eye_blink_parameters());
+    }
+
     /// Set the parameters that are affected by this controller.
-    pub fn set_ids<B: Into<Box<[usize]>>>(&mut self, parameter_ids: B) {
+    pub fn set_ids<B: Into<Box<[ParameterIndex]>>>(&mut self, parameter_ids: B) {
         self.parameter_ids = parameter_ids.into();
     }
 
@@ -85,7 +105,7 @@ impl EyeBlink {
 }
 
 impl Controller for EyeBlink {
-    fn update_parameters(&mut self, model: &mut Model, delta: f32) {
+    fn update_parameters(&mut self, _: &Model, accumulator: &mut Accumulator, delta: f32) {
         self.next_cycle -= delta;
         let val = match self.current_state {
             EyeState::Open => {
@@ -122,11 +142,53 @@ impl Controller for EyeBlink {
             },
         };
         for par in self.parameter_ids.iter().copied() {
-            model.parameter_values_mut()[par] = val;
+            accumulator.submit_parameter(par, BlendMode::Overwrite, val, 1.0);
         }
     }
 
     fn priority(&self) -> usize {
         crate::controller::default_priorities::EYE_BLINK
     }
+
+    fn reset(&mut self, _: &mut Model) {
+        self.current_state = EyeState::Open;
+        self.next_cycle = self.blink_interval;
+    }
+}
+
+#[test]
+fn eye_blink_fake_model() {
+    use crate::id::{param, StandardIds};
+    use cubism_core::{ModelDescription, ParameterDescription};
+
+    let mut model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new(param::EYE_L_OPEN, 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new(param::EYE_R_OPEN, 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new("ParamOther", 0.0, 1.0, 1.0)),
+    );
+    let mut eye_blink = EyeBlink::from_standard_ids(&StandardIds::new(model.moc())).unwrap();
+    eye_blink.set_timings(1.0, 0.25, 0.5, 0.25);
+    let mut accumulator = Accumulator::new();
+    let mut values_after = |eye_blink: &mut EyeBlink, delta: f32| {
+        model.reset_to_defaults();
+        accumulator.begin(&model);
+        eye_blink.update_parameters(&model, &mut accumulator, delta);
+        accumulator.resolve(&mut model);
+        model.parameter_values().to_vec()
+    };
+
+    // open, closing, closed, opening and open again
+    let expected = [
+        (0.5, 1.0),
+        (0.5, 1.0),
+        (0.125, 0.5),
+        (0.125, 0.0),
+        (0.25, 0.0),
+        (0.25, 0.5),
+        (0.25, 1.0),
+    ];
+    for &(delta, value) in expected.iter() {
+        assert_eq!(values_after(&mut eye_blink, delta), [value, value, 1.0]);
+    }
 }
@@ -0,0 +1,247 @@
+use std::path::Path;
+
+use cubism_core::{Model, ParameterIndex, PartIndex};
+
+use crate::accumulator::{Accumulator, BlendMode};
+use crate::asset::{self, AssetSource, FileSystem};
+use crate::controller::Controller;
+use crate::error::CubismResult;
+use crate::json::pose::Pose3;
+
+const EPSILON: f32 = 0.001;
+// The opacity at which the fading parts start to become transparent.
+const PHI: f32 = 0.5;
+// The maximum opacity the background may show through while fading.
+const BACK_OPACITY_THRESHOLD: f32 = 0.15;
+
+#[derive(Clone, Debug)]
+struct PosePart {
+    part: PartIndex,
+    // The parameter with the same id as the part, toggling its visibility.
+    parameter: Option<ParameterIndex>,
+    // The visibility value used if the model has no matching parameter.
+    fallback_value: f32,
+    links: Box<[PartIndex]>,
+}
+
+impl PosePart {
+    fn visibility(&self, model: &Model) -> f32 {
+        self.parameter
+            .map(|idx| model.parameter_by_index(idx).value)
+            .unwrap_or(self.fallback_value)
+    }
+
+    fn set_visibility(&mut self, model: &mut Model, value: f32) {
+        match self.parameter {
+            Some(idx) => *model.parameter_by_index_mut(idx).value = value,
+            None => self.fallback_value = value,
+        }
+    }
+}
+
+/// A Pose controller. This Controller switches between exclusively visible
+/// parts of part groups, fading the opacity when the visible part changes.
+///
+/// Which part of a group is visible is determined by the parameter sharing
+/// the part's id, the first part whose parameter is non-zero will be shown.
+/// Linked parts copy the opacity of the part they are linked to, links to
+/// parts that are entries of a group themselves are ignored.
+#[derive(Clone, Debug)]
+pub struct Pose {
+    groups: Box<[Box<[PosePart]>]>,
+    fade_in_time: f32,
+}
+
+impl Pose {
+    /// Creates a Pose from a path of a .pose3.json file and the corresponding
+    /// model.
+    ///
+    /// Note that this doesn't reset the model's part opacities, for that call
+    /// [`Pose::reset`].
+    #[inline]
+    pub fn from_pose3_json<P: AsRef<Path>>(model: &Model, path: P) -> CubismResult<Self> {
+        Self::from_source(model, &FileSystem, path)
+    }
+
+    /// Creates a Pose from a path of a .pose3.json file in the asset source
+    /// and the corresponding model.
+    ///
+    /// Note that this doesn't reset the model's part opacities, for that call
+    /// [`Pose::reset`].
+    pub fn from_source<P: AsRef<Path>>(
+        model: &Model,
+        source: &dyn AssetSource,
+        path: P,
+    ) -> CubismResult<Self> {
+        let pose3: Pose3 = asset::read_json(source, path.as_ref())?;
+        Ok(Self::from_pose3(model, &pose3))
+    }
+
+    /// Creates a Pose from a Pose3 and the corresponding model. Parts that
+    /// don't exist in the model are ignored.
+    ///
+    /// Note that this doesn't reset the model's part opacities, for that call
+    /// [`Pose::reset`].
+    pub fn from_pose3(model: &Model, pose3: &Pose3) -> Self {
+        // parts that are group entries get their opacity from their own group,
+        // linking them as well would submit two overwrites that get averaged
+        let group_parts: Vec<PartIndex> = pose3
+            .groups
+            .iter()
+            .flatten()
+            .filter_map(|item| model.part_index(&item.id))
+            .collect();
+        let groups = pose3
+            .groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .filter_map(|item| {
+                        Some(PosePart {
+                            part: model.part_index(&item.id)?,
+                            parameter: model.parameter_index(&item.id),
+                            fallback_value: 0.0,
+                            links: item
+                                .link
+                                .iter()
+                                .filter_map(|id| model.part_index(id))
+                                .filter(|idx| !group_parts.contains(idx))
+                                .collect(),
+                        })
+                    })
+                    .collect::<Box<[_]>>()
+            })
+            .filter(|group| !group.is_empty())
+            .collect();
+        Pose {
+            groups,
+            fade_in_time: if pose3.fade_in_time < 0.0 {
+                0.5
+            } else {
+                pose3.fade_in_time
+            },
+        }
+    }
+
+    /// Sets the time in seconds it takes for a newly visible part to fully
+    /// fade in.
+    pub fn set_fade_in_time(&mut self, fade_in_time: f32) {
+        self.fade_in_time = fade_in_time.max(0.0);
+    }
+
+    /// The time in seconds it takes for a newly visible part to fully fade in.
+    pub fn fade_in_time(&self) -> f32 {
+        self.fade_in_time
+    }
+
+    /// Resets the model to the initial pose, making the first part of every
+    /// group fully visible and hiding all the others.
+    pub fn reset(&mut self, model: &mut Model) {
+        for group in self.groups.iter_mut() {
+            for (i, part) in group.iter_mut().enumerate() {
+                let value = if i == 0 { 1.0 } else { 0.0 };
+                part.set_visibility(model, value);
+                *model.part_by_index_mut(part.part).opacity = value;
+                for &link in part.links.iter() {
+                    *model.part_by_index_mut(link).opacity = value;
+                }
+            }
+        }
+    }
+
+    fn fade_group(
+        group: &[PosePart],
+        model: &Model,
+        accumulator: &mut Accumulator,
+        delta: f32,
+        fade_in_time: f32,
+    ) {
+        let mut visible = None;
+        for (i, part) in group.iter().enumerate() {
+            if part.visibility(model) > EPSILON {
+                if visible.is_some() {
+                    break;
+                }
+                let opacity = if fade_in_time == 0.0 {
+                    1.0
+                } else {
+                    accumulator.part_opacity(part.part) + delta / fade_in_time
+                };
+                visible = Some((i, opacity.min(1.0)));
+            }
+        }
+        let (visible, new_opacity) = visible.unwrap_or((0, 1.0));
+
+        for (i, part) in group.iter().enumerate() {
+            let opacity = if i == visible {
+                new_opacity
+            } else {
+                let mut a1 = if new_opacity < PHI {
+                    new_opacity * (PHI - 1.0) / PHI + 1.0
+                } else {
+                    (1.0 - new_opacity) * PHI / (1.0 - PHI)
+                };
+                let back_opacity = (1.0 - a1) * (1.0 - new_opacity);
+                if back_opacity > BACK_OPACITY_THRESHOLD {
+                    a1 = 1.0 - BACK_OPACITY_THRESHOLD / (1.0 - new_opacity);
+                }
+                accumulator.part_opacity(part.part).min(a1)
+            };
+            // linked parts copy the opacity of the part they are linked to
+            for &idx in std::iter::once(&part.part).chain(part.links.iter()) {
+                accumulator.submit_part_opacity(idx, BlendMode::Overwrite, opacity, 1.0);
+            }
+        }
+    }
+}
+
+impl Controller for Pose {
+    fn update_parameters(&mut self, model: &Model, accumulator: &mut Accumulator, delta: f32) {
+        let delta = delta.max(0.0);
+        for group in self.groups.iter() {
+            Self::fade_group(group, model, accumulator, delta, self.fade_in_time);
+        }
+    }
+
+    fn priority(&self) -> usize {
+        crate::controller::default_priorities::POSE
+    }
+
+    fn reset(&mut self, model: &mut Model) {
+        Pose::reset(self, model);
+    }
+}
+
+#[test]
+fn pose_link_to_group_part() {
+    use cubism_core::{ModelDescription, ParameterDescription, PartDescription};
+
+    let mut description = ModelDescription::new();
+    for id in ["PartA", "PartB", "PartC"].iter() {
+        description = description
+            .with_parameter(ParameterDescription::new(*id, 0.0, 1.0, 0.0))
+            .with_part(PartDescription::new(*id));
+    }
+    let mut model = Model::from_description(description);
+    let pose3: Pose3 = r#"{
+        "Type": "Live2D Pose",
+        "FadeInTime": 0.0,
+        "Groups": [
+            [{ "Id": "PartA", "Link": ["PartB"] }],
+            [{ "Id": "PartC", "Link": [] }, { "Id": "PartB", "Link": [] }]
+        ]
+    }"#
+    .parse()
+    .unwrap();
+    let mut pose = Pose::from_pose3(&model, &pose3);
+    pose.reset(&mut model);
+    assert_eq!(model.part_opacities(), [1.0, 0.0, 1.0]);
+
+    let mut accumulator = Accumulator::new();
+    accumulator.begin(&model);
+    pose.update_parameters(&model, &mut accumulator, 0.1);
+    accumulator.resolve(&mut model);
+    // PartB is hidden by its own group instead of being averaged with PartA
+    assert_eq!(model.part_opacities(), [1.0, 0.0, 1.0]);
+}
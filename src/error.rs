@@ -1,11 +1,84 @@
 //! Errors returned by cubism.
-use std::{error, fmt, io};
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+};
 
 use cubism_core::MocError;
 
+use crate::json::FormatError;
+
 /// The result type, returned by this library.
 pub type CubismResult<T> = std::result::Result<T, CubismError>;
 
+/// The file reference of a model3.json that was being loaded when an error
+/// occurred.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileReference {
+    /// The model3.json itself.
+    Model3,
+    /// The moc3 file.
+    Moc,
+    /// The texture at the index.
+    Texture(usize),
+    /// The expression with the name.
+    Expression(String),
+    /// The motion at the index of the motion group.
+    Motion {
+        /// The name of the motion group.
+        group: String,
+        /// The index of the motion in the group.
+        index: usize,
+    },
+    /// The physics3.json file.
+    Physics,
+    /// The pose3.json file.
+    Pose,
+    /// The userdata3.json file.
+    UserData,
+    /// The cdi3.json file.
+    DisplayInfo,
+}
+
+impl fmt::Display for FileReference {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileReference::Model3 => write!(fmt, "model3"),
+            FileReference::Moc => write!(fmt, "moc"),
+            FileReference::Texture(idx) => write!(fmt, "texture {}", idx),
+            FileReference::Expression(name) => write!(fmt, "expression {:?}", name),
+            FileReference::Motion { group, index } => {
+                write!(fmt, "motion {} of group {:?}", index, group)
+            },
+            FileReference::Physics => write!(fmt, "physics"),
+            FileReference::Pose => write!(fmt, "pose"),
+            FileReference::UserData => write!(fmt, "user data"),
+            FileReference::DisplayInfo => write!(fmt, "display info"),
+        }
+    }
+}
+
+/// The kind of object an id refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IdKind {
+    /// A parameter id.
+    Parameter,
+    /// A part id.
+    Part,
+    /// A drawable id.
+    Drawable,
+}
+
+impl fmt::Display for IdKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdKind::Parameter => write!(fmt, "parameter"),
+            IdKind::Part => write!(fmt, "part"),
+            IdKind::Drawable => write!(fmt, "drawable"),
+        }
+    }
+}
+
 /// An error returned by this library.
 #[derive(Debug)]
 pub enum CubismError {
@@ -16,15 +89,201 @@ pub enum CubismError {
     Json(serde_json::Error),
     /// An io error occurred.
     Io(io::Error),
+    /// A file could not be read.
+    ReadFile {
+        /// The path of the file.
+        path: PathBuf,
+        /// The file reference that was being loaded, if any.
+        reference: Option<FileReference>,
+        /// The underlying io error.
+        source: io::Error,
+    },
+    /// A json file could not be parsed.
+    ParseJson {
+        /// The path of the file.
+        path: PathBuf,
+        /// The file reference that was being loaded, if any.
+        reference: Option<FileReference>,
+        /// The line the error occurred at, starting at 1.
+        line: usize,
+        /// The column the error occurred at, starting at 1.
+        column: usize,
+        /// The underlying json error.
+        source: serde_json::Error,
+    },
+    /// A json file has a version or type that is not supported.
+    UnsupportedFormat {
+        /// The path of the file.
+        path: PathBuf,
+        /// The file reference that was being loaded, if any.
+        reference: Option<FileReference>,
+        /// The underlying format error.
+        source: FormatError,
+    },
+    /// A moc file could not be loaded.
+    LoadMoc {
+        /// The path of the moc file.
+        path: PathBuf,
+        /// The underlying moc error.
+        source: MocError,
+    },
+    /// The model3.json doesn't reference a moc file.
+    MissingMoc,
+    /// The zip archive a model was loaded from doesn't contain a model3.json.
+    MissingModel3,
+    /// A file refers to an id that doesn't exist in the moc.
+    UnresolvedId {
+        /// The path of the file containing the id.
+        path: PathBuf,
+        /// The file reference that was being loaded, if any.
+        reference: Option<FileReference>,
+        /// What kind of object the id refers to.
+        kind: IdKind,
+        /// The id.
+        id: String,
+    },
+}
+
+impl CubismError {
+    /// Attaches the file reference that was being loaded to this error, if it
+    /// refers to a file and doesn't have one yet.
+    pub fn with_reference(mut self, new: FileReference) -> Self {
+        match &mut self {
+            CubismError::ReadFile { reference, .. }
+            | CubismError::ParseJson { reference, .. }
+            | CubismError::UnsupportedFormat { reference, .. }
+            | CubismError::UnresolvedId { reference, .. }
+                if reference.is_none() =>
+            {
+                *reference = Some(new)
+            },
+            _ => (),
+        }
+        self
+    }
+
+    pub(crate) fn read_file(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        CubismError::ReadFile {
+            path: path.into(),
+            reference: None,
+            source,
+        }
+    }
+
+    pub(crate) fn format(path: impl Into<PathBuf>, source: FormatError) -> Self {
+        match source {
+            FormatError::Json(e) => Self::parse_json(path, e),
+            source => CubismError::UnsupportedFormat {
+                path: path.into(),
+                reference: None,
+                source,
+            },
+        }
+    }
+
+    pub(crate) fn parse_json(path: impl Into<PathBuf>, source: serde_json::Error) -> Self {
+        CubismError::ParseJson {
+            path: path.into(),
+            reference: None,
+            line: source.line(),
+            column: source.column(),
+            source,
+        }
+    }
+}
+
+// The location of an error, the path of the file followed by the file
+// reference. Files without a path, like a model3.json that has been parsed
+// elsewhere, are only described by their reference.
+struct Location<'a>(&'a Path, &'a Option<FileReference>);
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.0.as_os_str().is_empty(), self.1) {
+            (true, None) => write!(fmt, "a file"),
+            (true, Some(reference)) => write!(fmt, "{}", reference),
+            (false, Some(reference)) => write!(fmt, "{} ({})", self.0.display(), reference),
+            (_, None) => write!(fmt, "{}", self.0.display()),
+        }
+    }
+}
+
+impl error::Error for CubismError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CubismError::Moc(e) => Some(e),
+            CubismError::Json(e) => Some(e),
+            CubismError::Io(e) => Some(e),
+            CubismError::ReadFile { source, .. } => Some(source),
+            CubismError::ParseJson { source, .. } => Some(source),
+            CubismError::UnsupportedFormat { source, .. } => Some(source),
+            CubismError::LoadMoc { source, .. } => Some(source),
+            CubismError::MissingMoc
+            | CubismError::MissingModel3
+            | CubismError::UnresolvedId { .. } => None,
+        }
+    }
 }
 
-impl error::Error for CubismError {}
 impl fmt::Display for CubismError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CubismError::Moc(e) => (e as &dyn fmt::Display).fmt(fmt),
             CubismError::Json(e) => (e as &dyn fmt::Display).fmt(fmt),
             CubismError::Io(e) => (e as &dyn fmt::Display).fmt(fmt),
+            CubismError::ReadFile {
+                path,
+                reference,
+                source,
+            } => write!(
+                fmt,
+                "failed to read {}: {}",
+                Location(path, reference),
+                source
+            ),
+            CubismError::ParseJson {
+                path,
+                reference,
+                line,
+                column,
+                source,
+            } => write!(
+                fmt,
+                "failed to parse {} at line {} column {}: {}",
+                Location(path, reference),
+                line,
+                column,
+                source
+            ),
+            CubismError::UnsupportedFormat {
+                path,
+                reference,
+                source,
+            } => write!(
+                fmt,
+                "failed to load {}: {}",
+                Location(path, reference),
+                source
+            ),
+            CubismError::LoadMoc { path, source } => {
+                write!(fmt, "failed to load moc {}: {}", path.display(), source)
+            },
+            CubismError::MissingMoc => write!(fmt, "no moc file has been specified"),
+            CubismError::MissingModel3 => {
+                write!(fmt, "the archive does not contain a model3.json file")
+            },
+            CubismError::UnresolvedId {
+                path,
+                reference,
+                kind,
+                id,
+            } => write!(
+                fmt,
+                "{} refers to {} id {:?} which does not exist in the moc",
+                Location(path, reference),
+                kind,
+                id
+            ),
         }
     }
 }
@@ -46,3 +305,27 @@ impl From<io::Error> for CubismError {
         CubismError::Io(e)
     }
 }
+
+#[test]
+fn error_with_reference() {
+    let json_err = serde_json::from_str::<serde_json::Value>("{\n  \"Type\": }").unwrap_err();
+    let err = CubismError::parse_json("f01.exp3.json", json_err)
+        .with_reference(FileReference::Expression("f01".to_owned()))
+        .with_reference(FileReference::Pose);
+    match &err {
+        CubismError::ParseJson {
+            reference, line, ..
+        } => {
+            assert_eq!(
+                reference,
+                &Some(FileReference::Expression("f01".to_owned()))
+            );
+            assert_eq!(*line, 2);
+        },
+        _ => panic!("unexpected error {:?}", err),
+    }
+    assert!(err
+        .to_string()
+        .starts_with("failed to parse f01.exp3.json (expression \"f01\") at line 2"));
+    assert!(error::Error::source(&err).is_some());
+}
@@ -1,31 +1,52 @@
 //! A model expression.
 
-use std::{fs, path::Path};
+use std::path::Path;
 
+use crate::accumulator::{Accumulator, BlendMode};
+use crate::asset::{self, AssetSource, FileSystem};
 use crate::error::CubismResult;
 use crate::json::expression::{Expression3, ExpressionBlendType, ExpressionParameter};
+use crate::motion::easing_sine;
 
-use cubism_core::Model;
+use cubism_core::{Model, ParameterIndex};
 
 /// A model expression.
 #[derive(Clone, Debug)]
 pub struct Expression {
     fade_in: f32,
     fade_out: f32,
-    parameters: Vec<(usize, ExpressionBlendType, f32)>,
+    parameters: Vec<(ParameterIndex, ExpressionBlendType, f32)>,
 }
 
 impl Expression {
     /// Creates a Expression from a path of .exp3.json file and the
     /// corresponding model.
+    #[inline]
     pub fn from_exp3_json<P: AsRef<Path>>(model: &Model, path: P) -> CubismResult<Expression> {
+        Self::from_source(model, &FileSystem, path)
+    }
+
+    /// Creates a Expression from a path of .exp3.json file in the asset source
+    /// and the corresponding model.
+    pub fn from_source<P: AsRef<Path>>(
+        model: &Model,
+        source: &dyn AssetSource,
+        path: P,
+    ) -> CubismResult<Expression> {
+        let exp3: Expression3 = asset::read_json(source, path.as_ref())?;
+        Ok(Self::from_exp3(model, exp3))
+    }
+
+    /// Creates a Expression from a Expression3 and the corresponding model.
+    /// Parameters that don't exist in the model are ignored.
+    pub fn from_exp3(model: &Model, exp3: Expression3) -> Expression {
         let Expression3 {
             fade_in_time: fade_in,
             fade_out_time: fade_out,
             parameters,
             ..
-        } = Expression3::from_reader(fs::File::open(path)?)?;
-        Ok(Expression {
+        } = exp3;
+        Expression {
             fade_in,
             fade_out,
             parameters: parameters
@@ -37,21 +58,51 @@ impl Expression {
                          value,
                      }| {
                         model
-                            .parameter_ids()
-                            .iter()
-                            .position(|id2| *id2 == id)
+                            .parameter_index(&id)
                             .map(|idx| (idx, blend_type, value))
                     },
                 )
                 .collect::<Vec<_>>(),
-        })
+        }
+    }
+
+    /// The time in seconds it takes for the expression to fade in after it
+    /// has been set.
+    pub fn fade_in_time(&self) -> f32 {
+        self.fade_in
+    }
+
+    /// The time in seconds it takes for the expression to fade out after it
+    /// has been replaced.
+    pub fn fade_out_time(&self) -> f32 {
+        self.fade_out
+    }
+
+    /// The weight the expression is applied with `elapsed` seconds after it
+    /// has been set.
+    pub fn fade_in_weight(&self, elapsed: f32) -> f32 {
+        if self.fade_in > 0.0 {
+            easing_sine(elapsed / self.fade_in)
+        } else {
+            1.0
+        }
+    }
+
+    /// The weight the expression is applied with `elapsed` seconds after it
+    /// has been replaced.
+    pub fn fade_out_weight(&self, elapsed: f32) -> f32 {
+        if self.fade_out > 0.0 {
+            1.0 - easing_sine(elapsed / self.fade_out)
+        } else {
+            0.0
+        }
     }
 
     /// Apply an expression to a model.
     pub fn apply(&self, model: &mut Model, mut weight: f32) {
-        weight = weight.min(1.0).max(0.0);
+        weight = weight.clamp(0.0, 1.0);
         for (id, blend_type, value) in self.parameters.iter().copied() {
-            let model_value = &mut model.parameter_values_mut()[id];
+            let model_value = model.parameter_by_index_mut(id).value;
             *model_value = match blend_type {
                 ExpressionBlendType::Add => value.mul_add(weight, *model_value),
                 ExpressionBlendType::Multiply => *model_value * (value - 1.0).mul_add(weight, 1.0),
@@ -59,4 +110,53 @@ impl Expression {
             };
         }
     }
+
+    /// Submits the expression to an accumulator started with the
+    /// corresponding model, `elapsed` seconds after it has been set. The
+    /// weight is faded in by the [`fade_in_weight`](#method.fade_in_weight).
+    pub fn contribute(&self, accumulator: &mut Accumulator, weight: f32, elapsed: f32) {
+        let weight = weight.clamp(0.0, 1.0) * self.fade_in_weight(elapsed);
+        for (id, blend_type, value) in self.parameters.iter().copied() {
+            accumulator.submit_parameter(id, BlendMode::from(blend_type), value, weight);
+        }
+    }
+}
+
+#[test]
+fn expression_contribute_fake_model() {
+    use cubism_core::{ModelDescription, ParameterDescription};
+
+    let mut model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new("ParamAdd", -30.0, 30.0, 0.0))
+            .with_parameter(ParameterDescription::new("ParamMultiply", 0.0, 2.0, 1.0))
+            .with_parameter(ParameterDescription::new("ParamOverwrite", 0.0, 1.0, 0.0)),
+    );
+    let exp3: Expression3 = r#"{
+        "Type": "Live2D Expression",
+        "Parameters": [
+            { "Id": "ParamAdd", "Value": 10.0, "Blend": "Add" },
+            { "Id": "ParamMultiply", "Value": 1.5, "Blend": "Multiply" },
+            { "Id": "ParamOverwrite", "Value": 2.0, "Blend": "Overwrite" },
+            { "Id": "ParamMissing", "Value": 1.0, "Blend": "Add" }
+        ]
+    }"#
+    .parse()
+    .unwrap();
+    let expression = Expression::from_exp3(&model, exp3);
+    assert_eq!(expression.parameters.len(), 3);
+
+    let mut accumulator = Accumulator::new();
+    accumulator.begin(&model);
+    expression.contribute(&mut accumulator, 0.5, expression.fade_in_time());
+    accumulator.resolve(&mut model);
+    // overwritten values are clamped to the parameter's range
+    assert_eq!(model.parameter_values(), [5.0, 1.25, 1.0]);
+
+    model.reset_to_defaults();
+    accumulator.begin(&model);
+    // halfway through the fade in the sine easing is at 0.5
+    expression.contribute(&mut accumulator, 1.0, 0.5 * expression.fade_in_time());
+    accumulator.resolve(&mut model);
+    assert_eq!(model.parameter_values()[0], 5.0);
 }
@@ -1,6 +1,12 @@
 #![allow(missing_docs)]
 // https://docs.live2d.com/cubism-editor-manual/standard-parametor-list/#
 
+use fxhash::FxHashMap;
+
+use cubism_core::{Moc, ParameterIndex, PartIndex};
+
+use crate::json::model::{GroupTarget, Model3};
+
 /// Common Group IDs
 pub mod groups {
     pub static EYE_BLINK: &str = "EyeBlink";
@@ -76,3 +82,279 @@ pub mod param {
 
     pub static NONE: &str = "NONE:";
 }
+
+/// Alternative ids for the standard ids, for models that don't follow the
+/// standard naming.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdAliases {
+    aliases: FxHashMap<String, Vec<String>>,
+}
+
+impl IdAliases {
+    /// Creates a new empty IdAliases.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an alias for the standard id. Aliases are tried in the order they
+    /// have been added, before the standard id itself.
+    pub fn insert(&mut self, standard: impl Into<String>, alias: impl Into<String>) {
+        self.aliases
+            .entry(standard.into())
+            .or_default()
+            .push(alias.into());
+    }
+
+    /// The aliases of the standard id.
+    pub fn get(&self, standard: &str) -> &[String] {
+        self.aliases.get(standard).map_or(&[], |aliases| &**aliases)
+    }
+
+    fn candidates<'a>(&'a self, standard: &'a str) -> impl Iterator<Item = &'a str> {
+        self.get(standard)
+            .iter()
+            .map(|alias| &**alias)
+            .chain(std::iter::once(standard))
+    }
+
+    fn parameter(&self, moc: &Moc, standard: &str) -> Option<ParameterIndex> {
+        self.candidates(standard)
+            .find_map(|id| moc.parameter_index(id))
+    }
+
+    fn part(&self, moc: &Moc, standard: &str) -> Option<PartIndex> {
+        self.candidates(standard).find_map(|id| moc.part_index(id))
+    }
+}
+
+macro_rules! standard_parameters {
+    ($($field:ident => $id:ident,)*) => {
+        /// The standard parameters of a moc, `None` if the moc doesn't have
+        /// the parameter.
+        #[derive(Clone, Debug, Default, PartialEq, Eq)]
+        pub struct StandardParameters {
+            $(
+                #[doc = concat!("The index of [`param::", stringify!($id), "`].")]
+                pub $field: Option<ParameterIndex>,
+            )*
+        }
+
+        impl StandardParameters {
+            fn resolve(moc: &Moc, aliases: &IdAliases) -> Self {
+                StandardParameters {
+                    $($field: aliases.parameter(moc, param::$id),)*
+                }
+            }
+        }
+    };
+}
+
+standard_parameters! {
+    angle_x => ANGLE_X,
+    angle_y => ANGLE_Y,
+    angle_z => ANGLE_Z,
+    eye_l_open => EYE_L_OPEN,
+    eye_l_smile => EYE_L_SMILE,
+    eye_r_open => EYE_R_OPEN,
+    eye_r_smile => EYE_R_SMILE,
+    eye_ball_x => EYE_BALL_X,
+    eye_ball_y => EYE_BALL_Y,
+    eye_ball_form => EYE_BALL_FORM,
+    brow_ly => BROW_LY,
+    brow_ry => BROW_RY,
+    brow_lx => BROW_LX,
+    brow_rx => BROW_RX,
+    brow_l_angle => BROW_L_ANGLE,
+    brow_r_angle => BROW_R_ANGLE,
+    brow_l_form => BROW_L_FORM,
+    brow_r_form => BROW_R_FORM,
+    mouth_form => MOUTH_FORM,
+    mouth_open_y => MOUTH_OPEN_Y,
+    cheek => CHEEK,
+    body_angle_x => BODY_ANGLE_X,
+    body_angle_y => BODY_ANGLE_Y,
+    body_angle_z => BODY_ANGLE_Z,
+    breath => BREATH,
+    arm_la => ARM_LA,
+    arm_ra => ARM_RA,
+    arm_lb => ARM_LB,
+    arm_rb => ARM_RB,
+    hand_l => HAND_L,
+    hand_r => HAND_R,
+    hair_front => HAIR_FRONT,
+    hair_side => HAIR_SIDE,
+    hair_back => HAIR_BACK,
+    hair_fluffy => HAIR_FLUFFY,
+    shoulder_y => SHOULDER_Y,
+    bust_x => BUST_X,
+    bust_y => BUST_Y,
+    base_x => BASE_X,
+    base_y => BASE_Y,
+}
+
+/// The standard parameters, parts and groups of a moc, resolved in one pass.
+///
+/// The built-in controllers use this table to find their targets instead of
+/// looking up the ids themselves.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StandardIds {
+    /// The standard parameters.
+    pub parameters: StandardParameters,
+    /// The [`parts::CORE`] part.
+    pub core: Option<PartIndex>,
+    /// The parts starting with [`parts::ARM_PREFIX`].
+    pub arms: Vec<PartIndex>,
+    /// The parts starting with [`parts::ARM_L_PREFIX`].
+    pub arms_l: Vec<PartIndex>,
+    /// The parts starting with [`parts::ARM_R_PREFIX`].
+    pub arms_r: Vec<PartIndex>,
+    /// The parameters of the [`groups::EYE_BLINK`] group, `None` if there is
+    /// no such group.
+    pub eye_blink: Option<Vec<ParameterIndex>>,
+    /// The parameters of the [`groups::LIP_SYNC`] group, `None` if there is
+    /// no such group.
+    pub lip_sync: Option<Vec<ParameterIndex>>,
+}
+
+impl StandardIds {
+    /// Resolves the standard ids against the moc. The groups stay `None`,
+    /// they are only defined by a model3.json.
+    pub fn new(moc: &Moc) -> Self {
+        Self::with_aliases(moc, &IdAliases::default())
+    }
+
+    /// Resolves the standard ids against the moc, trying the aliases first.
+    pub fn with_aliases(moc: &Moc, aliases: &IdAliases) -> Self {
+        let with_prefix = |prefix: &str| -> Vec<PartIndex> {
+            moc.part_ids()
+                .iter()
+                .enumerate()
+                .filter(|(_, id)| id.starts_with(prefix))
+                .filter_map(|(idx, _)| moc.part_index_at(idx))
+                .collect()
+        };
+        StandardIds {
+            parameters: StandardParameters::resolve(moc, aliases),
+            core: aliases.part(moc, parts::CORE),
+            arms: with_prefix(parts::ARM_PREFIX),
+            arms_l: with_prefix(parts::ARM_L_PREFIX),
+            arms_r: with_prefix(parts::ARM_R_PREFIX),
+            eye_blink: None,
+            lip_sync: None,
+        }
+    }
+
+    /// Resolves the standard ids against the moc and the groups of the
+    /// model3.json, trying the aliases first. The ids of the groups are used
+    /// as they are.
+    pub fn from_model3(moc: &Moc, model3: &Model3, aliases: &IdAliases) -> Self {
+        let group = |name: &str| -> Option<Vec<ParameterIndex>> {
+            let mut groups = model3
+                .groups
+                .iter()
+                .filter(|g| g.target == GroupTarget::Parameter && g.name == name)
+                .peekable();
+            groups.peek()?;
+            Some(
+                groups
+                    .flat_map(|g| g.ids.iter())
+                    .filter_map(|id| moc.parameter_index(id))
+                    .collect(),
+            )
+        };
+        StandardIds {
+            eye_blink: group(groups::EYE_BLINK),
+            lip_sync: group(groups::LIP_SYNC),
+            ..Self::with_aliases(moc, aliases)
+        }
+    }
+
+    /// The parameters an eye blink should act on, the [`groups::EYE_BLINK`]
+    /// group or the standard eye open parameters if there is no such group.
+    /// An empty group disables the eye blink.
+    pub fn eye_blink_parameters(&self) -> Vec<ParameterIndex> {
+        if let Some(group) = &self.eye_blink {
+            return group.clone();
+        }
+        self.parameters
+            .eye_l_open
+            .into_iter()
+            .chain(self.parameters.eye_r_open)
+            .collect()
+    }
+}
+
+#[test]
+fn standard_ids_aliases() {
+    use cubism_core::{Model, ModelDescription, ParameterDescription};
+
+    let model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new(param::EYE_L_OPEN, 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new("EyeLeft", 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new("EyeRight", 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new("EyeRightOpen", 0.0, 1.0, 1.0)),
+    );
+    let moc = model.moc();
+    let ids = StandardIds::new(moc);
+    assert_eq!(
+        ids.parameters.eye_l_open,
+        moc.parameter_index(param::EYE_L_OPEN)
+    );
+    assert_eq!(ids.parameters.eye_r_open, None);
+
+    let mut aliases = IdAliases::new();
+    aliases.insert(param::EYE_L_OPEN, "EyeLeft");
+    aliases.insert(param::EYE_R_OPEN, "Missing");
+    aliases.insert(param::EYE_R_OPEN, "EyeRightOpen");
+    aliases.insert(param::EYE_R_OPEN, "EyeRight");
+    assert_eq!(aliases.get(param::EYE_R_OPEN).len(), 3);
+    let ids = StandardIds::with_aliases(moc, &aliases);
+    // aliases take precedence over the standard id and over later aliases
+    assert_eq!(ids.parameters.eye_l_open, moc.parameter_index("EyeLeft"));
+    assert_eq!(
+        ids.parameters.eye_r_open,
+        moc.parameter_index("EyeRightOpen")
+    );
+}
+
+#[test]
+fn standard_ids_eye_blink_fallback() {
+    use cubism_core::{Model, ModelDescription, ParameterDescription};
+
+    let model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new(param::EYE_L_OPEN, 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new(param::EYE_R_OPEN, 0.0, 1.0, 1.0))
+            .with_parameter(ParameterDescription::new("Blink", 0.0, 1.0, 1.0)),
+    );
+    let moc = model.moc();
+    let standard = vec![
+        moc.parameter_index(param::EYE_L_OPEN).unwrap(),
+        moc.parameter_index(param::EYE_R_OPEN).unwrap(),
+    ];
+    let from_groups = |groups: &str| {
+        let model3: Model3 = format!(
+            r#"{{"Version": 3, "FileReferences": {{"Moc": "a.moc3", "Textures": []}},
+                "Groups": {}}}"#,
+            groups
+        )
+        .parse()
+        .unwrap();
+        StandardIds::from_model3(moc, &model3, &IdAliases::default())
+    };
+
+    // without a group the standard parameters are used
+    let ids = from_groups("[]");
+    assert_eq!(ids.eye_blink, None);
+    assert_eq!(ids.eye_blink_parameters(), standard);
+    // an empty group disables the eye blink
+    let ids = from_groups(r#"[{"Target": "Parameter", "Name": "EyeBlink", "Ids": []}]"#);
+    assert_eq!(ids.eye_blink, Some(Vec::new()));
+    assert!(ids.eye_blink_parameters().is_empty());
+    let ids = from_groups(r#"[{"Target": "Parameter", "Name": "EyeBlink", "Ids": ["Blink"]}]"#);
+    assert_eq!(
+        ids.eye_blink_parameters(),
+        [moc.parameter_index("Blink").unwrap()]
+    );
+}
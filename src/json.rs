@@ -1,5 +1,7 @@
 #![allow(missing_docs)]
 
+mod format;
+
 pub mod cdi;
 pub mod expression;
 pub mod model;
@@ -8,6 +10,10 @@ pub mod physics;
 pub mod pose;
 pub mod user_data;
 
+pub use self::format::{
+    parse, FileFormat, FormatError, FormatWarning, Identification, JsonFile, Parsed,
+};
+
 /// Utility function to map non-positive floats to 1.0 after deserialization
 pub(self) fn de_fade_time<'de, D>(d: D) -> Result<f32, D::Error>
 where
@@ -20,3 +26,99 @@ where
 pub(self) const fn float_1() -> f32 {
     1.0
 }
+
+// The sample models of the Cubism SDK, tests using them are skipped if
+// `CUBISM_CORE` isn't set at compile time.
+#[cfg(test)]
+pub(crate) fn samples_dir() -> Option<std::path::PathBuf> {
+    option_env!("CUBISM_CORE").map(|core| std::path::Path::new(core).join("Samples/Res"))
+}
+
+// Minimal valid documents of every format, mutated by `json_malformed_inputs`.
+#[cfg(test)]
+const MINIMAL_DOCUMENTS: &[&str] = &[
+    r#"{"Version": 3, "FileReferences": {"Moc": "a.moc3", "Textures": ["a.png"]},
+        "Groups": [{"Target": "Parameter", "Name": "EyeBlink", "Ids": ["A"]}]}"#,
+    r#"{"Version": 3, "Meta": {"Duration": 1.0, "Fps": 30.0, "Loop": true,
+        "AreBeziersRestricted": true, "CurveCount": 1, "TotalSegmentCount": 2,
+        "TotalPointCount": 5, "UserDataCount": 0, "TotalUserDataSize": 0},
+        "Curves": [{"Target": "Parameter", "Id": "A",
+        "Segments": [0, 0, 1, 0.2, 0.5, 0.4, 0.5, 0.6, 1, 0, 1, 1]}]}"#,
+    r#"{"Version": 3, "Meta": {"PhysicsSettingCount": 1, "TotalInputCount": 1,
+        "TotalOutputCount": 1, "VertexCount": 1, "EffectiveForces": {},
+        "PhysicsDictionary": []},
+        "PhysicsSettings": [{"Id": "P", "Input": [{"Source": {"Target": "Parameter",
+        "Id": "A"}, "Weight": 100, "Type": "X", "Reflect": false}], "Output": [],
+        "Vertices": [{"Position": {"X": 0, "Y": 0}, "Mobility": 1, "Delay": 1,
+        "Acceleration": 1, "Radius": 0}]}]}"#,
+    r#"{"Type": "Live2D Expression", "Parameters": [{"Id": "A", "Value": 1, "Blend": "Add"}]}"#,
+    r#"{"Type": "Live2D Pose", "Groups": [[{"Id": "A", "Link": []}, {"Id": "B", "Link": ["C"]}]]}"#,
+    r#"{"Version": 3, "Parameters": [{"Id": "A", "GroupId": "", "Name": "a"}], "Parts": []}"#,
+    r#"{"Version": 3, "Meta": {"UserDataCount": 1, "TotalUserDataSize": 1},
+        "UserData": [{"Target": "ArtMesh", "Id": "A", "Value": "a"}]}"#,
+];
+
+// Parses the data as every format through `format::parse`, which must never
+// panic. The document at the index must parse and survive a round-trip if
+// `intact` is set.
+#[cfg(test)]
+fn parse_all(data: &[u8], doc: usize, intact: bool) {
+    fn parse<T: JsonFile + serde::Serialize>(data: &[u8], intact: bool) {
+        let parsed = format::parse::<T>(data);
+        if intact {
+            let value = parsed.unwrap().value;
+            let json = serde_json::to_value(&value).unwrap();
+            let reparsed = format::parse::<T>(&serde_json::to_vec(&json).unwrap()).unwrap();
+            assert_eq!(serde_json::to_value(&reparsed.value).unwrap(), json);
+        }
+    }
+    parse::<model::Model3>(data, intact && doc == 0);
+    parse::<motion::Motion3>(data, intact && doc == 1);
+    parse::<physics::Physics3>(data, intact && doc == 2);
+    parse::<expression::Expression3>(data, intact && doc == 3);
+    parse::<pose::Pose3>(data, intact && doc == 4);
+    parse::<cdi::Cdi3>(data, intact && doc == 5);
+    parse::<user_data::UserData3>(data, intact && doc == 6);
+}
+
+#[test]
+fn json_minimal_documents() {
+    use std::str::FromStr;
+    model::Model3::from_str(MINIMAL_DOCUMENTS[0]).unwrap();
+    motion::Motion3::from_str(MINIMAL_DOCUMENTS[1]).unwrap();
+    physics::Physics3::from_str(MINIMAL_DOCUMENTS[2]).unwrap();
+    expression::Expression3::from_str(MINIMAL_DOCUMENTS[3]).unwrap();
+    pose::Pose3::from_str(MINIMAL_DOCUMENTS[4]).unwrap();
+    cdi::Cdi3::from_str(MINIMAL_DOCUMENTS[5]).unwrap();
+    user_data::UserData3::from_str(MINIMAL_DOCUMENTS[6]).unwrap();
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn json_malformed_inputs(
+        doc in 0..MINIMAL_DOCUMENTS.len(),
+        edits in proptest::collection::vec((0..4u8, proptest::num::usize::ANY, proptest::num::u8::ANY), 1..4),
+    ) {
+        let mut data = MINIMAL_DOCUMENTS[doc].as_bytes().to_vec();
+        for (op, pos, byte) in edits {
+            if data.is_empty() {
+                break;
+            }
+            let pos = pos % data.len();
+            match op {
+                0 => data.truncate(pos),
+                1 => data[pos] = byte,
+                2 => {
+                    data.remove(pos);
+                },
+                // whitespace between tokens leaves the document intact
+                _ => data.insert(pos, b" \t\r\n"[byte as usize % 4]),
+            }
+        }
+        // the document is intact if it still holds the same json
+        let original: serde_json::Value = serde_json::from_str(MINIMAL_DOCUMENTS[doc]).unwrap();
+        let intact = serde_json::from_slice::<serde_json::Value>(&data).ok() == Some(original);
+        parse_all(&data, doc, intact);
+    }
+}
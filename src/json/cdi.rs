@@ -1,12 +1,15 @@
 /// Parses .cdi3.json.
 use serde::{Deserialize, Serialize};
 
+use super::{format, FileFormat, FormatError, JsonFile};
+
 use std::str::FromStr;
 
 /// Rust structure representation for .cdi3.json file.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Cdi3 {
+    #[serde(default = "format::default_version")]
     pub version: usize,
     #[serde(default)]
     pub parameters: Vec<Cdi3Parameter>,
@@ -14,56 +17,64 @@ pub struct Cdi3 {
     pub parameter_groups: Vec<Cdi3ParameterGroup>,
     #[serde(default)]
     pub parts: Vec<Cdi3Part>,
+    /// Pairs of parameters edited together, added by newer exports.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub combined_parameters: Vec<Vec<String>>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Cdi3Parameter {
-    id: String,
+    pub id: String,
     #[serde(default)]
-    group_id: String,
-    name: String,
+    pub group_id: String,
+    pub name: String,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Cdi3ParameterGroup {
-    id: String,
+    pub id: String,
     #[serde(default)]
-    group_id: String,
-    name: String,
+    pub group_id: String,
+    pub name: String,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Cdi3Part {
-    id: String,
-    name: String,
+    pub id: String,
+    pub name: String,
 }
 
 impl Cdi3 {
     /// Parses a Cdi3 from a .cdi3.json reader.
     #[inline]
-    pub fn from_reader<R: std::io::Read>(r: R) -> serde_json::Result<Self> {
-        serde_json::from_reader(r)
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, FormatError> {
+        format::from_reader(r)
     }
 }
 
+impl JsonFile for Cdi3 {
+    const FORMAT: FileFormat = FileFormat::Cdi3;
+}
+
 impl FromStr for Cdi3 {
-    type Err = serde_json::Error;
+    type Err = FormatError;
 
     /// Parses a Cdi3 from a .cdi3.json string.
     #[inline]
-    fn from_str(s: &str) -> serde_json::Result<Self> {
-        serde_json::from_str(s)
+    fn from_str(s: &str) -> Result<Self, FormatError> {
+        format::parse(s.as_bytes()).map(|parsed| parsed.value)
     }
 }
 
 #[test]
 fn json_samples_cdi3() {
-    use std::iter::FromIterator;
-    let path =
-        std::path::PathBuf::from_iter(&[env!("CUBISM_CORE"), "Samples/Res/Rice/Rice.cdi3.json"]);
+    let path = match super::samples_dir() {
+        Some(path) => path.join("Rice/Rice.cdi3.json"),
+        None => return,
+    };
 
     Cdi3::from_str(
         &std::fs::read_to_string(&path)
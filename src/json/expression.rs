@@ -1,6 +1,9 @@
 // Parses .exp3.json.
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{format, FileFormat, FormatError, FormatWarning, JsonFile};
 
 use std::str::FromStr;
 
@@ -20,18 +23,33 @@ pub struct Expression3 {
 impl Expression3 {
     /// Parses a Expression3 from a .expression3.json reader.
     #[inline]
-    pub fn from_reader<R: std::io::Read>(r: R) -> serde_json::Result<Self> {
-        serde_json::from_reader(r)
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, FormatError> {
+        format::from_reader(r)
+    }
+}
+
+impl JsonFile for Expression3 {
+    const FORMAT: FileFormat = FileFormat::Exp3;
+
+    fn upgrade(json: &mut Value, _: &mut Vec<FormatWarning>) -> bool {
+        // Parameters without a blend mode are added, like in the official SDK.
+        let mut changed = false;
+        if let Some(params) = json.get_mut("Parameters").and_then(Value::as_array_mut) {
+            for param in params {
+                changed |= format::insert_default(param, "Blend", "Add".into());
+            }
+        }
+        changed
     }
 }
 
 impl FromStr for Expression3 {
-    type Err = serde_json::Error;
+    type Err = FormatError;
 
     /// Parses a Expression3 from a .expression3.json string.
     #[inline]
-    fn from_str(s: &str) -> serde_json::Result<Self> {
-        serde_json::from_str(s)
+    fn from_str(s: &str) -> Result<Self, FormatError> {
+        format::parse(s.as_bytes()).map(|parsed| parsed.value)
     }
 }
 
@@ -53,8 +71,10 @@ pub struct ExpressionParameter {
 
 #[test]
 fn json_samples_exp3() {
-    use std::iter::FromIterator;
-    let path = std::path::PathBuf::from_iter(&[env!("CUBISM_CORE"), "Samples/Res"]);
+    let path = match super::samples_dir() {
+        Some(path) => path,
+        None => return,
+    };
     for model in &["Haru/expressions", "Natori/exp"] {
         let exp_path = path.join(model);
         let expressions = std::fs::read_dir(exp_path).unwrap();
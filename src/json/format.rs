@@ -0,0 +1,357 @@
+//! Version detection and compatibility handling shared by all json formats.
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use std::{error, fmt, io::Read, ops::RangeInclusive};
+
+// The only supported version of the versioned formats. Older versions are
+// rejected, there are no upgrades from them.
+const VERSION: u8 = 3;
+
+/// The json file formats of a model package.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    /// .model3.json
+    Model3,
+    /// .motion3.json
+    Motion3,
+    /// .physics3.json
+    Physics3,
+    /// .exp3.json
+    Exp3,
+    /// .pose3.json
+    Pose3,
+    /// .cdi3.json
+    Cdi3,
+    /// .userdata3.json
+    UserData3,
+}
+
+impl FileFormat {
+    /// The file extension of the format, without the `.json` suffix.
+    pub fn name(self) -> &'static str {
+        match self {
+            FileFormat::Model3 => "model3",
+            FileFormat::Motion3 => "motion3",
+            FileFormat::Physics3 => "physics3",
+            FileFormat::Exp3 => "exp3",
+            FileFormat::Pose3 => "pose3",
+            FileFormat::Cdi3 => "cdi3",
+            FileFormat::UserData3 => "userdata3",
+        }
+    }
+
+    /// How the format identifies itself.
+    pub fn identification(self) -> Identification {
+        match self {
+            FileFormat::Exp3 => Identification::Type("Live2D Expression"),
+            FileFormat::Pose3 => Identification::Type("Live2D Pose"),
+            _ => Identification::Version(VERSION.into()..=VERSION.into()),
+        }
+    }
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}", self.name())
+    }
+}
+
+/// How a format identifies itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Identification {
+    /// A `Version` field, with the range of versions that are supported.
+    /// Currently this is always version 3 alone. Files with an older version
+    /// are rejected, files with a newer version are loaded on a best effort
+    /// basis.
+    Version(RangeInclusive<u64>),
+    /// A `Type` field with a fixed value. These formats are not versioned.
+    Type(&'static str),
+}
+
+/// A problem with a json file that didn't prevent it from being loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormatWarning {
+    /// The file has a version newer than the supported ones.
+    NewerVersion {
+        /// The version of the file.
+        version: u64,
+        /// The newest supported version.
+        supported: u64,
+    },
+    /// The file has no `Version` field, it has been loaded as the newest
+    /// supported version.
+    MissingVersion {
+        /// The version the file has been loaded as.
+        assumed: u64,
+    },
+    /// The file has the wrong `Type`.
+    WrongType {
+        /// The type of the file.
+        found: String,
+    },
+    /// The file contains a field that isn't known to this crate. The path
+    /// leads from the root of the file to the field.
+    UnknownField(String),
+    /// An entry has been dropped because its target isn't known to this
+    /// crate.
+    UnknownTarget {
+        /// The path of the dropped entry.
+        path: String,
+        /// The unknown target.
+        target: String,
+    },
+}
+
+impl fmt::Display for FormatWarning {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatWarning::NewerVersion { version, supported } => write!(
+                fmt,
+                "version {} is newer than the supported version {}",
+                version, supported
+            ),
+            FormatWarning::MissingVersion { assumed } => {
+                write!(fmt, "missing version, assuming version {}", assumed)
+            },
+            FormatWarning::WrongType { found } => write!(fmt, "unexpected type {:?}", found),
+            FormatWarning::UnknownField(path) => write!(fmt, "unknown field {}", path),
+            FormatWarning::UnknownTarget { path, target } => {
+                write!(fmt, "dropped {} with unknown target {:?}", path, target)
+            },
+        }
+    }
+}
+
+/// An error returned when parsing a json file fails.
+#[derive(Debug)]
+pub enum FormatError {
+    /// The file isn't valid json or doesn't match the format.
+    Json(serde_json::Error),
+    /// The version of the file is older than version 3, the only supported one.
+    UnsupportedVersion {
+        /// The format of the file.
+        format: FileFormat,
+        /// The version of the file.
+        version: u64,
+    },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Json(e) => (e as &dyn fmt::Display).fmt(fmt),
+            FormatError::UnsupportedVersion { format, version } => {
+                write!(fmt, "{} version {} is not supported", format, version)
+            },
+        }
+    }
+}
+
+impl error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FormatError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(e: serde_json::Error) -> Self {
+        FormatError::Json(e)
+    }
+}
+
+/// A parsed json file together with the warnings raised while parsing it.
+#[derive(Clone, Debug)]
+pub struct Parsed<T> {
+    /// The parsed file.
+    pub value: T,
+    /// The warnings raised while parsing.
+    pub warnings: Vec<FormatWarning>,
+}
+
+/// A json file format, parsed with [`parse`].
+pub trait JsonFile: DeserializeOwned {
+    /// The format of the file.
+    const FORMAT: FileFormat;
+
+    /// Fills in the fields that some exporters of the supported version omit,
+    /// returning whether anything has been changed. This doesn't convert older
+    /// versions, these are rejected before it's called.
+    fn upgrade(_json: &mut Value, _warnings: &mut Vec<FormatWarning>) -> bool {
+        false
+    }
+
+    /// Reports fields that have been kept without being understood, like the
+    /// flattened `extra` maps.
+    fn unknown_fields(&self, _warnings: &mut Vec<FormatWarning>) {}
+}
+
+/// Parses a json file, checking its version and filling in omitted fields.
+///
+/// Only version 3 of the versioned formats is accepted, older versions fail
+/// with [`FormatError::UnsupportedVersion`].
+///
+/// Fields that aren't known to this crate are reported as warnings instead of
+/// being silently ignored.
+pub fn parse<T: JsonFile>(data: &[u8]) -> Result<Parsed<T>, FormatError> {
+    let mut json: Value = serde_json::from_slice(data)?;
+    let mut warnings = Vec::new();
+    match T::FORMAT.identification() {
+        Identification::Version(range) => {
+            let version = match json.get("Version").and_then(Value::as_u64) {
+                Some(version) => version,
+                None => {
+                    warnings.push(FormatWarning::MissingVersion {
+                        assumed: *range.end(),
+                    });
+                    // the version fields default to the newest version, so
+                    // the file parses the same without inserting it
+                    *range.end()
+                },
+            };
+            if version < *range.start() {
+                return Err(FormatError::UnsupportedVersion {
+                    format: T::FORMAT,
+                    version,
+                });
+            } else if version > *range.end() {
+                warnings.push(FormatWarning::NewerVersion {
+                    version,
+                    supported: *range.end(),
+                });
+            }
+        },
+        Identification::Type(ty) => match json.get("Type").and_then(Value::as_str) {
+            Some(found) if found != ty => warnings.push(FormatWarning::WrongType {
+                found: found.to_owned(),
+            }),
+            _ => (),
+        },
+    }
+
+    let upgraded = T::upgrade(&mut json, &mut warnings);
+    let mut record = |path: serde_ignored::Path<'_>| {
+        warnings.push(FormatWarning::UnknownField(path.to_string()))
+    };
+    let value: T = if upgraded {
+        serde_ignored::deserialize(json, &mut record)?
+    } else {
+        // Deserialize from the original data if nothing changed, so that
+        // errors point to the line and column they occurred at.
+        serde_ignored::deserialize(&mut serde_json::Deserializer::from_slice(data), &mut record)?
+    };
+    value.unknown_fields(&mut warnings);
+    Ok(Parsed { value, warnings })
+}
+
+/// The version assumed for files without a `Version` field.
+pub(super) fn default_version<T: From<u8>>() -> T {
+    T::from(VERSION)
+}
+
+/// Reads and parses a json file with [`parse`], discarding the warnings.
+pub(super) fn from_reader<T: JsonFile, R: Read>(mut r: R) -> Result<T, FormatError> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data).map_err(serde_json::Error::io)?;
+    parse(&data).map(|parsed| parsed.value)
+}
+
+/// Removes the entries of the array at `key` whose `Target` isn't one of
+/// `known`, recording a warning for each.
+pub(super) fn retain_targets(
+    json: &mut Value,
+    key: &str,
+    known: &[&str],
+    warnings: &mut Vec<FormatWarning>,
+) -> bool {
+    let entries = match json.get_mut(key).and_then(Value::as_array_mut) {
+        Some(entries) => entries,
+        None => return false,
+    };
+    let len = entries.len();
+    let mut idx = 0;
+    entries.retain(|entry| {
+        let target = entry.get("Target").and_then(Value::as_str).unwrap_or("");
+        let retain = known.contains(&target);
+        if !retain {
+            warnings.push(FormatWarning::UnknownTarget {
+                path: format!("{}.{}", key, idx),
+                target: target.to_owned(),
+            });
+        }
+        idx += 1;
+        retain
+    });
+    entries.len() != len
+}
+
+/// Inserts the value under `key` into the object if it is missing.
+pub(super) fn insert_default(json: &mut Value, key: &str, default: Value) -> bool {
+    match json.as_object_mut() {
+        Some(object) if !object.contains_key(key) => {
+            object.insert(key.to_owned(), default);
+            true
+        },
+        _ => false,
+    }
+}
+
+#[test]
+fn json_parse_versions() {
+    use super::{expression::Expression3, user_data::UserData3};
+    let parsed: Parsed<UserData3> = parse(
+        br#"{"Version": 4, "Meta": {"UserDataCount": 2, "TotalUserDataSize": 2, "Foo": 1},
+            "UserData": [{"Target": "Part", "Id": "A", "Value": "a"},
+                         {"Target": "ArtMesh", "Id": "B", "Value": "b"}]}"#,
+    )
+    .unwrap();
+    assert_eq!(parsed.value.user_data.len(), 1);
+    assert_eq!(
+        parsed.warnings,
+        vec![
+            FormatWarning::NewerVersion {
+                version: 4,
+                supported: 3
+            },
+            FormatWarning::UnknownTarget {
+                path: "UserData.0".to_owned(),
+                target: "Part".to_owned()
+            },
+            FormatWarning::UnknownField("Meta.Foo".to_owned()),
+        ]
+    );
+
+    match parse::<UserData3>(br#"{"Version": 2, "Meta": {}, "UserData": []}"#) {
+        Err(FormatError::UnsupportedVersion { version: 2, .. }) => (),
+        res => panic!("unexpected result {:?}", res),
+    }
+    let parsed: Parsed<UserData3> =
+        parse(br#"{"Meta": {"UserDataCount": 0, "TotalUserDataSize": 0}, "UserData": []}"#)
+            .unwrap();
+    assert_eq!(
+        parsed.warnings,
+        vec![FormatWarning::MissingVersion { assumed: 3 }]
+    );
+    assert_eq!(parsed.value.version, 3);
+    // errors in files without a version still point to where they occurred
+    let data = br#"{
+        "Meta": {"UserDataCount": 0, "TotalUserDataSize": 0},
+        "UserData": 1
+    }"#;
+    match parse::<UserData3>(data) {
+        Err(FormatError::Json(e)) => assert_eq!(e.line(), 3),
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    let parsed: Parsed<Expression3> =
+        parse(br#"{"Type": "Live2D Expression", "Parameters": [{"Id": "A", "Value": 1}]}"#)
+            .unwrap();
+    assert_eq!(
+        parsed.value.parameters[0].blend_type,
+        super::expression::ExpressionBlendType::Add
+    );
+    assert!(parsed.warnings.is_empty());
+}
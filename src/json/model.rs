@@ -1,10 +1,15 @@
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{format, FileFormat, FormatError, FormatWarning, JsonFile};
 
 use std::{path::PathBuf, str::FromStr};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Model3 {
+    #[serde(default = "format::default_version")]
     pub version: usize,
     #[serde(default)]
     pub file_references: FileReferences,
@@ -12,40 +17,92 @@ pub struct Model3 {
     pub groups: Vec<Group>,
     #[serde(default)]
     pub hit_areas: Vec<HitArea>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub layout: Option<Layout>,
+    /// Keys not known to this crate, kept for lossless round-trips.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Model3 {
     /// Parses a Model3 from a .model3.json reader.
     #[inline]
-    pub fn from_reader<R: std::io::Read>(r: R) -> serde_json::Result<Self> {
-        serde_json::from_reader(r)
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, FormatError> {
+        format::from_reader(r)
+    }
+}
+
+impl JsonFile for Model3 {
+    const FORMAT: FileFormat = FileFormat::Model3;
+
+    fn upgrade(json: &mut Value, warnings: &mut Vec<FormatWarning>) -> bool {
+        format::retain_targets(json, "Groups", &["Parameter", "Part", "Drawable"], warnings)
+    }
+
+    fn unknown_fields(&self, warnings: &mut Vec<FormatWarning>) {
+        let mut report = |prefix: String, extra: &Map<String, Value>| {
+            for key in extra.keys() {
+                warnings.push(FormatWarning::UnknownField(prefix.clone() + key));
+            }
+        };
+        report(String::new(), &self.extra);
+        let refs = &self.file_references;
+        report("FileReferences.".to_owned(), &refs.extra);
+        for (i, exp) in refs.expressions.iter().enumerate() {
+            report(format!("FileReferences.Expressions.{}.", i), &exp.extra);
+        }
+        for (group, motions) in refs.motions.iter() {
+            for (i, motion) in motions.iter().enumerate() {
+                report(
+                    format!("FileReferences.Motions.{}.{}.", group, i),
+                    &motion.extra,
+                );
+            }
+        }
+        for (i, group) in self.groups.iter().enumerate() {
+            report(format!("Groups.{}.", i), &group.extra);
+        }
+        for (i, hit_area) in self.hit_areas.iter().enumerate() {
+            report(format!("HitAreas.{}.", i), &hit_area.extra);
+        }
+        if let Some(layout) = self.layout.as_ref() {
+            report("Layout.".to_owned(), &layout.extra);
+        }
     }
 }
 
 impl FromStr for Model3 {
-    type Err = serde_json::Error;
+    type Err = FormatError;
 
     /// Parses a Model3 from a .model3.json string.
     #[inline]
-    fn from_str(s: &str) -> serde_json::Result<Self> {
-        serde_json::from_str(s)
+    fn from_str(s: &str) -> Result<Self, FormatError> {
+        format::parse(s.as_bytes()).map(|parsed| parsed.value)
     }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct FileReferences {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub moc: Option<PathBuf>,
     #[serde(default)]
     pub textures: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub pose: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub physics: Option<PathBuf>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub expressions: Vec<Expression>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub motions: Motions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub user_data: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_info: Option<PathBuf>,
+    /// Keys not known to this crate, kept for lossless round-trips.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -54,42 +111,45 @@ pub struct Group {
     pub target: GroupTarget,
     pub name: String,
     pub ids: Vec<String>,
+    /// Keys not known to this crate, kept for lossless round-trips.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialOrd, PartialEq)]
 pub enum GroupTarget {
     Parameter,
     Part,
-    //Drawable?
+    Drawable,
 }
 
-// TODO: Might very well be just a hashmap figure out whether these names should
-// be hardcoded or not
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct Motions {
-    #[serde(default)]
-    pub idle: Vec<Motion>,
-    #[serde(default, rename = "TapBody")]
-    pub tap_body: Vec<Motion>,
-    #[serde(default, rename = "PinchIn")]
-    pub pinch_in: Vec<Motion>,
-    #[serde(default, rename = "PinchOut")]
-    pub pinch_out: Vec<Motion>,
-    #[serde(default)]
-    pub shake: Vec<Motion>,
-    #[serde(default, rename = "FlickHead")]
-    pub flick_head: Vec<Motion>,
-}
+/// The motion groups of a model, keyed by group name in the order they appear
+/// in the model3.json.
+pub type Motions = IndexMap<String, Vec<Motion>>;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Motion {
     pub file: PathBuf,
-    #[serde(rename = "FadeInTime", default = "super::float_1")]
-    pub fade_in_time: f32,
-    #[serde(rename = "FadeOutTime", default = "super::float_1")]
-    pub fade_out_time: f32,
+    /// The fade-in time overriding the motion's own, if any.
+    #[serde(
+        rename = "FadeInTime",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fade_in_time: Option<f32>,
+    /// The fade-out time overriding the motion's own, if any.
+    #[serde(
+        rename = "FadeOutTime",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fade_out_time: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound: Option<PathBuf>,
+    /// Keys not known to this crate, kept for lossless round-trips.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -97,6 +157,9 @@ pub struct Motion {
 pub struct Expression {
     pub name: String,
     pub file: PathBuf,
+    /// Keys not known to this crate, kept for lossless round-trips.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -104,25 +167,45 @@ pub struct Expression {
 pub struct HitArea {
     pub name: String,
     pub id: String,
+    /// Keys not known to this crate, kept for lossless round-trips.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Layout {
-    #[serde(rename = "CenterX")]
-    pub center_x: f32,
-    #[serde(rename = "CenterY")]
-    pub center_y: f32,
-    pub x: f32,
-    pub y: f32,
-    pub width: f32,
-    pub height: f32,
+    #[serde(rename = "CenterX", default, skip_serializing_if = "Option::is_none")]
+    pub center_x: Option<f32>,
+    #[serde(rename = "CenterY", default, skip_serializing_if = "Option::is_none")]
+    pub center_y: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bottom: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right: Option<f32>,
+    /// Keys not known to this crate, kept for lossless round-trips.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[test]
 fn json_samples_model3() {
-    use std::iter::FromIterator;
-    let path = std::path::PathBuf::from_iter(&[env!("CUBISM_CORE"), "Samples/Res"]);
+    let path = match super::samples_dir() {
+        Some(path) => path,
+        None => return,
+    };
     for model in &["Haru", "Hiyori", "Mark", "Natori"] {
         let model_path = path.join([model, "/", model, ".model3.json"].concat());
         Model3::from_str(
@@ -132,3 +215,35 @@ fn json_samples_model3() {
         .unwrap_or_else(|e| panic!("error while parsing {:?}: {:?}", &model_path, e));
     }
 }
+
+#[test]
+fn json_model3_roundtrip() {
+    let json = r#"{
+        "Version": 3,
+        "FileReferences": {
+            "Moc": "model.moc3",
+            "Textures": ["model.2048/texture_00.png"],
+            "DisplayInfo": "model.cdi3.json",
+            "Motions": {
+                "Wave": [{"File": "motions/wave.motion3.json", "FadeInTime": 0.5, "FadeOutTime": 0.5, "Sound": "sounds/wave.wav"}],
+                "Idle": [{"File": "motions/idle.motion3.json"}]
+            },
+            "Unknown": {"Nested": [1, 2, 3]}
+        },
+        "Groups": [{"Target": "Drawable", "Name": "Outline", "Ids": ["ArtMesh1"]}],
+        "HitAreas": [],
+        "Layout": {"Width": 2.0, "Top": 1.0, "Left": -1.0, "Unknown": "kept"}
+    }"#;
+    let model3 = Model3::from_str(json).unwrap();
+    let names: Vec<_> = model3.file_references.motions.keys().collect();
+    assert_eq!(names, ["Wave", "Idle"]);
+    assert_eq!(model3.groups[0].target, GroupTarget::Drawable);
+    // fade times that aren't given stay unset instead of defaulting
+    let idle = &model3.file_references.motions["Idle"][0];
+    assert_eq!((idle.fade_in_time, idle.fade_out_time), (None, None));
+    assert_eq!(model3.layout.as_ref().unwrap().extra["Unknown"], "kept");
+    assert_eq!(
+        serde_json::to_value(&model3).unwrap(),
+        serde_json::from_str::<Value>(json).unwrap()
+    );
+}
@@ -1,7 +1,10 @@
 //! Parses .motion3.json.
 use serde::{self, Deserialize, Serialize};
+use serde_json::Value;
 
-use std::str::FromStr;
+use super::{format, FileFormat, FormatError, FormatWarning, JsonFile};
+
+use std::{convert::TryFrom, fmt, str::FromStr};
 
 /// Rust structure representation for Motion3 metadata.
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -27,10 +30,16 @@ pub struct Meta {
     pub user_data_count: usize,
     /// A total size of user data.
     pub total_user_data_size: usize,
+    /// Fade-in time of the whole motion, added by newer exports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_in_time: Option<f32>,
+    /// Fade-out time of the whole motion, added by newer exports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_out_time: Option<f32>,
 }
 
 /// Point.
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SegmentPoint {
     /// Time.
     pub time: f32,
@@ -39,7 +48,7 @@ pub struct SegmentPoint {
 }
 
 /// Segment.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Segment {
     /// Linear.
     Linear(SegmentPoint, SegmentPoint),
@@ -53,138 +62,179 @@ pub enum Segment {
 
 mod segment_parser {
     use crate::json::motion::{Segment, SegmentPoint};
-    use serde::{
-        self,
-        de::{self, SeqAccess, Visitor},
-        Deserializer, Serializer,
-    };
+    use serde::Serializer;
+
+    use std::fmt;
+
+    const SEG_LINEAR: i32 = 0; // リニア
+    const SEG_BEZIER: i32 = 1; // ベジェ曲線
+    const SEG_STEPPED: i32 = 2; // ステップ
+    const SEG_INV: i32 = 3; // インバースステップ
+
+    /// An error in the segments of a curve.
+    #[derive(Debug)]
+    pub struct SegmentError {
+        /// The index of the segment the error occurred in.
+        pub index: usize,
+        /// What is wrong with the segment.
+        pub kind: SegmentErrorKind,
+    }
 
-    struct SegmentVisitor;
+    /// What is wrong with a segment.
+    #[derive(Debug)]
+    pub enum SegmentErrorKind {
+        /// The curve has no starting point.
+        MissingStart,
+        /// The segment type is not one of the known types.
+        UnknownType(f32),
+        /// The segment ends before all of its points have been read.
+        Truncated,
+    }
 
-    impl<'de> Visitor<'de> for SegmentVisitor {
-        type Value = Vec<Segment>;
-        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-            formatter.write_str("sequence of integers / numbers")
+    impl fmt::Display for SegmentError {
+        fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.kind {
+                SegmentErrorKind::MissingStart => write!(fmt, "missing the starting point"),
+                SegmentErrorKind::UnknownType(ty) => {
+                    write!(fmt, "segment {} has unknown type {}", self.index, ty)
+                },
+                SegmentErrorKind::Truncated => write!(fmt, "segment {} is truncated", self.index),
+            }
         }
+    }
 
-        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
-            let mut seq = seq;
-            let mut ret = vec![];
-
-            const SEG_LINEAR: i32 = 0; // リニア
-            const SEG_BEZIER: i32 = 1; // ベジェ曲線
-            const SEG_STEPPED: i32 = 2; // ステップ
-            const SEG_INV: i32 = 3; // インバースステップ
-
-            // parse the first position
-            let t0: f32 = seq.next_element()?.unwrap();
-            let v0: f32 = seq.next_element()?.unwrap();
-
-            let mut last_point = SegmentPoint {
-                time: t0,
-                value: v0,
-            };
-
-            // parse positions
-            while let Some(seg_type) = seq.next_element()? as Option<i32> {
-                match seg_type {
-                    SEG_LINEAR => {
-                        let t0: f32 = seq.next_element()?.unwrap();
-                        let v0: f32 = seq.next_element()?.unwrap();
-
-                        let next_point = SegmentPoint {
-                            time: t0,
-                            value: v0,
-                        };
-
-                        ret.push(Segment::Linear(last_point, next_point));
-                        last_point = next_point;
-                    },
-                    SEG_STEPPED => {
-                        let t0: f32 = seq.next_element()?.unwrap();
-                        let v0: f32 = seq.next_element()?.unwrap();
-
-                        ret.push(Segment::Stepped(last_point, t0));
-
-                        last_point = SegmentPoint {
-                            time: t0,
-                            value: v0,
-                        };
-                    },
-                    SEG_INV => {
-                        let t0: f32 = seq.next_element()?.unwrap();
-                        let v0: f32 = seq.next_element()?.unwrap();
-
-                        let tn = last_point.time;
-
-                        last_point = SegmentPoint {
-                            time: t0,
-                            value: v0,
-                        };
-
-                        ret.push(Segment::InverseStepped(tn, last_point));
-                    },
-                    SEG_BEZIER => {
-                        let t0: f32 = seq.next_element()?.unwrap();
-                        let v0: f32 = seq.next_element()?.unwrap();
-                        let t1: f32 = seq.next_element()?.unwrap();
-                        let v1: f32 = seq.next_element()?.unwrap();
-                        let t2: f32 = seq.next_element()?.unwrap();
-                        let v2: f32 = seq.next_element()?.unwrap();
-
-                        let next_point = SegmentPoint {
-                            time: t2,
-                            value: v2,
-                        };
-
-                        ret.push(Segment::Bezier([
-                            last_point,
-                            SegmentPoint {
-                                time: t0,
-                                value: v0,
-                            },
-                            SegmentPoint {
-                                time: t1,
-                                value: v1,
-                            },
-                            next_point,
-                        ]));
-
-                        last_point = next_point;
-                    },
-                    _ => return Err(de::Error::custom("invalid segment format.")),
-                }
+    struct Reader<'a> {
+        data: &'a [f32],
+        index: usize,
+    }
+
+    impl Reader<'_> {
+        fn point(&mut self) -> Result<SegmentPoint, SegmentError> {
+            match *self.data {
+                [time, value, ref rest @ ..] => {
+                    self.data = rest;
+                    Ok(SegmentPoint { time, value })
+                },
+                _ => Err(self.error(SegmentErrorKind::Truncated)),
             }
+        }
 
-            Ok(ret)
+        fn error(&self, kind: SegmentErrorKind) -> SegmentError {
+            SegmentError {
+                index: self.index,
+                kind,
+            }
         }
     }
 
-    pub fn serialize<S>(_: &[Segment], _: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        unimplemented!()
+    /// Parses the flat segment data of a curve.
+    pub fn parse(data: &[f32]) -> Result<Vec<Segment>, SegmentError> {
+        let mut reader = Reader { data, index: 0 };
+        let mut ret = vec![];
+
+        // parse the first position
+        let mut last_point = reader
+            .point()
+            .map_err(|_| reader.error(SegmentErrorKind::MissingStart))?;
+
+        // parse positions
+        while let Some((&seg_type, rest)) = reader.data.split_first() {
+            reader.data = rest;
+            // The type is stored as a float, reject fractional values instead
+            // of truncating them.
+            let ty = seg_type as i32;
+            if ty as f32 != seg_type {
+                return Err(reader.error(SegmentErrorKind::UnknownType(seg_type)));
+            }
+            match ty {
+                SEG_LINEAR => {
+                    let next_point = reader.point()?;
+                    ret.push(Segment::Linear(last_point, next_point));
+                    last_point = next_point;
+                },
+                SEG_STEPPED => {
+                    let next_point = reader.point()?;
+                    ret.push(Segment::Stepped(last_point, next_point.time));
+                    last_point = next_point;
+                },
+                SEG_INV => {
+                    let next_point = reader.point()?;
+                    ret.push(Segment::InverseStepped(last_point.time, next_point));
+                    last_point = next_point;
+                },
+                SEG_BEZIER => {
+                    let p1 = reader.point()?;
+                    let p2 = reader.point()?;
+                    let next_point = reader.point()?;
+                    ret.push(Segment::Bezier([last_point, p1, p2, next_point]));
+                    last_point = next_point;
+                },
+                _ => return Err(reader.error(SegmentErrorKind::UnknownType(seg_type))),
+            }
+            reader.index += 1;
+        }
+
+        Ok(ret)
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Segment>, D::Error>
+    /// Flattens the segments of a curve, the inverse of [`parse`].
+    ///
+    /// The points stepped segments don't store take the value of the
+    /// adjacent segment, which doesn't change how the curve evaluates.
+    pub fn serialize<S>(segments: &[Segment], serializer: S) -> Result<S::Ok, S::Error>
     where
-        D: Deserializer<'de>,
+        S: Serializer,
     {
-        deserializer.deserialize_seq(SegmentVisitor)
+        let start = |seg: &Segment| match *seg {
+            Segment::Linear(p0, _) | Segment::Stepped(p0, _) => p0,
+            Segment::Bezier([p0, ..]) => p0,
+            Segment::InverseStepped(time, p1) => SegmentPoint {
+                time,
+                value: p1.value,
+            },
+        };
+        let mut data = Vec::with_capacity(2 + segments.len() * 7);
+        let first = segments.first().map_or(
+            SegmentPoint {
+                time: 0.0,
+                value: 0.0,
+            },
+            start,
+        );
+        data.extend_from_slice(&[first.time, first.value]);
+        let mut push = |ty: i32, points: &[SegmentPoint]| {
+            data.push(ty as f32);
+            for point in points {
+                data.extend_from_slice(&[point.time, point.value]);
+            }
+        };
+        for (idx, seg) in segments.iter().enumerate() {
+            match *seg {
+                Segment::Linear(_, p1) => push(SEG_LINEAR, &[p1]),
+                Segment::Bezier([_, p1, p2, p3]) => push(SEG_BEZIER, &[p1, p2, p3]),
+                Segment::Stepped(p0, time) => {
+                    let value = segments
+                        .get(idx + 1)
+                        .map_or(p0.value, |next| start(next).value);
+                    push(SEG_STEPPED, &[SegmentPoint { time, value }])
+                },
+                Segment::InverseStepped(_, p1) => push(SEG_INV, &[p1]),
+            }
+        }
+        serializer.collect_seq(data)
     }
 }
 
 /// Rust structure representation for Motion3 curve data.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", try_from = "RawCurve")]
 pub struct Curve {
     /// Target.
     pub target: String,
     /// Id.
     pub id: String,
     /// Segments.
-    #[serde(with = "segment_parser")]
+    #[serde(serialize_with = "segment_parser::serialize")]
     pub segments: Vec<Segment>,
     /// Fade-in time. 1.0 [sec] as default.
     #[serde(default = "super::float_1")]
@@ -194,11 +244,66 @@ pub struct Curve {
     pub fade_out_time: f32,
 }
 
+// A curve with its segments still in the flat form of the file, converted into
+// a Curve after deserialization so that errors can name the curve.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawCurve {
+    target: String,
+    id: String,
+    segments: Vec<f32>,
+    #[serde(default = "super::float_1")]
+    fade_in_time: f32,
+    #[serde(default = "super::float_1")]
+    fade_out_time: f32,
+}
+
+/// An error in the segments of a motion curve.
+#[derive(Debug)]
+pub struct CurveError {
+    /// The id of the curve.
+    pub id: String,
+    error: segment_parser::SegmentError,
+}
+
+impl CurveError {
+    /// The index of the segment the error occurred in.
+    pub fn segment_index(&self) -> usize {
+        self.error.index
+    }
+}
+
+impl fmt::Display for CurveError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "curve {:?}: {}", self.id, self.error)
+    }
+}
+
+impl std::error::Error for CurveError {}
+
+impl TryFrom<RawCurve> for Curve {
+    type Error = CurveError;
+
+    fn try_from(raw: RawCurve) -> Result<Self, CurveError> {
+        match segment_parser::parse(&raw.segments) {
+            Ok(segments) => Ok(Curve {
+                target: raw.target,
+                id: raw.id,
+                segments,
+                fade_in_time: raw.fade_in_time,
+                fade_out_time: raw.fade_out_time,
+            }),
+            Err(error) => Err(CurveError { id: raw.id, error }),
+        }
+    }
+}
+
 /// Rust structure representation for Motion3.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Motion3 {
     /// Version.
+    #[serde(default = "format::default_version")]
     pub version: u32,
     /// Metadata.
     pub meta: Meta,
@@ -211,18 +316,36 @@ pub struct Motion3 {
 impl Motion3 {
     /// Parses a Motion3 from a .motion3.json reader.
     #[inline]
-    pub fn from_reader<R: std::io::Read>(r: R) -> serde_json::Result<Self> {
-        serde_json::from_reader(r)
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, FormatError> {
+        format::from_reader(r)
+    }
+}
+
+impl JsonFile for Motion3 {
+    const FORMAT: FileFormat = FileFormat::Motion3;
+
+    fn upgrade(json: &mut Value, _: &mut Vec<FormatWarning>) -> bool {
+        // Exports of older editors lack some of the meta fields.
+        match json.get_mut("Meta") {
+            Some(meta) => {
+                let restricted =
+                    format::insert_default(meta, "AreBeziersRestricted", Value::Bool(false));
+                let count = format::insert_default(meta, "UserDataCount", 0.into());
+                let size = format::insert_default(meta, "TotalUserDataSize", 0.into());
+                restricted | count | size
+            },
+            None => false,
+        }
     }
 }
 
 impl FromStr for Motion3 {
-    type Err = serde_json::Error;
+    type Err = FormatError;
 
     /// Parses a Motion3 from a .motion3.json string.
     #[inline]
-    fn from_str(s: &str) -> serde_json::Result<Self> {
-        serde_json::from_str(s)
+    fn from_str(s: &str) -> Result<Self, FormatError> {
+        format::parse(s.as_bytes()).map(|parsed| parsed.value)
     }
 }
 
@@ -235,8 +358,10 @@ pub struct MotionUserData {
 
 #[test]
 fn json_samples_motion3() {
-    use std::iter::FromIterator;
-    let path = std::path::PathBuf::from_iter(&[env!("CUBISM_CORE"), "Samples/Res"]);
+    let path = match super::samples_dir() {
+        Some(path) => path,
+        None => return,
+    };
     for model in &["Haru", "Hiyori", "Mark", "Natori"] {
         let motion_path = path.join([model, "/motions/"].concat());
         let motions = std::fs::read_dir(motion_path).unwrap();
@@ -256,3 +381,118 @@ fn json_samples_motion3() {
         }
     }
 }
+
+#[test]
+fn json_motion3_malformed_segments() {
+    fn motion3(segments: &str) -> String {
+        format!(
+            r#"{{
+                "Version": 3,
+                "Meta": {{
+                    "Duration": 1.0, "Fps": 30.0, "Loop": true, "AreBeziersRestricted": true,
+                    "CurveCount": 1, "TotalSegmentCount": 1, "TotalPointCount": 2,
+                    "UserDataCount": 0, "TotalUserDataSize": 0
+                }},
+                "Curves": [{{ "Target": "Parameter", "Id": "ParamAngleX", "Segments": {} }}]
+            }}"#,
+            segments
+        )
+    }
+    assert!(Motion3::from_str(&motion3("[0, 0, 0, 1, 1]")).is_ok());
+    for (segments, message) in &[
+        ("[]", "missing the starting point"),
+        ("[0]", "missing the starting point"),
+        ("[0, 0, 0, 1]", "segment 0 is truncated"),
+        ("[0, 0, 0, 1, 1, 1, 1, 1, 1, 1]", "segment 1 is truncated"),
+        ("[0, 0, 4, 1, 1]", "segment 0 has unknown type 4"),
+        ("[0, 0, 0.5, 1, 1]", "segment 0 has unknown type 0.5"),
+    ] {
+        let err = Motion3::from_str(&motion3(segments)).unwrap_err();
+        let expected = format!("curve \"ParamAngleX\": {}", message);
+        assert!(
+            err.to_string().starts_with(&expected),
+            "{} does not start with {}",
+            err,
+            expected
+        );
+    }
+}
+
+#[cfg(test)]
+use proptest::strategy::Strategy;
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn json_motion3_segments_roundtrip(
+        start in proptest::array::uniform2(-10.0f32..10.0),
+        segments in proptest::collection::vec(
+            (0..4i32, proptest::array::uniform6(-10.0f32..10.0)),
+            0..8,
+        ),
+    ) {
+        let mut data = start.to_vec();
+        for (ty, values) in segments {
+            data.push(ty as f32);
+            let points = if ty == 1 { 3 } else { 1 };
+            data.extend_from_slice(&values[..points * 2]);
+        }
+        let parsed = segment_parser::parse(&data).unwrap();
+        let serialized = segment_parser::serialize(&parsed, serde_json::value::Serializer).unwrap();
+        let flat: Vec<f32> = serde_json::from_value(serialized).unwrap();
+        proptest::prop_assert_eq!(flat.len(), data.len());
+        proptest::prop_assert_eq!(segment_parser::parse(&flat).unwrap(), parsed);
+    }
+
+    #[test]
+    fn json_motion3_random_segments(
+        segments in proptest::collection::vec(
+            proptest::prop_oneof![(0..5i32).prop_map(|ty| ty as f32), -2.0f32..2.0],
+            0..24,
+        ),
+    ) {
+        let data = super::MINIMAL_DOCUMENTS[1].replace(
+            "[0, 0, 1, 0.2, 0.5, 0.4, 0.5, 0.6, 1, 0, 1, 1]",
+            &serde_json::to_string(&segments).unwrap(),
+        );
+        let parsed = format::parse::<Motion3>(data.as_bytes());
+        match segment_parser::parse(&segments) {
+            Ok(expected) => {
+                let motion3 = parsed.unwrap().value;
+                proptest::prop_assert_eq!(&motion3.curves[0].segments, &expected);
+                // the segments survive a round-trip through the file
+                let data = serde_json::to_vec(&motion3).unwrap();
+                let reparsed = format::parse::<Motion3>(&data).unwrap().value;
+                proptest::prop_assert_eq!(&reparsed.curves[0].segments, &expected);
+            },
+            Err(e) => {
+                // walk the segments independently to find the offending one
+                let mut rest = segments.get(2..).unwrap_or(&[]);
+                let mut index = 0;
+                let missing_start = segments.len() < 2;
+                while let Some((&ty, values)) = rest.split_first() {
+                    let len = if ty == 1.0 {
+                        6
+                    } else if [0.0, 2.0, 3.0].contains(&ty) {
+                        2
+                    } else {
+                        break;
+                    };
+                    if values.len() < len {
+                        break;
+                    }
+                    rest = &values[len..];
+                    index += 1;
+                }
+                proptest::prop_assert!(!rest.is_empty() || missing_start);
+                proptest::prop_assert_eq!(e.index, index);
+                proptest::prop_assert_eq!(
+                    matches!(e.kind, segment_parser::SegmentErrorKind::MissingStart),
+                    missing_start
+                );
+                let message = parsed.unwrap_err().to_string();
+                proptest::prop_assert!(message.contains(&e.to_string()), "{}", message);
+            },
+        }
+    }
+}
@@ -1,5 +1,8 @@
 /// Parses .physics3.json.
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{format, FileFormat, FormatError, FormatWarning, JsonFile};
 
 use std::str::FromStr;
 
@@ -7,134 +10,161 @@ use std::str::FromStr;
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Physics3 {
-    version: usize,
-    meta: Physics3Meta,
-    physics_settings: Vec<PhysicsSetting>,
+    #[serde(default = "format::default_version")]
+    pub version: usize,
+    pub meta: Physics3Meta,
+    pub physics_settings: Vec<PhysicsSetting>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsSetting {
-    id: String,
+    pub id: String,
     #[serde(default)]
-    input: Vec<PhysicsInput>,
+    pub input: Vec<PhysicsInput>,
     #[serde(default)]
-    output: Vec<PhysicsOutput>,
+    pub output: Vec<PhysicsOutput>,
     #[serde(default)]
-    vertices: Vec<PhysicsVertex>,
-    normalization: Option<PhysicsNormalization>,
+    pub vertices: Vec<PhysicsVertex>,
+    pub normalization: Option<PhysicsNormalization>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsInput {
-    source: PhysicsTarget,
-    weight: f32,
+    pub source: PhysicsTarget,
+    pub weight: f32,
     #[serde(rename = "Type")]
-    ty: String,
-    reflect: bool,
+    pub ty: String,
+    pub reflect: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsOutput {
-    destination: PhysicsTarget,
-    vertex_index: usize,
-    scale: f32,
-    weight: f32,
+    pub destination: PhysicsTarget,
+    pub vertex_index: usize,
+    pub scale: f32,
+    pub weight: f32,
     #[serde(rename = "Type")]
-    ty: String,
-    reflect: bool,
+    pub ty: String,
+    pub reflect: bool,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsVertex {
-    position: Vec2D,
-    mobility: f32,
-    delay: f32,
-    acceleration: f32,
-    radius: f32,
+    pub position: Vec2D,
+    pub mobility: f32,
+    pub delay: f32,
+    pub acceleration: f32,
+    pub radius: f32,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsNormalization {
-    position: PhysicsNormalizationParameter,
-    angle: PhysicsNormalizationParameter,
+    pub position: PhysicsNormalizationParameter,
+    pub angle: PhysicsNormalizationParameter,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsNormalizationParameter {
-    minimum: f32,
-    maximum: f32,
-    default: f32,
+    pub minimum: f32,
+    pub maximum: f32,
+    pub default: f32,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsTarget {
-    target: String,
-    id: String,
+    pub target: String,
+    pub id: String,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Physics3Meta {
-    total_input_count: usize,
-    total_output_count: usize,
-    vertex_count: usize,
-    physics_setting_count: usize,
-    effective_forces: EffectiveForces,
-    physics_dictionary: Vec<PhysicsIdName>,
+    pub total_input_count: usize,
+    pub total_output_count: usize,
+    pub vertex_count: usize,
+    pub physics_setting_count: usize,
+    pub effective_forces: EffectiveForces,
+    pub physics_dictionary: Vec<PhysicsIdName>,
+    /// The rate the simulation runs at, added by newer exports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f32>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PhysicsIdName {
-    id: String,
-    name: String,
+    pub id: String,
+    pub name: String,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct EffectiveForces {
     #[serde(default)]
-    gravity: Vec2D,
+    pub gravity: Vec2D,
     #[serde(default)]
-    wind: Vec2D,
+    pub wind: Vec2D,
 }
 
 #[derive(Clone, Copy, Default, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Vec2D {
-    x: f32,
-    y: f32,
+    pub x: f32,
+    pub y: f32,
 }
 
 impl Physics3 {
     /// Parses a Physics3 from a .physics3.json reader.
     #[inline]
-    pub fn from_reader<R: std::io::Read>(r: R) -> serde_json::Result<Self> {
-        serde_json::from_reader(r)
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, FormatError> {
+        format::from_reader(r)
+    }
+}
+
+impl JsonFile for Physics3 {
+    const FORMAT: FileFormat = FileFormat::Physics3;
+
+    fn upgrade(json: &mut Value, _: &mut Vec<FormatWarning>) -> bool {
+        // Exports of older editors lack the effective forces and the dictionary.
+        match json.get_mut("Meta") {
+            Some(meta) => {
+                let forces = format::insert_default(
+                    meta,
+                    "EffectiveForces",
+                    Value::Object(Default::default()),
+                );
+                let dictionary =
+                    format::insert_default(meta, "PhysicsDictionary", Value::Array(Vec::new()));
+                forces | dictionary
+            },
+            None => false,
+        }
     }
 }
 
 impl FromStr for Physics3 {
-    type Err = serde_json::Error;
+    type Err = FormatError;
 
     /// Parses a Physics3 from a .physics3.json string.
     #[inline]
-    fn from_str(s: &str) -> serde_json::Result<Self> {
-        serde_json::from_str(s)
+    fn from_str(s: &str) -> Result<Self, FormatError> {
+        format::parse(s.as_bytes()).map(|parsed| parsed.value)
     }
 }
 
 #[test]
 fn json_samples_physics3() {
-    use std::iter::FromIterator;
-    let path = std::path::PathBuf::from_iter(&[env!("CUBISM_CORE"), "Samples/Res"]);
+    let path = match super::samples_dir() {
+        Some(path) => path,
+        None => return,
+    };
     for model in &["Rice", "Hiyori", "Mark", "Natori"] {
         let path = path.join([model, "/", model, ".physics3.json"].concat());
 
@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{format, FileFormat, FormatError, FormatWarning, JsonFile};
 
 use std::str::FromStr;
 
@@ -15,8 +18,8 @@ pub struct Pose3 {
 impl Pose3 {
     /// Parses a Pose3 from a .pose3.json reader.
     #[inline]
-    pub fn from_reader<R: std::io::Read>(r: R) -> serde_json::Result<Self> {
-        serde_json::from_reader(r)
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, FormatError> {
+        format::from_reader(r)
     }
 
     fn fade_in_time_default() -> f32 {
@@ -24,13 +27,28 @@ impl Pose3 {
     }
 }
 
+impl JsonFile for Pose3 {
+    const FORMAT: FileFormat = FileFormat::Pose3;
+
+    fn upgrade(json: &mut Value, _: &mut Vec<FormatWarning>) -> bool {
+        // Items without links may omit the `Link` field.
+        let mut changed = false;
+        if let Some(groups) = json.get_mut("Groups").and_then(Value::as_array_mut) {
+            for item in groups.iter_mut().filter_map(Value::as_array_mut).flatten() {
+                changed |= format::insert_default(item, "Link", Value::Array(Vec::new()));
+            }
+        }
+        changed
+    }
+}
+
 impl FromStr for Pose3 {
-    type Err = serde_json::Error;
+    type Err = FormatError;
 
     /// Parses a Pose3 from a .pose3.json string.
     #[inline]
-    fn from_str(s: &str) -> serde_json::Result<Self> {
-        serde_json::from_str(s)
+    fn from_str(s: &str) -> Result<Self, FormatError> {
+        format::parse(s.as_bytes()).map(|parsed| parsed.value)
     }
 }
 
@@ -45,8 +63,10 @@ type PoseGroup = Vec<PoseItem>;
 
 #[test]
 fn json_samples_pose3() {
-    use std::iter::FromIterator;
-    let path = std::path::PathBuf::from_iter(&[env!("CUBISM_CORE"), "Samples/Res"]);
+    let path = match super::samples_dir() {
+        Some(path) => path,
+        None => return,
+    };
     for model in &["Haru", "Hiyori", "Natori"] {
         let pose_path = path.join([model, "/", model, ".pose3.json"].concat());
         Pose3::from_str(
@@ -1,10 +1,14 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{format, FileFormat, FormatError, FormatWarning, JsonFile};
 
 use std::str::FromStr;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct UserData3 {
+    #[serde(default = "format::default_version")]
     pub version: usize,
     pub meta: Meta,
     pub user_data: Vec<UserData>,
@@ -13,18 +17,26 @@ pub struct UserData3 {
 impl UserData3 {
     /// Parses a UserData3 from a .userdata3.json reader.
     #[inline]
-    pub fn from_reader<R: std::io::Read>(r: R) -> serde_json::Result<Self> {
-        serde_json::from_reader(r)
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, FormatError> {
+        format::from_reader(r)
+    }
+}
+
+impl JsonFile for UserData3 {
+    const FORMAT: FileFormat = FileFormat::UserData3;
+
+    fn upgrade(json: &mut Value, warnings: &mut Vec<FormatWarning>) -> bool {
+        format::retain_targets(json, "UserData", &["ArtMesh"], warnings)
     }
 }
 
 impl FromStr for UserData3 {
-    type Err = serde_json::Error;
+    type Err = FormatError;
 
     /// Parses a UserData3 from a .userdata3.json string.
     #[inline]
-    fn from_str(s: &str) -> serde_json::Result<Self> {
-        serde_json::from_str(s)
+    fn from_str(s: &str) -> Result<Self, FormatError> {
+        format::parse(s.as_bytes()).map(|parsed| parsed.value)
     }
 }
 
@@ -51,8 +63,10 @@ pub enum UserDataTarget {
 
 #[test]
 fn json_samples_userdata3() {
-    use std::iter::FromIterator;
-    let path = std::path::PathBuf::from_iter(&[env!("CUBISM_CORE"), "Samples/Res"]);
+    let path = match super::samples_dir() {
+        Some(path) => path,
+        None => return,
+    };
     for model in &["Haru", "Hiyori", "Mark"] {
         let userdata_path = path.join([model, "/", model, ".userdata3.json"].concat());
         UserData3::from_str(
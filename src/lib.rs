@@ -4,11 +4,16 @@
 //! A framework for Live2D's cubism sdk
 pub use cubism_core as core;
 
+pub mod accumulator;
+pub mod asset;
 pub mod controller;
 pub mod error;
 pub mod expression;
 pub mod id;
 pub mod json;
+pub mod matrix;
 pub mod model;
 pub mod motion;
+pub mod physics;
 pub(crate) mod util;
+pub mod validation;
@@ -0,0 +1,584 @@
+//! Model and view matrices used to place a model on the screen.
+//!
+//! The coordinate spaces involved are:
+//! - screen space, the pixel coordinates of the window with the origin at the
+//!   top left and the y axis pointing down,
+//! - view space, where the shorter side of the window spans `[-1.0, 1.0]` and
+//!   the y axis points up,
+//! - model space, the canvas units the drawables' vertex positions are in.
+use std::ops;
+
+use crate::json::model::Layout;
+
+/// A column-major 4x4 matrix.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix44 {
+    tr: [f32; 16],
+}
+
+impl Matrix44 {
+    /// The identity matrix.
+    pub const IDENTITY: Matrix44 = Matrix44 {
+        tr: [
+            1.0, 0.0, 0.0, 0.0, //
+            0.0, 1.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0, //
+            0.0, 0.0, 0.0, 1.0,
+        ],
+    };
+
+    /// Creates a matrix from a column-major array.
+    #[inline]
+    pub fn from_array(tr: [f32; 16]) -> Self {
+        Matrix44 { tr }
+    }
+
+    /// Creates a matrix that scales by the given factors.
+    pub fn from_scale(x: f32, y: f32) -> Self {
+        let mut this = Self::IDENTITY;
+        this.set_scale(x, y);
+        this
+    }
+
+    /// Creates a matrix that translates by the given offsets.
+    pub fn from_translation(x: f32, y: f32) -> Self {
+        let mut this = Self::IDENTITY;
+        this.set_translation(x, y);
+        this
+    }
+
+    /// Returns the column-major array of this matrix.
+    #[inline]
+    pub fn as_array(&self) -> &[f32; 16] {
+        &self.tr
+    }
+
+    /// Returns the scaling factors of this matrix.
+    #[inline]
+    pub fn scale(&self) -> [f32; 2] {
+        [self.tr[0], self.tr[5]]
+    }
+
+    /// Returns the translation of this matrix.
+    #[inline]
+    pub fn translation(&self) -> [f32; 2] {
+        [self.tr[12], self.tr[13]]
+    }
+
+    /// Sets the scaling factors of this matrix.
+    #[inline]
+    pub fn set_scale(&mut self, x: f32, y: f32) {
+        self.tr[0] = x;
+        self.tr[5] = y;
+    }
+
+    /// Sets the translation of this matrix.
+    #[inline]
+    pub fn set_translation(&mut self, x: f32, y: f32) {
+        self.tr[12] = x;
+        self.tr[13] = y;
+    }
+
+    /// Sets the x translation of this matrix.
+    #[inline]
+    pub fn set_translation_x(&mut self, x: f32) {
+        self.tr[12] = x;
+    }
+
+    /// Sets the y translation of this matrix.
+    #[inline]
+    pub fn set_translation_y(&mut self, y: f32) {
+        self.tr[13] = y;
+    }
+
+    /// Translates the result of this matrix by the given offsets.
+    pub fn translate_relative(&mut self, x: f32, y: f32) {
+        *self = Self::from_translation(x, y) * *self;
+    }
+
+    /// Scales the result of this matrix by the given factors.
+    pub fn scale_relative(&mut self, x: f32, y: f32) {
+        *self = Self::from_scale(x, y) * *self;
+    }
+
+    /// Transforms the x coordinate, ignoring rotation and shearing.
+    #[inline]
+    pub fn transform_x(&self, x: f32) -> f32 {
+        self.tr[0] * x + self.tr[12]
+    }
+
+    /// Transforms the y coordinate, ignoring rotation and shearing.
+    #[inline]
+    pub fn transform_y(&self, y: f32) -> f32 {
+        self.tr[5] * y + self.tr[13]
+    }
+
+    /// Transforms the point, ignoring rotation and shearing.
+    #[inline]
+    pub fn transform_point(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        [self.transform_x(x), self.transform_y(y)]
+    }
+
+    /// Applies the inverse transformation to the x coordinate, ignoring
+    /// rotation and shearing.
+    #[inline]
+    pub fn invert_transform_x(&self, x: f32) -> f32 {
+        (x - self.tr[12]) / self.tr[0]
+    }
+
+    /// Applies the inverse transformation to the y coordinate, ignoring
+    /// rotation and shearing.
+    #[inline]
+    pub fn invert_transform_y(&self, y: f32) -> f32 {
+        (y - self.tr[13]) / self.tr[5]
+    }
+
+    /// Applies the inverse transformation to the point, ignoring rotation and
+    /// shearing.
+    #[inline]
+    pub fn invert_transform_point(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        [self.invert_transform_x(x), self.invert_transform_y(y)]
+    }
+}
+
+impl Default for Matrix44 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl ops::Mul for Matrix44 {
+    type Output = Matrix44;
+
+    /// Multiplies the matrices, the resulting matrix applies `rhs` first and
+    /// `self` second.
+    fn mul(self, rhs: Matrix44) -> Matrix44 {
+        let mut tr = [0.0; 16];
+        for col in 0..4 {
+            for row in 0..4 {
+                tr[col * 4 + row] = (0..4)
+                    .map(|k| self.tr[k * 4 + row] * rhs.tr[col * 4 + k])
+                    .sum();
+            }
+        }
+        Matrix44 { tr }
+    }
+}
+
+impl ops::MulAssign for Matrix44 {
+    fn mul_assign(&mut self, rhs: Matrix44) {
+        *self = *self * rhs;
+    }
+}
+
+/// A matrix transforming model space into view space.
+///
+/// The model is initially scaled so that its canvas is 2.0 units high,
+/// spanning the full height of a landscape view.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ModelMatrix {
+    matrix: Matrix44,
+    width: f32,
+    height: f32,
+}
+
+impl ModelMatrix {
+    /// Creates a new model matrix for a canvas of the given size in canvas
+    /// units.
+    pub fn new(width: f32, height: f32) -> Self {
+        let mut this = ModelMatrix {
+            matrix: Matrix44::IDENTITY,
+            width,
+            height,
+        };
+        this.set_height(2.0);
+        this
+    }
+
+    /// Creates a new model matrix from the canvas info of a model as returned
+    /// by [`Model::canvas_info`].
+    ///
+    /// [`Model::canvas_info`]: ../core/struct.Model.html#method.canvas_info
+    pub fn from_canvas_info(([width, height], _, ppu): ([f32; 2], [f32; 2], f32)) -> Self {
+        Self::new(width / ppu, height / ppu)
+    }
+
+    /// Applies the layout of a model3.json to this matrix.
+    ///
+    /// The size is applied first and the position second, so positions that
+    /// are relative to the size of the model are placed correctly.
+    pub fn setup_from_layout(&mut self, layout: &Layout) {
+        if let Some(width) = layout.width {
+            self.set_width(width);
+        }
+        if let Some(height) = layout.height {
+            self.set_height(height);
+        }
+        if let Some(x) = layout.x {
+            self.set_x(x);
+        }
+        if let Some(y) = layout.y {
+            self.set_y(y);
+        }
+        if let Some(center_x) = layout.center_x {
+            self.set_center_x(center_x);
+        }
+        if let Some(center_y) = layout.center_y {
+            self.set_center_y(center_y);
+        }
+        if let Some(top) = layout.top {
+            self.set_top(top);
+        }
+        if let Some(bottom) = layout.bottom {
+            self.set_bottom(bottom);
+        }
+        if let Some(left) = layout.left {
+            self.set_left(left);
+        }
+        if let Some(right) = layout.right {
+            self.set_right(right);
+        }
+    }
+
+    /// The width of the canvas in canvas units.
+    #[inline]
+    pub fn canvas_width(&self) -> f32 {
+        self.width
+    }
+
+    /// The height of the canvas in canvas units.
+    #[inline]
+    pub fn canvas_height(&self) -> f32 {
+        self.height
+    }
+
+    /// Scales the model uniformly so that it has the given width in view
+    /// units.
+    pub fn set_width(&mut self, width: f32) {
+        let scale = width / self.width;
+        self.matrix.set_scale(scale, scale);
+    }
+
+    /// Scales the model uniformly so that it has the given height in view
+    /// units.
+    pub fn set_height(&mut self, height: f32) {
+        let scale = height / self.height;
+        self.matrix.set_scale(scale, scale);
+    }
+
+    /// Sets the position of the model's origin.
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.matrix.set_translation(x, y);
+    }
+
+    /// Places the model so that its center lies at the given position.
+    pub fn set_center_position(&mut self, x: f32, y: f32) {
+        self.set_center_x(x);
+        self.set_center_y(y);
+    }
+
+    /// Sets the x position of the model's origin.
+    pub fn set_x(&mut self, x: f32) {
+        self.matrix.set_translation_x(x);
+    }
+
+    /// Sets the y position of the model's origin.
+    pub fn set_y(&mut self, y: f32) {
+        self.matrix.set_translation_y(y);
+    }
+
+    /// Places the model so that its top edge lies at `y`.
+    pub fn set_top(&mut self, y: f32) {
+        self.set_y(y);
+    }
+
+    /// Places the model so that its bottom edge lies at `y`.
+    pub fn set_bottom(&mut self, y: f32) {
+        let h = self.height * self.matrix.scale()[1];
+        self.matrix.set_translation_y(y - h);
+    }
+
+    /// Places the model so that its left edge lies at `x`.
+    pub fn set_left(&mut self, x: f32) {
+        self.set_x(x);
+    }
+
+    /// Places the model so that its right edge lies at `x`.
+    pub fn set_right(&mut self, x: f32) {
+        let w = self.width * self.matrix.scale()[0];
+        self.matrix.set_translation_x(x - w);
+    }
+
+    /// Places the model so that its horizontal center lies at `x`.
+    pub fn set_center_x(&mut self, x: f32) {
+        let w = self.width * self.matrix.scale()[0];
+        self.matrix.set_translation_x(x - w / 2.0);
+    }
+
+    /// Places the model so that its vertical center lies at `y`.
+    pub fn set_center_y(&mut self, y: f32) {
+        let h = self.height * self.matrix.scale()[1];
+        self.matrix.set_translation_y(y - h / 2.0);
+    }
+}
+
+impl ops::Deref for ModelMatrix {
+    type Target = Matrix44;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.matrix
+    }
+}
+
+impl ops::DerefMut for ModelMatrix {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.matrix
+    }
+}
+
+/// A rectangle in view space.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ViewRect {
+    /// The left edge.
+    pub left: f32,
+    /// The right edge.
+    pub right: f32,
+    /// The bottom edge.
+    pub bottom: f32,
+    /// The top edge.
+    pub top: f32,
+}
+
+/// A matrix transforming view space for zooming and panning, with limits
+/// on how far the view may be moved and scaled.
+///
+/// It also keeps track of the screen size to convert between screen, view and
+/// model space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ViewMatrix {
+    matrix: Matrix44,
+    screen_to_view: Matrix44,
+    projection: Matrix44,
+    screen_rect: ViewRect,
+    max_screen_rect: ViewRect,
+    min_scale: f32,
+    max_scale: f32,
+}
+
+impl ViewMatrix {
+    /// Creates a new view matrix for a screen of the given size in pixels.
+    ///
+    /// The view may be panned up to twice the visible area and zoomed between
+    /// 0.8 and 2.0 by default.
+    pub fn new(width: f32, height: f32) -> Self {
+        let mut this = ViewMatrix {
+            matrix: Matrix44::IDENTITY,
+            screen_to_view: Matrix44::IDENTITY,
+            projection: Matrix44::IDENTITY,
+            screen_rect: ViewRect::default(),
+            max_screen_rect: ViewRect::default(),
+            min_scale: 0.8,
+            max_scale: 2.0,
+        };
+        this.resize(width, height);
+        this
+    }
+
+    /// Updates the screen size in pixels, resetting the screen rectangles to
+    /// match the new aspect ratio. The current zoom and pan are retained.
+    pub fn resize(&mut self, width: f32, height: f32) {
+        let unit = width.min(height) / 2.0;
+        let (half_w, half_h) = (width / 2.0 / unit, height / 2.0 / unit);
+        self.screen_rect = ViewRect {
+            left: -half_w,
+            right: half_w,
+            bottom: -half_h,
+            top: half_h,
+        };
+        self.max_screen_rect = ViewRect {
+            left: -2.0 * half_w,
+            right: 2.0 * half_w,
+            bottom: -2.0 * half_h,
+            top: 2.0 * half_h,
+        };
+        self.screen_to_view = Matrix44::from_translation(-width / 2.0, -height / 2.0);
+        self.screen_to_view.scale_relative(1.0 / unit, -1.0 / unit);
+        self.projection = Matrix44::from_scale(1.0 / half_w, 1.0 / half_h);
+    }
+
+    /// The visible area in view space.
+    #[inline]
+    pub fn screen_rect(&self) -> ViewRect {
+        self.screen_rect
+    }
+
+    /// Sets the visible area in view space.
+    #[inline]
+    pub fn set_screen_rect(&mut self, rect: ViewRect) {
+        self.screen_rect = rect;
+    }
+
+    /// The area in view space the visible area may be panned around in.
+    #[inline]
+    pub fn max_screen_rect(&self) -> ViewRect {
+        self.max_screen_rect
+    }
+
+    /// Sets the area in view space the visible area may be panned around in.
+    #[inline]
+    pub fn set_max_screen_rect(&mut self, rect: ViewRect) {
+        self.max_screen_rect = rect;
+    }
+
+    /// The minimum zoom factor.
+    #[inline]
+    pub fn min_scale(&self) -> f32 {
+        self.min_scale
+    }
+
+    /// Sets the minimum zoom factor.
+    #[inline]
+    pub fn set_min_scale(&mut self, min_scale: f32) {
+        self.min_scale = min_scale;
+    }
+
+    /// The maximum zoom factor.
+    #[inline]
+    pub fn max_scale(&self) -> f32 {
+        self.max_scale
+    }
+
+    /// Sets the maximum zoom factor.
+    #[inline]
+    pub fn set_max_scale(&mut self, max_scale: f32) {
+        self.max_scale = max_scale;
+    }
+
+    /// Returns true if the view is zoomed in as far as allowed.
+    #[inline]
+    pub fn is_max_scale(&self) -> bool {
+        self.matrix.scale()[0] >= self.max_scale
+    }
+
+    /// Returns true if the view is zoomed out as far as allowed.
+    #[inline]
+    pub fn is_min_scale(&self) -> bool {
+        self.matrix.scale()[0] <= self.min_scale
+    }
+
+    /// Pans the view by the given offset in view units, without moving the
+    /// visible area outside of the maximum screen rectangle.
+    pub fn adjust_translate(&mut self, mut x: f32, mut y: f32) {
+        let [scale_x, scale_y] = self.matrix.scale();
+        let [tx, ty] = self.matrix.translation();
+        let (screen, max) = (self.screen_rect, self.max_screen_rect);
+        if scale_x * max.left + tx + x > screen.left {
+            x = screen.left - scale_x * max.left - tx;
+        }
+        if scale_x * max.right + tx + x < screen.right {
+            x = screen.right - scale_x * max.right - tx;
+        }
+        if scale_y * max.top + ty + y < screen.top {
+            y = screen.top - scale_y * max.top - ty;
+        }
+        if scale_y * max.bottom + ty + y > screen.bottom {
+            y = screen.bottom - scale_y * max.bottom - ty;
+        }
+        self.matrix.translate_relative(x, y);
+    }
+
+    /// Zooms the view by the given factor around the given point in view
+    /// units, clamping the resulting zoom between the minimum and maximum
+    /// scale.
+    pub fn adjust_scale(&mut self, cx: f32, cy: f32, mut scale: f32) {
+        let current = self.matrix.scale()[0];
+        let target = scale * current;
+        if current > 0.0 {
+            if target < self.min_scale {
+                scale = self.min_scale / current;
+            } else if target > self.max_scale {
+                scale = self.max_scale / current;
+            }
+        }
+        self.matrix.translate_relative(-cx, -cy);
+        self.matrix.scale_relative(scale, scale);
+        self.matrix.translate_relative(cx, cy);
+    }
+
+    /// Converts a point in screen pixels into view space.
+    pub fn screen_to_view(&self, point: [f32; 2]) -> [f32; 2] {
+        self.matrix
+            .invert_transform_point(self.screen_to_view.transform_point(point))
+    }
+
+    /// Converts a point in view space into model space.
+    pub fn view_to_model(&self, model: &ModelMatrix, point: [f32; 2]) -> [f32; 2] {
+        model.invert_transform_point(point)
+    }
+
+    /// Converts a point in screen pixels into model space.
+    ///
+    /// The result can be passed to [`UserModel::hit_test`] as
+    /// [`HitPoint::Units`] to test against what is being rendered.
+    ///
+    /// [`UserModel::hit_test`]: ../model/struct.UserModel.html#method.hit_test
+    /// [`HitPoint::Units`]: ../model/enum.HitPoint.html#variant.Units
+    pub fn screen_to_model(&self, model: &ModelMatrix, point: [f32; 2]) -> [f32; 2] {
+        self.view_to_model(model, self.screen_to_view(point))
+    }
+
+    /// Converts a point in model space into screen pixels.
+    pub fn model_to_screen(&self, model: &ModelMatrix, point: [f32; 2]) -> [f32; 2] {
+        self.screen_to_view
+            .invert_transform_point(self.matrix.transform_point(model.transform_point(point)))
+    }
+
+    /// Returns the matrix transforming model space into clip space, to be used
+    /// by renderers.
+    pub fn mvp(&self, model: &ModelMatrix) -> Matrix44 {
+        self.projection * self.matrix * **model
+    }
+}
+
+impl ops::Deref for ViewMatrix {
+    type Target = Matrix44;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.matrix
+    }
+}
+
+impl ops::DerefMut for ViewMatrix {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.matrix
+    }
+}
+
+#[test]
+fn matrix_mul_order() {
+    let m = Matrix44::from_translation(1.0, 2.0) * Matrix44::from_scale(2.0, 3.0);
+    assert_eq!(m.transform_point([1.0, 1.0]), [3.0, 5.0]);
+    assert_eq!(m.invert_transform_point([3.0, 5.0]), [1.0, 1.0]);
+}
+
+#[test]
+fn model_matrix_center() {
+    let mut m = ModelMatrix::new(4.0, 4.0);
+    assert_eq!(m.scale(), [0.5, 0.5]);
+    m.set_center_position(0.0, 0.0);
+    assert_eq!(m.transform_point([2.0, 2.0]), [0.0, 0.0]);
+}
+
+#[test]
+fn view_matrix_screen_roundtrip() {
+    let mut view = ViewMatrix::new(800.0, 400.0);
+    assert_eq!(view.screen_to_view([400.0, 200.0]), [0.0, 0.0]);
+    assert_eq!(view.screen_to_view([800.0, 0.0]), [2.0, 1.0]);
+    view.adjust_scale(0.0, 0.0, 1.5);
+    let model = ModelMatrix::new(2.0, 2.0);
+    let p = view.screen_to_model(&model, [100.0, 300.0]);
+    let back = view.model_to_screen(&model, p);
+    assert!((back[0] - 100.0).abs() < 1e-4 && (back[1] - 300.0).abs() < 1e-4);
+}
@@ -1,81 +1,440 @@
 //! A UserModel that represents a functional parsed model3.json.
-use std::{fmt, fs, io, ops, path::Path};
+use std::{fmt, ops, path::Path};
 
-use cubism_core::Model;
+use cubism_core::{DrawableIndex, Model};
 
-use crate::controller::{Controller, ControllerMap, ExpressionController, EyeBlink};
-use crate::error::CubismResult;
+use indexmap::IndexMap;
+
+use crate::accumulator::Accumulator;
+use crate::asset::{self, AssetSource, FileSystem, Prefixed};
+use crate::controller::{Controller, ControllerMap, ExpressionController, EyeBlink, Pose};
+use crate::error::{CubismError, CubismResult, FileReference, IdKind};
 use crate::expression::Expression;
-use crate::json::model::{GroupTarget, Model3};
+use crate::id::{IdAliases, StandardIds};
+use crate::json::cdi::Cdi3;
+use crate::json::expression::Expression3;
+use crate::json::model::Model3;
+use crate::json::motion::Motion3;
+use crate::json::physics::Physics3;
+use crate::json::pose::Pose3;
+use crate::json::user_data::{UserData, UserData3};
+use crate::matrix::ModelMatrix;
+use crate::motion::Motion;
+use crate::physics::Physics;
+
+/// Options controlling which parts of a model3.json package are loaded by
+/// [`UserModel::from_model3_with_options`].
+///
+/// Everything is loaded by default, ids that don't exist in the moc are
+/// ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Load the expressions into an [`ExpressionController`].
+    pub expressions: bool,
+    /// Create an [`EyeBlink`] controller from the `EyeBlink` group, or the
+    /// standard eye open parameters if there is no such group.
+    pub eye_blink: bool,
+    /// Load the pose into a [`Pose`] controller.
+    pub pose: bool,
+    /// Load the physics into a [`Physics`] controller.
+    pub physics: bool,
+    /// Load the motions.
+    pub motions: bool,
+    /// Load the user data.
+    pub user_data: bool,
+    /// Register the hit areas.
+    pub hit_areas: bool,
+    /// Load the display info.
+    pub display_info: bool,
+    /// Fail with [`CubismError::UnresolvedId`] if an expression, the physics,
+    /// the pose or a motion refers to an id the moc doesn't contain, instead
+    /// of ignoring it.
+    ///
+    /// [`CubismError::UnresolvedId`]: ../error/enum.CubismError.html#variant.UnresolvedId
+    pub strict_ids: bool,
+    /// The aliases tried before the standard ids when resolving them.
+    pub id_aliases: IdAliases,
+    /// Check the consistency of the moc before loading it, see
+    /// [`Model::from_bytes`]. Disabling the check loads mocs with
+    /// [`Model::from_bytes_unchecked`] instead, malformed files can crash the
+    /// native library then, so it should only be disabled for trusted files.
+    ///
+    /// [`Model::from_bytes`]: ../core/struct.Model.html#method.from_bytes
+    /// [`Model::from_bytes_unchecked`]: ../core/struct.Model.html#method.from_bytes_unchecked
+    pub check_moc_consistency: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            expressions: true,
+            eye_blink: true,
+            pose: true,
+            physics: true,
+            motions: true,
+            user_data: true,
+            hit_areas: true,
+            display_info: true,
+            strict_ids: false,
+            id_aliases: IdAliases::default(),
+            check_moc_consistency: true,
+        }
+    }
+}
 
 /// A UserModel that represents a functional parsed model3.json.
 pub struct UserModel {
     model: Model,
     // registered controllers
     controller_map: ControllerMap,
+    // the standard ids resolved against the moc
+    standard_ids: StandardIds,
+    // contributions of the motions and controllers for the current frame
+    accumulator: Accumulator,
     // saved snapshot of the models parameter for reloading
     parameter_snapshot: Box<[f32]>,
+    // named hit areas and the drawable index they test against
+    hit_areas: Vec<(String, DrawableIndex)>,
+    model_matrix: ModelMatrix,
+    // motions grouped by their group name
+    motions: IndexMap<String, Vec<Motion>>,
+    user_data: Vec<UserData>,
+    display_info: Option<Cdi3>,
+}
+
+/// A point used for hit testing.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HitPoint {
+    /// A point in canvas units, the coordinate space of the drawables' vertex
+    /// positions. Screen coordinates can be converted into this space with
+    /// [`ViewMatrix::screen_to_model`].
+    ///
+    /// [`ViewMatrix::screen_to_model`]: ../matrix/struct.ViewMatrix.html#method.screen_to_model
+    Units([f32; 2]),
+    /// A point in canvas pixels, with the origin at the top left of the
+    /// canvas and the y axis pointing down.
+    Pixels([f32; 2]),
 }
 
 impl UserModel {
     /// Creates a new UserModel backed by the given Model.
     pub fn new(model: Model) -> Self {
         let parameter_snapshot = model.parameter_values().into();
+        let model_matrix = ModelMatrix::from_canvas_info(model.canvas_info());
+        let mut controller_map = ControllerMap::new();
+        controller_map.attach(model.moc_arc());
+        let standard_ids = StandardIds::new(model.moc());
         Self {
             model,
-            controller_map: ControllerMap::new(),
+            controller_map,
+            standard_ids,
+            accumulator: Accumulator::new(),
             parameter_snapshot,
+            hit_areas: Vec::new(),
+            model_matrix,
+            motions: IndexMap::new(),
+            user_data: Vec::new(),
+            display_info: None,
         }
     }
 
     /// Creates a UserModel from a path of a model3.json file
     #[inline]
     pub fn from_model3_json<P: AsRef<Path>>(path: P) -> CubismResult<Self> {
+        Self::from_model3_json_with_options(path, &LoadOptions::default())
+    }
+
+    /// Creates a UserModel from a path of a model3.json file, only loading the
+    /// parts enabled in the options.
+    #[inline]
+    pub fn from_model3_json_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+    ) -> CubismResult<Self> {
+        Self::from_source(&FileSystem, path, options)
+    }
+
+    /// Creates a UserModel from a path of a model3.json file in the asset
+    /// source, only loading the parts enabled in the options. The file
+    /// references are resolved relative to the model3.json.
+    pub fn from_source<P: AsRef<Path>>(
+        source: &dyn AssetSource,
+        path: P,
+        options: &LoadOptions,
+    ) -> CubismResult<Self> {
         let path = path.as_ref();
-        let model3 = Model3::from_reader(fs::File::open(path)?)?;
-        Self::from_model3(path.parent().unwrap(), &model3)
+        let model3: Model3 = asset::read_json(source, path)?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::from_model3_source(&Prefixed::new(base, source), &model3, options)
+    }
+
+    /// Creates a UserModel from a zip archive containing a model3.json and the
+    /// files it references, only loading the parts enabled in the options.
+    ///
+    /// If the archive contains multiple model3.json files the one closest to
+    /// the archive root is used, if there are none
+    /// [`CubismError::MissingModel3`] is returned.
+    #[cfg(feature = "zip")]
+    pub fn from_zip<R: std::io::Read + std::io::Seek>(
+        reader: R,
+        options: &LoadOptions,
+    ) -> CubismResult<Self> {
+        let source = crate::asset::ZipSource::new(reader)?;
+        let model3_path = source.find_model3().ok_or(CubismError::MissingModel3)?;
+        Self::from_source(&source, model3_path, options)
     }
 
     /// Creates a UserModel from a Model3 and the parent path of the file it was
     /// loaded from.
+    #[inline]
     pub fn from_model3(base: &Path, model3: &Model3) -> CubismResult<Self> {
-        if let Some(moc_path) = model3.file_references.moc.as_ref() {
-            let model = Model::from_bytes(&fs::read(base.join(moc_path))?)?;
-            let mut this = Self::new(model);
+        Self::from_model3_with_options(base, model3, &LoadOptions::default())
+    }
+
+    /// Creates a UserModel from a Model3 and the parent path of the file it was
+    /// loaded from, only loading the parts enabled in the options.
+    #[inline]
+    pub fn from_model3_with_options(
+        base: &Path,
+        model3: &Model3,
+        options: &LoadOptions,
+    ) -> CubismResult<Self> {
+        Self::from_model3_source(&Prefixed::new(base, FileSystem), model3, options)
+    }
+
+    /// Creates a UserModel from a Model3 whose file references are resolved
+    /// in the asset source, only loading the parts enabled in the options.
+    pub fn from_model3_source(
+        source: &dyn AssetSource,
+        model3: &Model3,
+        options: &LoadOptions,
+    ) -> CubismResult<Self> {
+        let refs = &model3.file_references;
+        let moc_path = refs.moc.as_ref().ok_or(CubismError::MissingMoc)?;
+        let moc =
+            asset::read(source, moc_path).map_err(|e| e.with_reference(FileReference::Moc))?;
+        let model = if options.check_moc_consistency {
+            Model::from_bytes(&moc)
+        } else {
+            // the caller trusts the file
+            unsafe { Model::from_bytes_unchecked(&moc) }
+        };
+        let model = model.map_err(|e| CubismError::LoadMoc {
+            path: source.full_path(moc_path),
+            source: e,
+        })?;
+        let mut this = Self::new(model);
+        this.standard_ids = StandardIds::from_model3(this.model.moc(), model3, &options.id_aliases);
 
+        if let Some(layout) = model3.layout.as_ref() {
+            this.model_matrix.setup_from_layout(layout);
+        }
+
+        if options.expressions {
             let mut expr_con = ExpressionController::new();
-            for res in model3.file_references.expressions.iter().map(|exp| {
-                Expression::from_exp3_json(&this.model, base.join(&exp.file))
-                    .map(|expr| (exp.name.clone(), expr))
-            }) {
-                let (name, expr) = res?;
-                expr_con.register(name, expr);
+            for exp in refs.expressions.iter() {
+                let reference = || FileReference::Expression(exp.name.clone());
+                let exp3: Expression3 = asset::read_json(source, &exp.file)
+                    .map_err(|e| e.with_reference(reference()))?;
+                if options.strict_ids {
+                    let ids = exp3.parameters.iter().map(|param| &*param.id);
+                    check_ids(
+                        this.model.parameter_ids(),
+                        IdKind::Parameter,
+                        ids,
+                        &exp.file,
+                    )
+                    .map_err(|e| e.with_reference(reference()))?;
+                }
+                expr_con.register(exp.name.clone(), Expression::from_exp3(&this.model, exp3));
             }
             this.controller_map.register(expr_con);
+        }
 
-            if let Some(eye_blink) = Self::try_create_eye_blink(&this.model, model3) {
+        if options.hit_areas {
+            for hit_area in model3.hit_areas.iter() {
+                this.register_hit_area(hit_area.name.clone(), &hit_area.id);
+            }
+        }
+
+        if options.eye_blink {
+            if let Some(eye_blink) = EyeBlink::from_standard_ids(&this.standard_ids) {
                 this.controller_map.register(eye_blink);
             }
+        }
 
-            Ok(this)
-        } else {
-            Err(io::Error::new(io::ErrorKind::NotFound, "no moc file has been specified").into())
+        if let (true, Some(physics_path)) = (options.physics, refs.physics.as_ref()) {
+            let phys3: Physics3 = asset::read_json(source, physics_path)
+                .map_err(|e| e.with_reference(FileReference::Physics))?;
+            if options.strict_ids {
+                let ids = phys3.physics_settings.iter().flat_map(|setting| {
+                    let inputs = setting.input.iter().map(|input| &*input.source.id);
+                    let outputs = setting.output.iter().map(|output| &*output.destination.id);
+                    inputs.chain(outputs)
+                });
+                check_ids(
+                    this.model.parameter_ids(),
+                    IdKind::Parameter,
+                    ids,
+                    physics_path,
+                )
+                .map_err(|e| e.with_reference(FileReference::Physics))?;
+            }
+            this.controller_map
+                .register(Physics::from_physics3(&this.model, &phys3));
+        }
+
+        if let (true, Some(pose_path)) = (options.pose, refs.pose.as_ref()) {
+            let pose3: Pose3 = asset::read_json(source, pose_path)
+                .map_err(|e| e.with_reference(FileReference::Pose))?;
+            if options.strict_ids {
+                let ids = pose3.groups.iter().flatten().flat_map(|item| {
+                    std::iter::once(&*item.id).chain(item.link.iter().map(|id| &**id))
+                });
+                check_ids(this.model.part_ids(), IdKind::Part, ids, pose_path)
+                    .map_err(|e| e.with_reference(FileReference::Pose))?;
+            }
+            let mut pose = Pose::from_pose3(&this.model, &pose3);
+            pose.reset(&mut this.model);
+            this.save_parameters();
+            this.controller_map.register(pose);
+        }
+
+        if options.motions {
+            for (group, motions) in refs.motions.iter() {
+                let motions = motions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, motion)| {
+                        let reference = || FileReference::Motion {
+                            group: group.clone(),
+                            index,
+                        };
+                        let motion3: Motion3 = asset::read_json(source, &motion.file)
+                            .map_err(|e| e.with_reference(reference()))?;
+                        if options.strict_ids {
+                            this.check_motion_ids(&motion3, &source.full_path(&motion.file))
+                                .map_err(|e| e.with_reference(reference()))?;
+                        }
+                        let mut loaded = Motion::new(motion3);
+                        // the model3.json overrides the motion's own fade times
+                        if let Some(fade_in_time) = motion.fade_in_time {
+                            loaded.set_fade_in_time(fade_in_time);
+                        }
+                        if let Some(fade_out_time) = motion.fade_out_time {
+                            loaded.set_fade_out_time(fade_out_time);
+                        }
+                        Ok(loaded)
+                    })
+                    .collect::<CubismResult<_>>()?;
+                this.motions.insert(group.clone(), motions);
+            }
+        }
+
+        if let (true, Some(user_data_path)) = (options.user_data, refs.user_data.as_ref()) {
+            let user_data3: UserData3 = asset::read_json(source, user_data_path)
+                .map_err(|e| e.with_reference(FileReference::UserData))?;
+            this.user_data = user_data3.user_data;
+        }
+
+        if let (true, Some(cdi_path)) = (options.display_info, refs.display_info.as_ref()) {
+            this.display_info = Some(
+                asset::read_json(source, cdi_path)
+                    .map_err(|e| e.with_reference(FileReference::DisplayInfo))?,
+            );
+        }
+
+        Ok(this)
+    }
+
+    fn check_motion_ids(&self, motion3: &Motion3, path: &Path) -> CubismResult<()> {
+        let curve_ids = |target| {
+            motion3
+                .curves
+                .iter()
+                .filter(move |curve| curve.target == target)
+                .map(|curve| &*curve.id)
+        };
+        check_ids(
+            self.model.parameter_ids(),
+            IdKind::Parameter,
+            curve_ids("Parameter"),
+            path,
+        )?;
+        check_ids(
+            self.model.part_ids(),
+            IdKind::Part,
+            curve_ids("PartOpacity"),
+            path,
+        )
+    }
+
+    /// Registers a hit area under the given name that tests against the
+    /// drawable with the given id. Returns false if the model has no such
+    /// drawable.
+    pub fn register_hit_area(&mut self, name: impl Into<String>, drawable_id: &str) -> bool {
+        match self.model.drawable_index(drawable_id) {
+            Some(idx) => {
+                self.hit_areas.push((name.into(), idx));
+                true
+            },
+            None => false,
         }
     }
 
-    fn try_create_eye_blink(model: &Model, model3: &Model3) -> Option<EyeBlink> {
-        let eye_blink_ids: Box<[usize]> = model3
-            .groups
+    /// The names of all registered hit areas.
+    pub fn hit_area_names(&self) -> impl Iterator<Item = &str> {
+        self.hit_areas.iter().map(|(name, _)| &**name)
+    }
+
+    /// Tests whether the point lies on the hit area with the given name.
+    /// Returns false if no hit area with that name exists.
+    ///
+    /// The test is done against the triangles of the drawable's deformed
+    /// vertex positions, so the model has to be updated beforehand.
+    pub fn hit_test(&self, name: &str, point: HitPoint) -> bool {
+        let point = self.hit_point_to_units(point);
+        self.hit_areas
             .iter()
-            .find(|g| g.target == GroupTarget::Parameter && g.name == "EyeBlink")?
-            .ids
+            .filter(|(n, _)| n == name)
+            .any(|&(_, idx)| self.drawable_contains(idx, point))
+    }
+
+    /// Returns the names of all hit areas that lie under the given point.
+    ///
+    /// The test is done against the triangles of the drawable's deformed
+    /// vertex positions, so the model has to be updated beforehand.
+    pub fn hit_areas_at(&self, point: HitPoint) -> impl Iterator<Item = &str> {
+        let point = self.hit_point_to_units(point);
+        self.hit_areas
             .iter()
-            .flat_map(|id| model.parameter_ids().iter().position(|id2| *id2 == *id))
-            .collect();
+            .filter(move |&&(_, idx)| self.drawable_contains(idx, point))
+            .map(|(name, _)| &**name)
+    }
 
-        let mut eb = EyeBlink::default();
-        eb.set_ids(eye_blink_ids);
-        Some(eb)
+    fn hit_point_to_units(&self, point: HitPoint) -> [f32; 2] {
+        match point {
+            HitPoint::Units(point) => point,
+            HitPoint::Pixels([x, y]) => {
+                let (_, [origin_x, origin_y], ppu) = self.model.canvas_info();
+                [(x - origin_x) / ppu, (origin_y - y) / ppu]
+            },
+        }
+    }
+
+    fn drawable_contains(&self, idx: DrawableIndex, point: [f32; 2]) -> bool {
+        let drawable = self.model.drawable_by_index(idx);
+        let positions = drawable.vertex_positions;
+        drawable.indices.chunks_exact(3).any(|tri| {
+            triangle_contains(
+                [
+                    positions[tri[0] as usize],
+                    positions[tri[1] as usize],
+                    positions[tri[2] as usize],
+                ],
+                point,
+            )
+        })
     }
 
     /// Saves the current parameter values of this model in a hidden snapshot.
@@ -98,17 +457,41 @@ impl UserModel {
             .swap_with_slice(self.model.parameter_values_mut());
     }
 
-    /// Applies the expression(if set), runs the controllers in order and
-    /// updates the model.
+    /// Applies the playing motions, runs the controllers in order and updates
+    /// the model.
+    ///
+    /// The motions and the controllers submit their values to an
+    /// [`Accumulator`] which is resolved into the model once for the motions
+    /// and once for the controllers, clamping the parameter values to their
+    /// ranges. The values resolved from the motions are saved as the
+    /// parameter snapshot.
     pub fn update(&mut self, delta: f32) {
         self.load_parameters();
-        // do motion update here
+        self.accumulator.begin(&self.model);
+        for motion in self.motions.values_mut().flatten() {
+            if motion.is_playing() {
+                motion.tick(f64::from(delta));
+                motion.contribute(&self.model, &mut self.accumulator);
+            }
+        }
+        self.accumulator.resolve(&mut self.model);
         self.save_parameters();
-        self.controller_map
-            .update_enabled_controllers(&mut self.model, delta);
+        self.accumulator.begin(&self.model);
+        self.controller_map.update_enabled_controllers(
+            &mut self.model,
+            &mut self.accumulator,
+            delta,
+        );
+        self.accumulator.resolve(&mut self.model);
         self.model.update();
     }
 
+    /// Resets the state of all controllers, for example after the model has
+    /// been teleported.
+    pub fn reset_controllers(&mut self) {
+        self.controller_map.reset_controllers(&mut self.model);
+    }
+
     /// The controller map of this model.
     pub fn controllers_map(&self) -> &ControllerMap {
         &self.controller_map
@@ -131,6 +514,75 @@ impl UserModel {
         self.controller_map.get_mut::<C>()
     }
 
+    /// The motions of this model, keyed by their group name.
+    pub fn motions(&self) -> &IndexMap<String, Vec<Motion>> {
+        &self.motions
+    }
+
+    /// The motions of this model, keyed by their group name.
+    pub fn motions_mut(&mut self) -> &mut IndexMap<String, Vec<Motion>> {
+        &mut self.motions
+    }
+
+    /// Returns the motion at the index of the group if it exists.
+    pub fn motion(&self, group: &str, index: usize) -> Option<&Motion> {
+        self.motions
+            .get(group)
+            .and_then(|motions| motions.get(index))
+    }
+
+    /// Returns the motion at the index of the group if it exists.
+    pub fn motion_mut(&mut self, group: &str, index: usize) -> Option<&mut Motion> {
+        self.motions
+            .get_mut(group)
+            .and_then(|motions| motions.get_mut(index))
+    }
+
+    /// The standard parameters, parts and groups of this model.
+    pub fn standard_ids(&self) -> &StandardIds {
+        &self.standard_ids
+    }
+
+    /// Resolves the standard ids again, trying the aliases first, and
+    /// retargets the [`EyeBlink`] controller if there is one. The groups of
+    /// the model3.json are kept.
+    pub fn set_id_aliases(&mut self, aliases: &IdAliases) {
+        let StandardIds {
+            eye_blink,
+            lip_sync,
+            ..
+        } = std::mem::take(&mut self.standard_ids);
+        self.standard_ids = StandardIds {
+            eye_blink,
+            lip_sync,
+            ..StandardIds::with_aliases(self.model.moc(), aliases)
+        };
+        if let Some(eye_blink) = self.controller_map.get_mut::<EyeBlink>() {
+            eye_blink.set_standard_ids(&self.standard_ids);
+        }
+    }
+
+    /// The user data of this model.
+    pub fn user_data(&self) -> &[UserData] {
+        &self.user_data
+    }
+
+    /// The display info of this model, holding the human readable names of
+    /// its parameters and parts.
+    pub fn display_info(&self) -> Option<&Cdi3> {
+        self.display_info.as_ref()
+    }
+
+    /// The matrix placing this model in view space.
+    pub fn model_matrix(&self) -> &ModelMatrix {
+        &self.model_matrix
+    }
+
+    /// The matrix placing this model in view space.
+    pub fn model_matrix_mut(&mut self) -> &mut ModelMatrix {
+        &mut self.model_matrix
+    }
+
     /// The underlying core model.
     pub fn model(&self) -> &Model {
         &self.model
@@ -142,6 +594,40 @@ impl UserModel {
     }
 }
 
+// Returns an UnresolvedId error for the first id that isn't in `known`.
+fn check_ids<'a>(
+    known: &[&str],
+    kind: IdKind,
+    mut ids: impl Iterator<Item = &'a str>,
+    path: &Path,
+) -> CubismResult<()> {
+    match ids.find(|id| !known.contains(id)) {
+        Some(id) => Err(CubismError::UnresolvedId {
+            path: path.to_owned(),
+            reference: None,
+            kind,
+            id: id.to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn triangle_contains([a, b, c]: [[f32; 2]; 3], p: [f32; 2]) -> bool {
+    let edge = |p0: [f32; 2], p1: [f32; 2]| {
+        (p1[0] - p0[0]) * (p[1] - p0[1]) - (p1[1] - p0[1]) * (p[0] - p0[0])
+    };
+    // collapsed triangles cover no area, but points on them would pass the
+    // sign test
+    let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+    if area == 0.0 {
+        return false;
+    }
+    let (d0, d1, d2) = (edge(a, b), edge(b, c), edge(c, a));
+    let has_neg = d0 < 0.0 || d1 < 0.0 || d2 < 0.0;
+    let has_pos = d0 > 0.0 || d1 > 0.0 || d2 > 0.0;
+    !(has_neg && has_pos)
+}
+
 impl ops::Deref for UserModel {
     type Target = Model;
     #[inline]
@@ -155,3 +641,132 @@ impl fmt::Debug for UserModel {
         f.debug_struct("UserModel").finish()
     }
 }
+
+#[test]
+fn triangle_contains_point() {
+    let tri = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+    assert!(triangle_contains(tri, [0.25, 0.25]));
+    assert!(triangle_contains(tri, [0.5, 0.0]));
+    assert!(!triangle_contains(tri, [0.75, 0.75]));
+    assert!(!triangle_contains(tri, [-0.1, 0.5]));
+    // winding order must not matter
+    assert!(triangle_contains([tri[0], tri[2], tri[1]], [0.25, 0.25]));
+    // zero-area triangles contain nothing
+    assert!(!triangle_contains([[0.0, 0.0]; 3], [0.0, 0.0]));
+    assert!(!triangle_contains(
+        [[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]],
+        [0.5, 0.5]
+    ));
+}
+
+#[test]
+fn user_model_hit_test() {
+    use cubism_core::{DrawableDescription, ModelDescription, ParameterDescription};
+
+    let square = |id: &str, offset: [f32; 2]| {
+        let mut drawable =
+            DrawableDescription::new(id, vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]])
+                .with_offsets(0, vec![offset; 4]);
+        drawable.indices = vec![0, 1, 2, 0, 2, 3];
+        drawable
+    };
+    // the second drawable collapses into the point [1.0, 1.0] at the
+    // parameter's maximum
+    let mut collapsed = DrawableDescription::new("Collapsed", vec![[0.0, 0.0]; 3])
+        .with_offsets(0, vec![[1.0, 1.0]; 3]);
+    collapsed.indices = vec![0, 1, 2];
+    let description = ModelDescription::new()
+        .with_parameter(ParameterDescription::new("ParamMove", 0.0, 1.0, 0.0))
+        .with_drawable(square("Body", [1.0, 0.0]))
+        .with_drawable(collapsed);
+    let mut model = UserModel::new(Model::from_description(description));
+    assert!(model.register_hit_area("Body", "Body"));
+    assert!(model.register_hit_area("Point", "Collapsed"));
+    assert!(!model.register_hit_area("Missing", "Missing"));
+
+    model.update(0.0);
+    assert!(model.hit_test("Body", HitPoint::Units([0.5, 0.5])));
+    assert!(!model.hit_test("Body", HitPoint::Units([1.5, 0.5])));
+    assert!(!model.hit_test("Point", HitPoint::Units([0.0, 0.0])));
+
+    // the hit test follows the deformed vertex positions
+    let param = model.parameter_index("ParamMove").unwrap();
+    *model.model_mut().parameter_by_index_mut(param).value = 1.0;
+    model.save_parameters();
+    model.update(0.0);
+    assert!(!model.hit_test("Body", HitPoint::Units([0.5, 0.5])));
+    assert!(model.hit_test("Body", HitPoint::Units([1.5, 0.5])));
+    assert!(!model.hit_test("Point", HitPoint::Units([1.0, 1.0])));
+    assert_eq!(
+        model
+            .hit_areas_at(HitPoint::Units([1.5, 0.5]))
+            .collect::<Vec<_>>(),
+        ["Body"]
+    );
+}
+
+#[test]
+fn user_model_controller_stages() {
+    use crate::accumulator::BlendMode;
+    use cubism_core::{ModelDescription, ParameterDescription};
+
+    // writes the source parameter
+    struct Source;
+    impl Controller for Source {
+        fn update_parameters(&mut self, model: &Model, acc: &mut Accumulator, _: f32) {
+            let idx = model.parameter_index_at(0).unwrap();
+            acc.submit_parameter(idx, BlendMode::Overwrite, 1.0, 1.0);
+        }
+        fn priority(&self) -> usize {
+            0
+        }
+    }
+    // copies the source parameter into the parameter at the index, running
+    // with the priority before it
+    struct Follow(usize);
+    impl Controller for Follow {
+        fn update_parameters(&mut self, model: &Model, acc: &mut Accumulator, _: f32) {
+            let value = model.parameter_values()[0];
+            let idx = model.parameter_index_at(self.0).unwrap();
+            acc.submit_parameter(idx, BlendMode::Overwrite, value, 1.0);
+        }
+        fn priority(&self) -> usize {
+            self.0 - 1
+        }
+    }
+
+    let description = ModelDescription::new()
+        .with_parameter(ParameterDescription::new("Source", 0.0, 1.0, 0.0))
+        .with_parameter(ParameterDescription::new("Same", 0.0, 1.0, 0.0))
+        .with_parameter(ParameterDescription::new("Later", 0.0, 1.0, 0.0));
+    let mut model = UserModel::new(Model::from_description(description));
+    model.controllers_map_mut().register(Source);
+    model
+        .controllers_map_mut()
+        .register_named("same", Follow(1));
+    model
+        .controllers_map_mut()
+        .register_named("later", Follow(2));
+    model.update(0.0);
+    // the follower of a lower priority sees the output of the source, the one
+    // with the same priority doesn't
+    assert_eq!(model.model().parameter_values(), [1.0, 0.0, 1.0]);
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn user_model_zip_without_model3() {
+    use std::io::{Cursor, Write};
+    use zip::{write::FileOptions, ZipWriter};
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .start_file("pkg/idle.motion3.json", FileOptions::default())
+        .unwrap();
+    writer.write_all(b"{}").unwrap();
+    let archive = writer.finish().unwrap();
+    match UserModel::from_zip(archive, &LoadOptions::default()) {
+        Err(CubismError::MissingModel3) => (),
+        res => panic!("unexpected result {:?}", res.map(|_| ())),
+    }
+}
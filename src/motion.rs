@@ -1,11 +1,12 @@
 //! Motion.
 
-use std::fs;
 use std::ops::{Deref, DerefMut};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use crate::accumulator::{Accumulator, BlendMode};
+use crate::asset::{self, AssetSource, FileSystem};
 use crate::core::Model;
-use crate::error::CubismResult;
+use crate::error::{CubismError, CubismResult, IdKind};
 use crate::json::motion::{Motion3, Segment, SegmentPoint};
 
 fn lerp_points(p0: SegmentPoint, p1: SegmentPoint, t: f32) -> SegmentPoint {
@@ -55,6 +56,11 @@ fn segment_interpolate(seg: &Segment, t: f32) -> f32 {
     }
 }
 
+// Eases `t` in [0.0, 1.0] in and out along a sine curve.
+pub(crate) fn easing_sine(t: f32) -> f32 {
+    0.5 - 0.5 * (t.clamp(0.0, 1.0) * std::f32::consts::PI).cos()
+}
+
 /// Handles motions and animates a model.
 #[derive(Clone, Debug)]
 pub struct Motion {
@@ -64,6 +70,11 @@ pub struct Motion {
     looped: bool,
     playing: bool,
     current_time: f64,
+    // the time played since the motion has been started, unlike the current
+    // time this doesn't wrap around when looping
+    elapsed_time: f64,
+    fade_in_time: f32,
+    fade_out_time: f32,
 }
 
 impl Motion {
@@ -72,6 +83,8 @@ impl Motion {
         let duration = motion3.meta.duration;
         let fps = motion3.meta.fps;
         let looped = motion3.meta.looped;
+        let fade_in_time = motion3.meta.fade_in_time.unwrap_or(1.0);
+        let fade_out_time = motion3.meta.fade_out_time.unwrap_or(1.0);
 
         Motion {
             json: motion3,
@@ -80,6 +93,9 @@ impl Motion {
             looped,
             playing: false,
             current_time: 0.0,
+            elapsed_time: 0.0,
+            fade_in_time,
+            fade_out_time,
         }
     }
     /// Set whether the motion loops.
@@ -87,6 +103,46 @@ impl Motion {
         self.looped = looped;
     }
 
+    /// Sets the time in seconds it takes for the motion to fade in after it
+    /// started playing.
+    pub fn set_fade_in_time(&mut self, fade_in_time: f32) {
+        self.fade_in_time = fade_in_time.max(0.0);
+    }
+
+    /// The time in seconds it takes for the motion to fade in after it started
+    /// playing.
+    pub fn fade_in_time(&self) -> f32 {
+        self.fade_in_time
+    }
+
+    /// Sets the time in seconds it takes for a motion that doesn't loop to
+    /// fade out before it ends.
+    pub fn set_fade_out_time(&mut self, fade_out_time: f32) {
+        self.fade_out_time = fade_out_time.max(0.0);
+    }
+
+    /// The time in seconds it takes for a motion that doesn't loop to fade out
+    /// before it ends.
+    pub fn fade_out_time(&self) -> f32 {
+        self.fade_out_time
+    }
+
+    /// The weight the motion is currently applied with, according to its fade
+    /// in and fade out times.
+    pub fn fade_weight(&self) -> f32 {
+        let fade_in = if self.fade_in_time > 0.0 {
+            easing_sine(self.elapsed_time as f32 / self.fade_in_time)
+        } else {
+            1.0
+        };
+        let fade_out = if !self.looped && self.fade_out_time > 0.0 {
+            easing_sine((self.duration - self.current_time as f32) / self.fade_out_time)
+        } else {
+            1.0
+        };
+        fade_in * fade_out
+    }
+
     /// Plays a motion.
     pub fn play(&mut self) {
         self.playing = true;
@@ -101,6 +157,7 @@ impl Motion {
     pub fn stop(&mut self) {
         self.playing = false;
         self.current_time = 0.0;
+        self.elapsed_time = 0.0;
     }
 
     /// Return if the motion playing.
@@ -109,8 +166,14 @@ impl Motion {
     }
 
     /// Creates a Motion from a path of .motion3.json file.
+    #[inline]
     pub fn from_motion3_json<P: AsRef<Path>>(path: P) -> CubismResult<Motion> {
-        let json = Motion3::from_reader(fs::File::open(path)?)?;
+        Self::from_source(&FileSystem, path)
+    }
+
+    /// Creates a Motion from a path of .motion3.json file in the asset source.
+    pub fn from_source<P: AsRef<Path>>(source: &dyn AssetSource, path: P) -> CubismResult<Motion> {
+        let json: Motion3 = asset::read_json(source, path.as_ref())?;
 
         Ok(Motion::new(json))
     }
@@ -126,6 +189,7 @@ impl Motion {
         let duration = f64::from(self.duration);
 
         self.current_time += delta_time;
+        self.elapsed_time += delta_time;
 
         if duration <= self.current_time {
             if self.looped {
@@ -138,8 +202,38 @@ impl Motion {
     }
 
     /// Updates a model.
+    ///
+    /// Fails with [`CubismError::UnresolvedId`] without touching the model if
+    /// the motion refers to a parameter or part the model doesn't contain.
+    ///
+    /// [`CubismError::UnresolvedId`]: ../error/enum.CubismError.html#variant.UnresolvedId
     pub fn update(&self, model: &mut Model) -> CubismResult<()> {
+        for curve in &self.json.curves {
+            let kind = match &*curve.target {
+                "Parameter" if model.parameter_index(&curve.id).is_none() => IdKind::Parameter,
+                "PartOpacity" if model.part_index(&curve.id).is_none() => IdKind::Part,
+                _ => continue,
+            };
+            return Err(CubismError::UnresolvedId {
+                path: PathBuf::new(),
+                reference: None,
+                kind,
+                id: curve.id.clone(),
+            });
+        }
+        let mut accumulator = Accumulator::new();
+        accumulator.begin(model);
+        self.contribute(model, &mut accumulator);
+        accumulator.resolve(model);
+        Ok(())
+    }
+
+    /// Submits the values of the motion at its current time to the
+    /// accumulator, overwriting the parameter values and part opacities of
+    /// the model faded by the [`fade_weight`](#method.fade_weight).
+    pub fn contribute(&self, model: &Model, accumulator: &mut Accumulator) {
         let current = self.current_time as f32;
+        let weight = self.fade_weight();
 
         let mut lip_sync: Option<f32> = None;
         let mut eye_blink: Option<f32> = None;
@@ -172,16 +266,18 @@ impl Motion {
                         }
                     },
                     "PartOpacity" => {
-                        let param = model.part_mut(id);
-                        if let Some(param) = param {
-                            *param.opacity = value;
+                        if let Some(idx) = model.part_index(id) {
+                            accumulator.submit_part_opacity(
+                                idx,
+                                BlendMode::Overwrite,
+                                value,
+                                weight,
+                            );
                         }
                     },
                     "Parameter" => {
-                        let param = model.parameter_mut(id);
-                        if let Some(param) = param {
-                            // TODO: fade-in capability
-                            *param.value = value;
+                        if let Some(idx) = model.parameter_index(id) {
+                            accumulator.submit_parameter(idx, BlendMode::Overwrite, value, weight);
 
                             if let Some(_value) = eye_blink {
                                 // TODO: multiply eye_blink to value if the
@@ -212,9 +308,6 @@ impl Motion {
         if lip_sync.is_none() {
             // TODO: handle lip syncing when not overwritten
         }
-
-        // TODO: Better error handling
-        Ok(())
     }
 }
 
@@ -236,3 +329,62 @@ impl DerefMut for Motion {
         &mut self.json
     }
 }
+
+#[test]
+fn motion_fade_fake_model() {
+    use cubism_core::{ModelDescription, ParameterDescription};
+
+    let mut model = Model::from_description(
+        ModelDescription::new().with_parameter(ParameterDescription::new("ParamA", 0.0, 1.0, 0.0)),
+    );
+    let motion3: Motion3 = r#"{
+        "Version": 3,
+        "Meta": {
+            "Duration": 2.0,
+            "Fps": 30.0,
+            "Loop": false,
+            "AreBeziersRestricted": true,
+            "CurveCount": 1,
+            "TotalSegmentCount": 1,
+            "TotalPointCount": 2,
+            "UserDataCount": 0,
+            "TotalUserDataSize": 0,
+            "FadeInTime": 0.5
+        },
+        "Curves": [
+            { "Target": "Parameter", "Id": "ParamA", "Segments": [0.0, 1.0, 0, 2.0, 1.0] }
+        ]
+    }"#
+    .parse()
+    .unwrap();
+    let mut motion = Motion::new(motion3);
+    assert_eq!(motion.fade_in_time(), 0.5);
+    assert_eq!(motion.fade_out_time(), 1.0);
+    motion.play();
+
+    let mut value_at = |motion: &mut Motion, delta: f64| {
+        motion.tick(delta);
+        model.reset_to_defaults();
+        motion.update(&mut model).unwrap();
+        model.parameter_values()[0]
+    };
+    // halfway through the fade in the sine easing is at 0.5
+    assert_eq!(value_at(&mut motion, 0.25), 0.5);
+    assert_eq!(value_at(&mut motion, 0.5), 1.0);
+    // and halfway through the fade out again
+    assert_eq!(value_at(&mut motion, 0.75), 0.5);
+    assert_eq!(value_at(&mut motion, 0.5), 0.0);
+    assert!(!motion.is_playing());
+
+    let mut other = Model::from_description(
+        ModelDescription::new().with_parameter(ParameterDescription::new("ParamB", 0.0, 1.0, 0.0)),
+    );
+    match motion.update(&mut other) {
+        Err(CubismError::UnresolvedId {
+            kind: IdKind::Parameter,
+            id,
+            ..
+        }) => assert_eq!(id, "ParamA"),
+        res => panic!("unexpected result {:?}", res),
+    }
+}
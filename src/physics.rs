@@ -1,20 +1,627 @@
-use crate::core::Model;
-use crate::json::physics::Physics3;
+//! Physics simulation of a model.
+use std::{f32::consts::PI, path::Path};
 
+use crate::accumulator::{Accumulator, BlendMode};
+use crate::asset::{self, AssetSource, FileSystem};
+use crate::controller::Controller;
+use crate::core::{Model, ParameterIndex};
+use crate::error::CubismResult;
+use crate::json::physics::{Physics3, PhysicsNormalizationParameter};
+
+// Resistance applied to the rotation of the particles.
+const AIR_RESISTANCE: f32 = 5.0;
+// The weight of inputs and outputs that corresponds to a factor of 1.0.
+const MAXIMUM_WEIGHT: f32 = 100.0;
+// Positions with a smaller absolute x value are snapped to 0.0.
+const MOVEMENT_THRESHOLD: f32 = 0.001;
+// The longest time simulated at once, longer frames are cut short.
+const MAXIMUM_DELTA: f32 = 5.0;
+
+type Vec2 = [f32; 2];
+
+#[inline]
+fn add([ax, ay]: Vec2, [bx, by]: Vec2) -> Vec2 {
+    [ax + bx, ay + by]
+}
+
+#[inline]
+fn sub([ax, ay]: Vec2, [bx, by]: Vec2) -> Vec2 {
+    [ax - bx, ay - by]
+}
+
+#[inline]
+fn mul([x, y]: Vec2, s: f32) -> Vec2 {
+    [x * s, y * s]
+}
+
+#[inline]
+fn rotate([x, y]: Vec2, rad: f32) -> Vec2 {
+    let (sin, cos) = rad.sin_cos();
+    [x * cos - y * sin, x * sin + y * cos]
+}
+
+fn normalize([x, y]: Vec2) -> Vec2 {
+    let len = x.hypot(y);
+    if len > 0.0 {
+        [x / len, y / len]
+    } else {
+        [x, y]
+    }
+}
+
+// The angle between two directions in radians, wrapped into [-PI, PI].
+fn direction_to_radian([fx, fy]: Vec2, [tx, ty]: Vec2) -> f32 {
+    let mut ret = ty.atan2(tx) - fy.atan2(fx);
+    while ret < -PI {
+        ret += 2.0 * PI;
+    }
+    while ret > PI {
+        ret -= 2.0 * PI;
+    }
+    ret
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Component {
+    X,
+    Y,
+    Angle,
+}
+
+impl Component {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "X" => Some(Component::X),
+            "Y" => Some(Component::Y),
+            "Angle" => Some(Component::Angle),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Normalization {
+    minimum: f32,
+    maximum: f32,
+    default: f32,
+}
+
+impl From<PhysicsNormalizationParameter> for Normalization {
+    fn from(n: PhysicsNormalizationParameter) -> Self {
+        Normalization {
+            minimum: n.minimum,
+            maximum: n.maximum,
+            default: n.default,
+        }
+    }
+}
+
+impl Normalization {
+    // Maps the value from the parameter range into the normalized range.
+    fn normalize(&self, value: f32, min: f32, max: f32, reflect: bool) -> f32 {
+        let (min_value, max_value) = (min.min(max), min.max(max));
+        let value = value.max(min_value).min(max_value);
+        let (min_norm, max_norm) = (
+            self.minimum.min(self.maximum),
+            self.minimum.max(self.maximum),
+        );
+        let middle_value = min_value + (max_value - min_value) / 2.0;
+        let param_value = value - middle_value;
+
+        let (n_length, p_length) = if param_value > 0.0 {
+            (max_norm - self.default, max_value - middle_value)
+        } else if param_value < 0.0 {
+            (min_norm - self.default, min_value - middle_value)
+        } else {
+            (0.0, 0.0)
+        };
+        let result = if p_length != 0.0 {
+            param_value * (n_length / p_length) + self.default
+        } else {
+            self.default
+        };
+        if reflect {
+            result
+        } else {
+            -result
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Input {
+    source: ParameterIndex,
+    weight: f32,
+    component: Component,
+    reflect: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Output {
+    destination: ParameterIndex,
+    vertex_index: usize,
+    scale: f32,
+    weight: f32,
+    component: Component,
+    reflect: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Particle {
+    mobility: f32,
+    delay: f32,
+    acceleration: f32,
+    radius: f32,
+    position: Vec2,
+    last_position: Vec2,
+    last_gravity: Vec2,
+    velocity: Vec2,
+}
+
+#[derive(Clone, Debug)]
+struct PhysicsSetting {
+    inputs: Box<[Input]>,
+    outputs: Box<[Output]>,
+    particles: Box<[Particle]>,
+    normalization_position: Normalization,
+    normalization_angle: Normalization,
+}
+
+impl PhysicsSetting {
+    fn reset(&mut self) {
+        let mut position = [0.0, 0.0];
+        for (i, particle) in self.particles.iter_mut().enumerate() {
+            if i != 0 {
+                position = add(position, [0.0, particle.radius]);
+            }
+            particle.position = position;
+            particle.last_position = position;
+            particle.last_gravity = [0.0, 1.0];
+            particle.velocity = [0.0, 0.0];
+        }
+    }
+
+    fn update_particles(&mut self, translation: Vec2, angle: f32, wind: Vec2, delta: f32) {
+        let threshold = MOVEMENT_THRESHOLD * self.normalization_position.maximum;
+        let gravity = normalize([angle.to_radians().sin(), angle.to_radians().cos()]);
+
+        let particles = &mut self.particles[..];
+        if let Some(root) = particles.first_mut() {
+            root.position = translation;
+        }
+        for i in 1..particles.len() {
+            let parent = particles[i - 1].position;
+            let particle = &mut particles[i];
+
+            let force = add(mul(gravity, particle.acceleration), wind);
+            particle.last_position = particle.position;
+            let delay = particle.delay * delta * 30.0;
+
+            let radian = direction_to_radian(particle.last_gravity, gravity) / AIR_RESISTANCE;
+            let direction = rotate(sub(particle.position, parent), radian);
+            particle.position = add(parent, direction);
+            particle.position = add(
+                particle.position,
+                add(mul(particle.velocity, delay), mul(force, delay * delay)),
+            );
+
+            let new_direction = normalize(sub(particle.position, parent));
+            particle.position = add(parent, mul(new_direction, particle.radius));
+            if particle.position[0].abs() < threshold {
+                particle.position[0] = 0.0;
+            }
+
+            if delay != 0.0 {
+                particle.velocity = mul(
+                    sub(particle.position, particle.last_position),
+                    particle.mobility / delay,
+                );
+            }
+            particle.last_gravity = gravity;
+        }
+    }
+}
+
+/// A Physics controller. This Controller simulates the pendulums described by a
+/// physics3.json, driving output parameters like hair and accessory sway from
+/// input parameters like the head angle.
+#[derive(Clone, Debug)]
 pub struct Physics {
-    wind: (f32, f32),
-    gravity: (f32, f32),
-    rig: PhysicsRig,
+    settings: Box<[PhysicsSetting]>,
+    gravity: [f32; 2],
+    wind: [f32; 2],
+    // the rate the simulation runs at, every frame is simulated at once if
+    // there is none
+    fps: Option<f32>,
+    // the time that hasn't been simulated yet at the fixed rate
+    remaining: f32,
+    // reused by `evaluate` to not allocate every frame
+    accumulator: Accumulator,
+    // the values of the output parameters, reused by `contribute`
+    output_values: Vec<(ParameterIndex, f32)>,
 }
 
 impl Physics {
-    pub fn from_physics3(phys3: Physics3) -> Self {
+    /// Creates a Physics from a path of a .physics3.json file and the
+    /// corresponding model.
+    #[inline]
+    pub fn from_physics3_json<P: AsRef<Path>>(model: &Model, path: P) -> CubismResult<Self> {
+        Self::from_source(model, &FileSystem, path)
+    }
+
+    /// Creates a Physics from a path of a .physics3.json file in the asset
+    /// source and the corresponding model.
+    pub fn from_source<P: AsRef<Path>>(
+        model: &Model,
+        source: &dyn AssetSource,
+        path: P,
+    ) -> CubismResult<Self> {
+        let phys3: Physics3 = asset::read_json(source, path.as_ref())?;
+        Ok(Self::from_physics3(model, &phys3))
+    }
+
+    /// Creates a Physics from a Physics3 and the corresponding model. Inputs
+    /// and outputs referring to parameters the model doesn't have are ignored.
+    pub fn from_physics3(model: &Model, phys3: &Physics3) -> Self {
+        let settings = phys3
+            .physics_settings
+            .iter()
+            .map(|setting| {
+                let (normalization_position, normalization_angle) = setting
+                    .normalization
+                    .map(|n| (n.position.into(), n.angle.into()))
+                    .unwrap_or_default();
+                let mut setting = PhysicsSetting {
+                    inputs: setting
+                        .input
+                        .iter()
+                        .filter_map(|input| {
+                            Some(Input {
+                                source: model.parameter_index(&input.source.id)?,
+                                weight: input.weight / MAXIMUM_WEIGHT,
+                                component: Component::from_str(&input.ty)?,
+                                reflect: input.reflect,
+                            })
+                        })
+                        .collect(),
+                    outputs: setting
+                        .output
+                        .iter()
+                        .filter_map(|output| {
+                            Some(Output {
+                                destination: model.parameter_index(&output.destination.id)?,
+                                vertex_index: output.vertex_index,
+                                scale: output.scale,
+                                weight: output.weight / MAXIMUM_WEIGHT,
+                                component: Component::from_str(&output.ty)?,
+                                reflect: output.reflect,
+                            })
+                        })
+                        .collect(),
+                    particles: setting
+                        .vertices
+                        .iter()
+                        .map(|vertex| Particle {
+                            mobility: vertex.mobility,
+                            delay: vertex.delay,
+                            acceleration: vertex.acceleration,
+                            radius: vertex.radius,
+                            position: [0.0, 0.0],
+                            last_position: [0.0, 0.0],
+                            last_gravity: [0.0, 1.0],
+                            velocity: [0.0, 0.0],
+                        })
+                        .collect(),
+                    normalization_position,
+                    normalization_angle,
+                };
+                setting.reset();
+                setting
+            })
+            .collect();
         Physics {
-            wind: (0.0, 0.0),
-            gravity: (0.0, -1.0),
-            rig: PhysicsRig::from_physics3(phys3),
+            settings,
+            gravity: [0.0, -1.0],
+            wind: [0.0, 0.0],
+            fps: phys3.meta.fps,
+            remaining: 0.0,
+            accumulator: Accumulator::new(),
+            output_values: Vec::new(),
+        }
+    }
+
+    /// Sets the direction of gravity.
+    pub fn set_gravity(&mut self, gravity: [f32; 2]) {
+        self.gravity = gravity;
+    }
+
+    /// The direction of gravity.
+    pub fn gravity(&self) -> [f32; 2] {
+        self.gravity
+    }
+
+    /// Sets the direction and strength of the wind.
+    pub fn set_wind(&mut self, wind: [f32; 2]) {
+        self.wind = wind;
+    }
+
+    /// The direction and strength of the wind.
+    pub fn wind(&self) -> [f32; 2] {
+        self.wind
+    }
+
+    /// Sets the rate in steps per second the simulation runs at, or `None`
+    /// to simulate every frame at once.
+    pub fn set_fps(&mut self, fps: Option<f32>) {
+        self.fps = fps;
+        self.remaining = 0.0;
+    }
+
+    /// The rate in steps per second the simulation runs at, taken from the
+    /// `Fps` of the physics3.json by default.
+    pub fn fps(&self) -> Option<f32> {
+        self.fps
+    }
+
+    /// Resets all pendulums to their resting positions.
+    pub fn reset(&mut self) {
+        for setting in self.settings.iter_mut() {
+            setting.reset();
+        }
+        self.remaining = 0.0;
+    }
+
+    /// Runs the simulation for `delta` seconds and writes the results into the
+    /// output parameters of the model.
+    pub fn evaluate(&mut self, model: &mut Model, delta: f32) {
+        let mut accumulator = std::mem::take(&mut self.accumulator);
+        accumulator.begin(model);
+        self.contribute(model, &mut accumulator, delta);
+        accumulator.resolve(model);
+        self.accumulator = accumulator;
+    }
+
+    /// Runs the simulation for `delta` seconds and submits the results for the
+    /// output parameters to the accumulator.
+    ///
+    /// With a fixed rate the simulation advances in steps of its length, the
+    /// time left over is carried into the next call. Outputs driving the same
+    /// parameter are applied one after another, each one blending over the
+    /// value left by the previous ones by its weight.
+    pub fn contribute(&mut self, model: &Model, accumulator: &mut Accumulator, delta: f32) {
+        if delta <= 0.0 {
+            return;
+        }
+        let delta = delta.min(MAXIMUM_DELTA);
+        let (step, steps) = match self.fps {
+            Some(fps) if fps > 0.0 => {
+                let step = 1.0 / fps;
+                self.remaining += delta;
+                let steps = (self.remaining / step) as usize;
+                self.remaining -= steps as f32 * step;
+                (step, steps)
+            },
+            _ => (delta, 1),
+        };
+
+        self.output_values.clear();
+        for setting in self.settings.iter_mut() {
+            let mut translation = [0.0, 0.0];
+            let mut angle = 0.0;
+            for input in setting.inputs.iter() {
+                let param = model.parameter_by_index(input.source);
+                let normalization = match input.component {
+                    Component::Angle => &setting.normalization_angle,
+                    _ => &setting.normalization_position,
+                };
+                let value = normalization.normalize(
+                    param.value,
+                    param.min_value,
+                    param.max_value,
+                    input.reflect,
+                ) * input.weight;
+                match input.component {
+                    Component::X => translation[0] += value,
+                    Component::Y => translation[1] += value,
+                    Component::Angle => angle += value,
+                }
+            }
+            let translation = rotate(translation, (-angle).to_radians());
+
+            for _ in 0..steps {
+                setting.update_particles(translation, angle, self.wind, step);
+            }
+
+            let particles = &setting.particles;
+            for output in setting.outputs.iter() {
+                let idx = output.vertex_index;
+                if idx < 1 || idx >= particles.len() {
+                    break;
+                }
+                let translation = sub(particles[idx].position, particles[idx - 1].position);
+                let mut value = match output.component {
+                    Component::X => translation[0],
+                    Component::Y => translation[1],
+                    Component::Angle => {
+                        let parent_gravity = if idx >= 2 {
+                            sub(particles[idx - 1].position, particles[idx - 2].position)
+                        } else {
+                            mul(self.gravity, -1.0)
+                        };
+                        direction_to_radian(parent_gravity, translation)
+                    },
+                };
+                if output.reflect {
+                    value = -value;
+                }
+
+                let param = model.parameter_by_index(output.destination);
+                let value = (value * output.scale)
+                    .max(param.min_value)
+                    .min(param.max_value);
+                let pos = match self
+                    .output_values
+                    .iter()
+                    .position(|(idx, _)| *idx == output.destination)
+                {
+                    Some(pos) => pos,
+                    None => {
+                        let base = accumulator.parameter_value(output.destination);
+                        self.output_values.push((output.destination, base));
+                        self.output_values.len() - 1
+                    },
+                };
+                let current = &mut self.output_values[pos].1;
+                *current += (value - *current) * output.weight.min(1.0);
+            }
         }
+        for (idx, value) in self.output_values.iter().copied() {
+            accumulator.submit_parameter(idx, BlendMode::Overwrite, value, 1.0);
+        }
+    }
+}
+
+impl Controller for Physics {
+    fn update_parameters(&mut self, model: &Model, accumulator: &mut Accumulator, delta: f32) {
+        self.contribute(model, accumulator, delta);
+    }
+
+    fn priority(&self) -> usize {
+        crate::controller::default_priorities::PHYSICS
     }
 
-    pub fn update(&self, model: &Model, delta: f32) {}
+    fn reset(&mut self, _: &mut Model) {
+        Physics::reset(self);
+    }
+}
+
+#[test]
+fn physics_fake_model() {
+    use cubism_core::{ModelDescription, ParameterDescription};
+
+    let mut model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new("ParamAngleX", -30.0, 30.0, 0.0))
+            .with_parameter(ParameterDescription::new("ParamHair", -1.0, 1.0, 0.0)),
+    );
+    let phys3: Physics3 = r#"{
+        "Version": 3,
+        "Meta": {
+            "PhysicsSettingCount": 1, "TotalInputCount": 2, "TotalOutputCount": 1,
+            "VertexCount": 2, "EffectiveForces": {}, "PhysicsDictionary": []
+        },
+        "PhysicsSettings": [{
+            "Id": "PhysicsSetting1",
+            "Input": [
+                { "Source": { "Target": "Parameter", "Id": "ParamAngleX" },
+                  "Weight": 100, "Type": "X", "Reflect": false },
+                { "Source": { "Target": "Parameter", "Id": "ParamMissing" },
+                  "Weight": 100, "Type": "X", "Reflect": false }
+            ],
+            "Output": [
+                { "Destination": { "Target": "Parameter", "Id": "ParamHair" },
+                  "VertexIndex": 1, "Scale": 1, "Weight": 100, "Type": "X", "Reflect": false }
+            ],
+            "Vertices": [
+                { "Position": { "X": 0, "Y": 0 }, "Mobility": 1, "Delay": 1,
+                  "Acceleration": 1, "Radius": 0 },
+                { "Position": { "X": 0, "Y": 3 }, "Mobility": 0.9, "Delay": 0.6,
+                  "Acceleration": 1.5, "Radius": 3 }
+            ],
+            "Normalization": {
+                "Position": { "Minimum": -10, "Default": 0, "Maximum": 10 },
+                "Angle": { "Minimum": -10, "Default": 0, "Maximum": 10 }
+            }
+        }]
+    }"#
+    .parse()
+    .unwrap();
+    let mut physics = Physics::from_physics3(&model, &phys3);
+    let angle = model.parameter_index("ParamAngleX").unwrap();
+    let hair = model.parameter_index("ParamHair").unwrap();
+
+    // the pendulum hangs still while the input rests
+    physics.evaluate(&mut model, 1.0 / 30.0);
+    assert_eq!(model.parameter_by_index(hair).value, 0.0);
+
+    // moving the input swings the pendulum, the output stays in its range
+    *model.parameter_by_index_mut(angle).value = 30.0;
+    physics.evaluate(&mut model, 1.0 / 30.0);
+    let value = model.parameter_by_index(hair).value;
+    assert!(value != 0.0 && (-1.0..=1.0).contains(&value), "{}", value);
+
+    // without time passing nothing moves
+    physics.evaluate(&mut model, 0.0);
+    assert_eq!(model.parameter_by_index(hair).value, value);
+}
+
+#[test]
+fn physics_outputs_and_fps() {
+    use cubism_core::{ModelDescription, ParameterDescription};
+
+    let physics3 = |meta: &str, outputs: &str| -> Physics3 {
+        format!(
+            r#"{{
+                "Version": 3,
+                "Meta": {{
+                    "PhysicsSettingCount": 1, "TotalInputCount": 1, "TotalOutputCount": 2,
+                    "VertexCount": 2, "EffectiveForces": {{}}, "PhysicsDictionary": []{}
+                }},
+                "PhysicsSettings": [{{
+                    "Id": "PhysicsSetting1",
+                    "Input": [
+                        {{ "Source": {{ "Target": "Parameter", "Id": "ParamAngleX" }},
+                          "Weight": 100, "Type": "X", "Reflect": false }}
+                    ],
+                    "Output": [{}],
+                    "Vertices": [
+                        {{ "Position": {{ "X": 0, "Y": 0 }}, "Mobility": 1, "Delay": 1,
+                          "Acceleration": 1, "Radius": 0 }},
+                        {{ "Position": {{ "X": 0, "Y": 3 }}, "Mobility": 0.9, "Delay": 0.6,
+                          "Acceleration": 1.5, "Radius": 3 }}
+                    ],
+                    "Normalization": {{
+                        "Position": {{ "Minimum": -10, "Default": 0, "Maximum": 10 }},
+                        "Angle": {{ "Minimum": -10, "Default": 0, "Maximum": 10 }}
+                    }}
+                }}]
+            }}"#,
+            meta, outputs
+        )
+        .parse()
+        .unwrap()
+    };
+    let output = |scale: f32, weight: f32| {
+        format!(
+            r#"{{ "Destination": {{ "Target": "Parameter", "Id": "ParamHair" }},
+                  "VertexIndex": 1, "Scale": {}, "Weight": {}, "Type": "X", "Reflect": false }}"#,
+            scale, weight
+        )
+    };
+    let mut model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new("ParamAngleX", -30.0, 30.0, 30.0))
+            .with_parameter(ParameterDescription::new("ParamHair", -1.0, 1.0, 0.0)),
+    );
+    let hair = model.parameter_index("ParamHair").unwrap();
+    let mut simulate = |phys3: &Physics3, deltas: &[f32]| {
+        model.reset_to_defaults();
+        let mut physics = Physics::from_physics3(&model, phys3);
+        for &delta in deltas {
+            physics.evaluate(&mut model, delta);
+        }
+        model.parameter_by_index(hair).value
+    };
+
+    let single = simulate(&physics3("", &output(1.0, 100.0)), &[0.125]);
+    assert!(single != 0.0);
+    // the second output blends its value of 0.0 over the first one by half
+    let outputs = [output(1.0, 100.0), output(0.0, 50.0)].join(",");
+    let sequential = simulate(&physics3("", &outputs), &[0.125]);
+    assert_eq!(sequential, single * 0.5);
+
+    // at 8 steps per second the first half step doesn't simulate anything
+    let fixed = physics3(r#", "Fps": 8.0"#, &output(1.0, 100.0));
+    assert_eq!(simulate(&fixed, &[0.0625]), 0.0);
+    assert_eq!(simulate(&fixed, &[0.0625, 0.0625]), single);
 }
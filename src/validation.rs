@@ -0,0 +1,801 @@
+//! Validation of model packages, checking a model3.json and the files it
+//! references for problems before they are loaded.
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use cubism_core::Model;
+
+use crate::asset::{self, AssetSource, FileSystem, Prefixed};
+use crate::error::{CubismError, FileReference, IdKind};
+use crate::json::cdi::Cdi3;
+use crate::json::expression::{Expression3, ExpressionBlendType};
+use crate::json::model::{GroupTarget, Model3};
+use crate::json::motion::{Motion3, Segment};
+use crate::json::physics::Physics3;
+use crate::json::pose::Pose3;
+use crate::json::user_data::UserData3;
+use crate::json::{FormatWarning, JsonFile, Parsed};
+
+/// How severe a found issue is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The package loads, but likely doesn't behave as intended.
+    Warning,
+    /// The package is broken.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(fmt, "warning"),
+            Severity::Error => write!(fmt, "error"),
+        }
+    }
+}
+
+/// An issue found while validating a model package.
+#[derive(Debug)]
+pub enum Issue {
+    /// A file failed to load, or refers to an id the moc doesn't contain.
+    Load(CubismError),
+    /// A count in the meta section of a file doesn't match the actual data.
+    /// The official SDK preallocates its buffers from these counts, so counts
+    /// that are too small are errors.
+    MetaCount {
+        /// The path of the file.
+        path: PathBuf,
+        /// The file reference of the file.
+        reference: FileReference,
+        /// The name of the meta field.
+        field: &'static str,
+        /// The count stated by the meta section.
+        expected: usize,
+        /// The actual count.
+        actual: usize,
+    },
+    /// A json file has been loaded, but contains something this crate
+    /// doesn't understand.
+    Format {
+        /// The path of the file.
+        path: PathBuf,
+        /// The file reference of the file.
+        reference: FileReference,
+        /// The warning raised while parsing the file.
+        warning: FormatWarning,
+    },
+    /// A value lies outside of the range of the parameter it is applied to.
+    OutOfRange {
+        /// The path of the file.
+        path: PathBuf,
+        /// The file reference of the file.
+        reference: FileReference,
+        /// The id of the parameter.
+        id: String,
+        /// The value.
+        value: f32,
+        /// The minimum value of the parameter.
+        min: f32,
+        /// The maximum value of the parameter.
+        max: f32,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Load(e) => (e as &dyn fmt::Display).fmt(fmt),
+            Issue::MetaCount {
+                path,
+                reference,
+                field,
+                expected,
+                actual,
+            } => write!(
+                fmt,
+                "{} ({}): meta {} is {} but the file contains {}",
+                path.display(),
+                reference,
+                field,
+                expected,
+                actual
+            ),
+            Issue::Format {
+                path,
+                reference,
+                warning,
+            } => write!(fmt, "{} ({}): {}", path.display(), reference, warning),
+            Issue::OutOfRange {
+                path,
+                reference,
+                id,
+                value,
+                min,
+                max,
+            } => write!(
+                fmt,
+                "{} ({}): value {} of parameter {:?} is outside of its range [{}, {}]",
+                path.display(),
+                reference,
+                value,
+                id,
+                min,
+                max
+            ),
+        }
+    }
+}
+
+/// An issue together with its severity.
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// The severity of the issue.
+    pub severity: Severity,
+    /// The issue.
+    pub issue: Issue,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}: {}", self.severity, self.issue)
+    }
+}
+
+/// The result of validating a model package.
+#[derive(Debug, Default)]
+pub struct Report {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// All diagnostics in the order they were found.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// The diagnostics with [`Severity::Error`].
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.with_severity(Severity::Error)
+    }
+
+    /// The diagnostics with [`Severity::Warning`].
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.with_severity(Severity::Warning)
+    }
+
+    /// Returns true if any errors have been found.
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Returns true if neither errors nor warnings have been found.
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(move |d| d.severity == severity)
+    }
+
+    fn push(&mut self, severity: Severity, issue: Issue) {
+        self.diagnostics.push(Diagnostic { severity, issue });
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in &self.diagnostics {
+            writeln!(fmt, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates a Model3 whose file references are resolved relative to the
+/// base path.
+#[inline]
+pub fn validate(base: &Path, model3: &Model3) -> Report {
+    validate_source(&Prefixed::new(base, FileSystem), model3)
+}
+
+/// Validates a Model3 whose file references are resolved in the asset source.
+///
+/// Every referenced file is checked to exist and parse, json files are checked
+/// for unsupported versions and unknown fields, ids are checked
+/// against the moc, the meta counts of motions, physics and user data are
+/// checked against their contents and values of expressions and motions are
+/// checked against the parameter ranges.
+pub fn validate_source(source: &dyn AssetSource, model3: &Model3) -> Report {
+    let mut validator = Validator {
+        source,
+        model: None,
+        loaded_model: None,
+        report: Report::default(),
+    };
+    validator.validate(model3);
+    validator.report
+}
+
+/// Validates a Model3 against an already loaded model instead of the moc it
+/// references, resolving the other file references in the asset source.
+///
+/// The referenced moc file isn't read at all.
+pub fn validate_with_model(source: &dyn AssetSource, model3: &Model3, model: &Model) -> Report {
+    let mut validator = Validator {
+        source,
+        model: Some(model),
+        loaded_model: None,
+        report: Report::default(),
+    };
+    validator.validate(model3);
+    validator.report
+}
+
+struct Validator<'a> {
+    source: &'a dyn AssetSource,
+    // The given model. Id and range checks are skipped without it or a
+    // loaded one.
+    model: Option<&'a Model>,
+    // The model, if the moc could be loaded.
+    loaded_model: Option<Model>,
+    report: Report,
+}
+
+impl Validator<'_> {
+    fn model(&self) -> Option<&Model> {
+        self.model.or(self.loaded_model.as_ref())
+    }
+
+    fn validate(&mut self, model3: &Model3) {
+        let refs = &model3.file_references;
+        if self.model.is_none() {
+            match refs.moc.as_ref() {
+                Some(moc_path) => self.load_moc(moc_path),
+                None => self.error(CubismError::MissingMoc),
+            }
+        }
+
+        for (idx, texture) in refs.textures.iter().enumerate() {
+            if let Err(e) = asset::read(self.source, texture) {
+                self.error(e.with_reference(FileReference::Texture(idx)));
+            }
+        }
+
+        for group in model3.groups.iter() {
+            let kind = match group.target {
+                GroupTarget::Parameter => IdKind::Parameter,
+                GroupTarget::Part => IdKind::Part,
+                GroupTarget::Drawable => IdKind::Drawable,
+            };
+            let ids = group.ids.iter().map(|id| &**id);
+            self.check_ids(Path::new(""), &FileReference::Model3, kind, ids);
+        }
+        let ids = model3.hit_areas.iter().map(|hit_area| &*hit_area.id);
+        self.check_ids(Path::new(""), &FileReference::Model3, IdKind::Drawable, ids);
+
+        for exp in refs.expressions.iter() {
+            let reference = FileReference::Expression(exp.name.clone());
+            if let Some(exp3) = self.read_json(&exp.file, &reference) {
+                self.check_expression(&exp.file, &reference, &exp3);
+            }
+        }
+
+        for (group, motions) in refs.motions.iter() {
+            for (index, motion) in motions.iter().enumerate() {
+                let reference = FileReference::Motion {
+                    group: group.clone(),
+                    index,
+                };
+                if let Some(motion3) = self.read_json(&motion.file, &reference) {
+                    self.check_motion(&motion.file, &reference, &motion3);
+                }
+                if let Some(sound) = motion.sound.as_ref() {
+                    if let Err(e) = asset::read(self.source, sound) {
+                        self.warning(e.with_reference(reference));
+                    }
+                }
+            }
+        }
+
+        if let Some(path) = refs.physics.as_ref() {
+            if let Some(phys3) = self.read_json(path, &FileReference::Physics) {
+                self.check_physics(path, &phys3);
+            }
+        }
+
+        if let Some(path) = refs.pose.as_ref() {
+            if let Some(pose3) = self.read_json::<Pose3>(path, &FileReference::Pose) {
+                let ids = pose3.groups.iter().flatten().flat_map(|item| {
+                    std::iter::once(&*item.id).chain(item.link.iter().map(|id| &**id))
+                });
+                self.check_ids(path, &FileReference::Pose, IdKind::Part, ids);
+            }
+        }
+
+        if let Some(path) = refs.user_data.as_ref() {
+            let reference = FileReference::UserData;
+            if let Some(user_data3) = self.read_json::<UserData3>(path, &reference) {
+                self.check_count(
+                    path,
+                    &reference,
+                    "UserDataCount",
+                    user_data3.meta.user_data_count,
+                    user_data3.user_data.len(),
+                );
+                let size = user_data3.user_data.iter().map(|data| data.value.len());
+                self.check_count(
+                    path,
+                    &reference,
+                    "TotalUserDataSize",
+                    user_data3.meta.total_user_data_size,
+                    size.sum(),
+                );
+                let ids = user_data3.user_data.iter().map(|data| &*data.id);
+                self.check_ids(path, &reference, IdKind::Drawable, ids);
+            }
+        }
+
+        if let Some(path) = refs.display_info.as_ref() {
+            self.read_json::<Cdi3>(path, &FileReference::DisplayInfo);
+        }
+    }
+
+    fn load_moc(&mut self, path: &Path) {
+        let result = asset::read(self.source, path)
+            .map_err(|e| e.with_reference(FileReference::Moc))
+            .and_then(|moc| {
+                Model::from_bytes(&moc).map_err(|source| CubismError::LoadMoc {
+                    path: self.source.full_path(path),
+                    source,
+                })
+            });
+        match result {
+            Ok(model) => self.loaded_model = Some(model),
+            Err(e) => self.error(e),
+        }
+    }
+
+    fn read_json<T: JsonFile>(&mut self, path: &Path, reference: &FileReference) -> Option<T> {
+        match asset::read_json_file(self.source, path) {
+            Ok(Parsed { value, warnings }) => {
+                for warning in warnings {
+                    self.report.push(
+                        Severity::Warning,
+                        Issue::Format {
+                            path: self.source.full_path(path),
+                            reference: reference.clone(),
+                            warning,
+                        },
+                    );
+                }
+                Some(value)
+            },
+            Err(e) => {
+                self.error(e.with_reference(reference.clone()));
+                None
+            },
+        }
+    }
+
+    fn check_expression(&mut self, path: &Path, reference: &FileReference, exp3: &Expression3) {
+        let ids = exp3.parameters.iter().map(|param| &*param.id);
+        self.check_ids(path, reference, IdKind::Parameter, ids);
+        // Added and multiplied values are relative, so only overwriting ones
+        // can be checked against the range.
+        for param in exp3.parameters.iter() {
+            if param.blend_type == ExpressionBlendType::Overwrite {
+                self.check_range(path, reference, &param.id, param.value);
+            }
+        }
+    }
+
+    fn check_motion(&mut self, path: &Path, reference: &FileReference, motion3: &Motion3) {
+        let meta = &motion3.meta;
+        let curves = &motion3.curves;
+        let segment_count = curves.iter().map(|curve| curve.segments.len()).sum();
+        // Every curve starts with a single point, followed by the points of
+        // its segments.
+        let point_count = curves
+            .iter()
+            .map(|curve| {
+                1 + curve
+                    .segments
+                    .iter()
+                    .map(|seg| match seg {
+                        Segment::Bezier(_) => 3,
+                        _ => 1,
+                    })
+                    .sum::<usize>()
+            })
+            .sum();
+        self.check_count(
+            path,
+            reference,
+            "CurveCount",
+            meta.curve_count,
+            curves.len(),
+        );
+        self.check_count(
+            path,
+            reference,
+            "TotalSegmentCount",
+            meta.total_segment_count,
+            segment_count,
+        );
+        self.check_count(
+            path,
+            reference,
+            "TotalPointCount",
+            meta.total_point_count,
+            point_count,
+        );
+        self.check_count(
+            path,
+            reference,
+            "UserDataCount",
+            meta.user_data_count,
+            motion3.user_data.len(),
+        );
+        // The size is the total of the value strings in bytes.
+        self.check_count(
+            path,
+            reference,
+            "TotalUserDataSize",
+            meta.total_user_data_size,
+            motion3.user_data.iter().map(|data| data.value.len()).sum(),
+        );
+
+        let curve_ids = |target| {
+            curves
+                .iter()
+                .filter(move |curve| curve.target == target)
+                .map(|curve| &*curve.id)
+        };
+        self.check_ids(path, reference, IdKind::Parameter, curve_ids("Parameter"));
+        self.check_ids(path, reference, IdKind::Part, curve_ids("PartOpacity"));
+
+        for curve in curves.iter().filter(|curve| curve.target == "Parameter") {
+            // Bezier control points may lie outside of the range, only the
+            // points the curve passes through are checked.
+            let mut values = curve.segments.iter().flat_map(|seg| {
+                let (first, last) = match seg {
+                    Segment::Linear(p0, p1) => (p0.value, p1.value),
+                    Segment::Bezier([p0, _, _, p3]) => (p0.value, p3.value),
+                    Segment::Stepped(p0, _) => (p0.value, p0.value),
+                    Segment::InverseStepped(_, p1) => (p1.value, p1.value),
+                };
+                std::iter::once(first).chain(std::iter::once(last))
+            });
+            // Report only the first violation of every curve.
+            if let Some(value) = values.find(|&value| self.range_of(&curve.id, value).is_some()) {
+                self.check_range(path, reference, &curve.id, value);
+            }
+        }
+    }
+
+    fn check_physics(&mut self, path: &Path, phys3: &Physics3) {
+        let reference = &FileReference::Physics;
+        let settings = &phys3.physics_settings;
+        let meta = &phys3.meta;
+        let count = |f: fn(&crate::json::physics::PhysicsSetting) -> usize| {
+            settings.iter().map(f).sum::<usize>()
+        };
+        self.check_count(
+            path,
+            reference,
+            "PhysicsSettingCount",
+            meta.physics_setting_count,
+            settings.len(),
+        );
+        self.check_count(
+            path,
+            reference,
+            "TotalInputCount",
+            meta.total_input_count,
+            count(|setting| setting.input.len()),
+        );
+        self.check_count(
+            path,
+            reference,
+            "TotalOutputCount",
+            meta.total_output_count,
+            count(|setting| setting.output.len()),
+        );
+        self.check_count(
+            path,
+            reference,
+            "VertexCount",
+            meta.vertex_count,
+            count(|setting| setting.vertices.len()),
+        );
+
+        let ids = settings.iter().flat_map(|setting| {
+            let inputs = setting.input.iter().map(|input| &*input.source.id);
+            let outputs = setting.output.iter().map(|output| &*output.destination.id);
+            inputs.chain(outputs)
+        });
+        self.check_ids(path, reference, IdKind::Parameter, ids);
+    }
+
+    fn check_count(
+        &mut self,
+        path: &Path,
+        reference: &FileReference,
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    ) {
+        if expected != actual {
+            // Too small counts overflow the preallocated buffers of the SDK,
+            // too large ones merely waste memory.
+            let severity = if actual > expected {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            self.report.push(
+                severity,
+                Issue::MetaCount {
+                    path: self.source.full_path(path),
+                    reference: reference.clone(),
+                    field,
+                    expected,
+                    actual,
+                },
+            );
+        }
+    }
+
+    fn check_ids<'i>(
+        &mut self,
+        path: &Path,
+        reference: &FileReference,
+        kind: IdKind,
+        ids: impl Iterator<Item = &'i str>,
+    ) {
+        let model = match self.model() {
+            Some(model) => model,
+            None => return,
+        };
+        let known = match kind {
+            IdKind::Parameter => model.parameter_ids(),
+            IdKind::Part => model.part_ids(),
+            IdKind::Drawable => model.drawable_ids(),
+        };
+        let mut unresolved: Vec<&str> = ids.filter(|id| !known.contains(id)).collect();
+        unresolved.sort_unstable();
+        unresolved.dedup();
+        for id in unresolved {
+            self.error(CubismError::UnresolvedId {
+                path: self.source.full_path(path),
+                reference: Some(reference.clone()),
+                kind,
+                id: id.to_owned(),
+            });
+        }
+    }
+
+    // Returns the range of the parameter if the value lies outside of it.
+    fn range_of(&self, id: &str, value: f32) -> Option<(f32, f32)> {
+        let param = self.model()?.parameter(id)?;
+        if value < param.min_value || param.max_value < value {
+            Some((param.min_value, param.max_value))
+        } else {
+            None
+        }
+    }
+
+    fn check_range(&mut self, path: &Path, reference: &FileReference, id: &str, value: f32) {
+        if let Some((min, max)) = self.range_of(id, value) {
+            self.report.push(
+                Severity::Warning,
+                Issue::OutOfRange {
+                    path: self.source.full_path(path),
+                    reference: reference.clone(),
+                    id: id.to_owned(),
+                    value,
+                    min,
+                    max,
+                },
+            );
+        }
+    }
+
+    fn error(&mut self, e: CubismError) {
+        self.report.push(Severity::Error, Issue::Load(e));
+    }
+
+    fn warning(&mut self, e: CubismError) {
+        self.report.push(Severity::Warning, Issue::Load(e));
+    }
+}
+
+#[test]
+fn validate_samples() {
+    use std::str::FromStr;
+    let path = match crate::json::samples_dir() {
+        Some(path) => path,
+        None => return,
+    };
+    for model in &["Haru", "Hiyori", "Mark", "Natori"] {
+        let base = path.join(model);
+        let model3_path = base.join([model, ".model3.json"].concat());
+        let model3 = Model3::from_str(
+            &std::fs::read_to_string(&model3_path)
+                .unwrap_or_else(|e| panic!("error while reading {:?}: {:?}", &model3_path, e)),
+        )
+        .unwrap();
+        let report = validate(&base, &model3);
+        assert!(!report.has_errors(), "{:?}:\n{}", model3_path, report);
+    }
+}
+
+#[test]
+fn validate_fake_model_issues() {
+    use crate::asset::MemorySource;
+    use cubism_core::{DrawableDescription, ModelDescription, ParameterDescription};
+    use std::str::FromStr;
+
+    let model = Model::from_description(
+        ModelDescription::new()
+            .with_parameter(ParameterDescription::new("ParamAngleX", -30.0, 30.0, 0.0))
+            .with_drawable(DrawableDescription::new("ArtMesh1", vec![[0.0, 0.0]; 3])),
+    );
+    let mut source = MemorySource::new();
+    source.insert(
+        "model/smile.exp3.json",
+        &br#"{
+            "Type": "Live2D Expression",
+            "Parameters": [{ "Id": "ParamAngleX", "Value": 45.0, "Blend": "Overwrite" }]
+        }"#[..],
+    );
+    source.insert(
+        "model/idle.motion3.json",
+        &br#"{
+            "Version": 3,
+            "Meta": {
+                "Duration": 1.0,
+                "Fps": 30.0,
+                "Loop": true,
+                "CurveCount": 1,
+                "TotalSegmentCount": 1,
+                "TotalPointCount": 1,
+                "UserDataCount": 1,
+                "TotalUserDataSize": 3
+            },
+            "Curves": [
+                { "Target": "Parameter", "Id": "ParamMissing", "Segments": [0.0, 0.0, 0, 1.0, 0.0] },
+                { "Target": "Parameter", "Id": "ParamAngleX", "Segments": [0.0, 0.0, 0, 1.0, 0.0] }
+            ],
+            "UserData": [{ "Time": 0.5, "Value": "blink" }]
+        }"#[..],
+    );
+    source.insert(
+        "model/model.userdata3.json",
+        &br#"{
+            "Version": 3,
+            "Meta": { "UserDataCount": 1, "TotalUserDataSize": 8 },
+            "UserData": [{ "Target": "ArtMesh", "Id": "ArtMesh1", "Value": "body" }]
+        }"#[..],
+    );
+    let model3 = Model3::from_str(
+        r#"{
+            "Version": 3,
+            "FileReferences": {
+                "Moc": "model.moc3",
+                "Textures": [],
+                "Physics": "model.physics3.json",
+                "UserData": "model.userdata3.json",
+                "Expressions": [{ "Name": "smile", "File": "smile.exp3.json" }],
+                "Motions": { "Idle": [{ "File": "idle.motion3.json" }] }
+            }
+        }"#,
+    )
+    .unwrap();
+    let report = validate_with_model(&Prefixed::new("model", &source), &model3, &model);
+
+    let motion = FileReference::Motion {
+        group: "Idle".to_owned(),
+        index: 0,
+    };
+    let mut issues = report.diagnostics().iter();
+    match issues.next().map(|d| (d.severity, &d.issue)) {
+        Some((
+            Severity::Warning,
+            Issue::OutOfRange {
+                path, id, value, ..
+            },
+        )) => {
+            assert_eq!(path, Path::new("model/smile.exp3.json"));
+            assert_eq!((&**id, *value), ("ParamAngleX", 45.0));
+        },
+        issue => panic!("expected an out of range value, got {:?}", issue),
+    }
+    match issues.next().map(|d| (d.severity, &d.issue)) {
+        Some((
+            Severity::Error,
+            Issue::MetaCount {
+                reference,
+                field,
+                expected,
+                actual,
+                ..
+            },
+        )) => {
+            assert_eq!(reference, &motion);
+            assert_eq!((*field, *expected, *actual), ("CurveCount", 1, 2));
+        },
+        issue => panic!("expected a meta count mismatch, got {:?}", issue),
+    }
+    // the segment and point counts are off as well, the user data is larger
+    // than stated
+    let mut counts = Vec::new();
+    let mut issues = issues.peekable();
+    while let Some(Issue::MetaCount {
+        field,
+        expected,
+        actual,
+        ..
+    }) = issues.peek().map(|d| &d.issue)
+    {
+        counts.push((*field, *expected, *actual));
+        issues.next();
+    }
+    assert_eq!(
+        counts,
+        [
+            ("TotalSegmentCount", 1, 2),
+            ("TotalPointCount", 1, 4),
+            ("TotalUserDataSize", 3, 5),
+        ]
+    );
+    match issues.next().map(|d| (d.severity, &d.issue)) {
+        Some((
+            Severity::Error,
+            Issue::Load(CubismError::UnresolvedId {
+                reference,
+                kind,
+                id,
+                ..
+            }),
+        )) => {
+            assert_eq!(reference.as_ref(), Some(&motion));
+            assert_eq!((*kind, &**id), (IdKind::Parameter, "ParamMissing"));
+        },
+        issue => panic!("expected an unresolved id, got {:?}", issue),
+    }
+    match issues.next().map(|d| (d.severity, &d.issue)) {
+        Some((
+            Severity::Error,
+            Issue::Load(CubismError::ReadFile {
+                path, reference, ..
+            }),
+        )) => {
+            assert_eq!(path, Path::new("model/model.physics3.json"));
+            assert_eq!(reference, &Some(FileReference::Physics));
+        },
+        issue => panic!("expected a missing file, got {:?}", issue),
+    }
+    // a too large size only wastes memory
+    match issues.next().map(|d| (d.severity, &d.issue)) {
+        Some((
+            Severity::Warning,
+            Issue::MetaCount {
+                reference,
+                field,
+                expected,
+                actual,
+                ..
+            },
+        )) => {
+            assert_eq!(reference, &FileReference::UserData);
+            assert_eq!((*field, *expected, *actual), ("TotalUserDataSize", 8, 4));
+        },
+        issue => panic!("expected a meta count mismatch, got {:?}", issue),
+    }
+    assert!(issues.next().is_none());
+}